@@ -40,6 +40,9 @@ mkdir -p -m 640 ${RPM_BUILD_ROOT}/usr/share/man/man1
 install -m 0750 target/release/fim ${RPM_BUILD_ROOT}%{_bindir}/
 install -m 0640 config/linux/config.yml ${RPM_BUILD_ROOT}%{_configdir}/
 install -m 0640 config/index_template.json ${RPM_BUILD_ROOT}%{_configdir}/
+install -m 0640 config/event_schema.json ${RPM_BUILD_ROOT}%{_configdir}/
+install -m 0640 config/linux/rules.yml ${RPM_BUILD_ROOT}%{_configdir}/
+install -m 0640 config/linux/fim-plugin.conf ${RPM_BUILD_ROOT}%{_configdir}/
 install -m 0644 pkg/fim.service ${RPM_BUILD_ROOT}/lib/systemd/system/
 install -m 0644 pkg/fim.1 ${RPM_BUILD_ROOT}/usr/share/man/man1/
 
@@ -67,6 +70,15 @@ fi
 # -----------------------------------------------------------------------------
 
 %postun
+if [ $1 = 0 ];then
+    # Remove persistent audit rules managed by FIM
+    if [ -f /etc/audit/rules.d/fim.rules ]; then
+        rm -f /etc/audit/rules.d/fim.rules
+        if command -v augenrules > /dev/null 2>&1; then
+            augenrules --load > /dev/null 2>&1 || true
+        fi
+    fi
+fi
 
 # -----------------------------------------------------------------------------
 
@@ -93,6 +105,9 @@ rm -fr %{buildroot}
 %dir %attr(750, root, root) %{_configdir}
 %attr(640, root, root) %config(noreplace) %{_configdir}/config.yml
 %attr(640, root, root) %{_configdir}/index_template.json
+%attr(640, root, root) %{_configdir}/event_schema.json
+%attr(640, root, root) %config(noreplace) %{_configdir}/rules.yml
+%attr(640, root, root) %{_configdir}/fim-plugin.conf
 %attr(644, root, root) /lib/systemd/system/fim.service
 %attr(644, root, root) /usr/share/man/man1/fim.1.gz
 
@@ -0,0 +1,197 @@
+// Copyright (C) 2023, Achiefs.
+
+// Global constants definitions
+pub const PROC_PATH: &str = "/proc";
+// Seconds to remember processes that are not in /proc anymore
+pub const ANCESTRY_CACHE_TTL: u64 = 60;
+// Maximum number of ancestors reported
+pub const ANCESTRY_MAX_DEPTH: usize = 16;
+// Key and syscall of the audit rule that records the process executions
+pub const EXECVE_KEY: &str = "fim_execve";
+pub const EXECVE_SYSCALL: &str = "execve";
+
+// To read process information
+use std::fs;
+// To store the known processes
+use std::collections::HashMap;
+// To log the program process
+use log::debug;
+// To output the ancestry in the events
+use serde::Serialize;
+
+// ----------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Process {
+    pub pid: u32,
+    pub ppid: u32,
+    pub exe: String,
+    pub uid: Option<u32>
+}
+
+// ----------------------------------------------------------------------------
+
+// Process tree lookup, processes are read from /proc and remembered for a
+// while so the parents of short lived processes can still be reported
+pub struct Tracker {
+    proc_path: String,
+    ttl: u64,
+    // Process and the last second it was seen
+    processes: HashMap<u32, (Process, u64)>
+}
+
+impl Tracker {
+    pub fn new() -> Self {
+        Tracker {
+            proc_path: String::from(PROC_PATH),
+            ttl: ANCESTRY_CACHE_TTL,
+            processes: HashMap::new()
+        }
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Remember a process seen in an audit event
+    pub fn record(&mut self, process: Process, time: u64) {
+        if process.pid == 0 { return }
+        self.processes.insert(process.pid, (process, time));
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Ancestors of a process starting with its parent, up to init
+    pub fn get_ancestry(&mut self, ppid: u32, time: u64) -> Vec<Process> {
+        let ttl = self.ttl;
+        self.processes.retain(|_, (_, seen)| time.saturating_sub(*seen) <= ttl);
+        let mut ancestry: Vec<Process> = Vec::new();
+        let mut pid = ppid;
+        while ancestry.len() < ANCESTRY_MAX_DEPTH && pid != 0 {
+            let process = match self.get_process(pid, time) {
+                Some(process) => process,
+                None => {
+                    debug!("Process '{}' not found, ancestry incomplete", pid);
+                    break;
+                }
+            };
+            // Avoid loops of reused pids
+            if ancestry.iter().any(|p| p.pid == process.ppid) { break }
+            pid = process.ppid;
+            ancestry.push(process);
+        }
+        ancestry
+    }
+
+    // ------------------------------------------------------------------------
+
+    fn get_process(&mut self, pid: u32, time: u64) -> Option<Process> {
+        match read_process(&self.proc_path, pid) {
+            Some(process) => {
+                self.record(process.clone(), time);
+                Some(process)
+            },
+            None => self.processes.get(&pid).map(|(process, _)| process.clone())
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+impl Default for Tracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Read the parent, executable and real uid of a process from /proc
+pub fn read_process(proc_path: &str, pid: u32) -> Option<Process> {
+    let stat = fs::read_to_string(format!("{}/{}/stat", proc_path, pid)).ok()?;
+    // Command name is between parenthesis and could contain spaces
+    let ppid = stat.rsplit_once(')')?.1.split_whitespace().nth(1)?.parse().ok()?;
+    let status = fs::read_to_string(format!("{}/{}/status", proc_path, pid)).unwrap_or_default();
+    let uid = status.lines()
+        .find_map(|l| l.strip_prefix("Uid:"))
+        .and_then(|l| l.split_whitespace().next())
+        .and_then(|uid| uid.parse().ok());
+    // Kernel threads and processes of other users could not have exe
+    let exe = fs::read_link(format!("{}/{}/exe", proc_path, pid))
+        .map(|e| String::from(e.to_string_lossy()))
+        .unwrap_or_default();
+    Some(Process { pid, ppid, exe, uid })
+}
+
+// ----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_process(proc_path: &str, pid: &str, ppid: &str, exe: &str) {
+        let folder = format!("{}/{}", proc_path, pid);
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(format!("{}/stat", folder),
+            format!("{} (my app) S {} 100 100 0 -1 4194560", pid, ppid)).unwrap();
+        fs::write(format!("{}/status", folder),
+            "Name:\tapp\nUid:\t33\t33\t33\t33\nGid:\t33\t33\t33\t33\n").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(exe, format!("{}/exe", folder)).unwrap();
+    }
+
+    // ------------------------------------------------------------------------
+
+    fn create_test_tracker(proc_path: &str) -> Tracker {
+        Tracker {
+            proc_path: String::from(proc_path),
+            ttl: 60,
+            processes: HashMap::new()
+        }
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_read_process() {
+        let proc_path = "test_ancestry_read";
+        create_process(proc_path, "200", "100", "/usr/sbin/nginx");
+        let process = read_process(proc_path, 200).unwrap();
+        assert_eq!(process.pid, 200);
+        assert_eq!(process.ppid, 100);
+        assert_eq!(process.uid, Some(33));
+        #[cfg(unix)]
+        assert_eq!(process.exe, "/usr/sbin/nginx");
+        assert_eq!(read_process(proc_path, 300), None);
+        fs::remove_dir_all(proc_path).unwrap();
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[cfg(unix)]
+    #[test]
+    fn test_get_ancestry() {
+        let proc_path = "test_ancestry_tree";
+        create_process(proc_path, "1", "0", "/usr/lib/systemd/systemd");
+        create_process(proc_path, "100", "1", "/usr/sbin/nginx");
+        create_process(proc_path, "200", "100", "/usr/bin/bash");
+        let mut tracker = create_test_tracker(proc_path);
+
+        let ancestry = tracker.get_ancestry(200, 10);
+        let exes: Vec<&str> = ancestry.iter().map(|p| p.exe.as_str()).collect();
+        assert_eq!(exes, vec!["/usr/bin/bash", "/usr/sbin/nginx", "/usr/lib/systemd/systemd"]);
+        assert_eq!(ancestry[0].pid, 200);
+        assert_eq!(ancestry[0].ppid, 100);
+
+        // Exited processes are taken from the cache until they expire
+        fs::remove_dir_all(format!("{}/200", proc_path)).unwrap();
+        assert_eq!(tracker.get_ancestry(200, 20).len(), 3);
+        assert!(tracker.get_ancestry(200, 100).is_empty());
+
+        // Processes recorded from execve audit events
+        tracker.record(Process { pid: 300, ppid: 100,
+            exe: String::from("/usr/bin/sh"), uid: Some(33) }, 100);
+        assert_eq!(tracker.get_ancestry(300, 110).len(), 3);
+        tracker.record(Process { pid: 0, ppid: 100, exe: String::new(), uid: None }, 100);
+        assert!(!tracker.processes.contains_key(&0));
+        fs::remove_dir_all(proc_path).unwrap();
+    }
+}
@@ -0,0 +1,255 @@
+// Copyright (C) 2023, Achiefs.
+
+// Global constants definitions
+// Milliseconds of audit time to wait for the records of an event
+pub const ASSEMBLY_TIMEOUT: u128 = 2000;
+
+// To manage readed data into collection
+use std::collections::HashMap;
+// To complete idle events on quiet hosts
+use std::time::{Duration, Instant};
+// To log the program process
+use log::debug;
+
+// Defined type to simplify syntax
+type SHashMap = HashMap<String, String>;
+
+// ----------------------------------------------------------------------------
+
+// Records received of a single audit event
+struct Group {
+    timestamp: u128,
+    // Arrival of the last record
+    updated: Instant,
+    records: Vec<SHashMap>
+}
+
+// ----------------------------------------------------------------------------
+
+// Group audit records by their 'msg=audit(timestamp:serial)' identifier,
+// records of different events can arrive interleaved
+pub struct Assembler {
+    timeout: u128,
+    groups: HashMap<String, Group>,
+    // Order of arrival, to emit expired events in order
+    order: Vec<String>
+}
+
+impl Assembler {
+    pub fn new() -> Self {
+        Assembler {
+            timeout: ASSEMBLY_TIMEOUT,
+            groups: HashMap::new(),
+            order: Vec::new()
+        }
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Add a parsed audit record, returns the events completed by it. An event
+    // is completed by EOE or PROCTITLE records (audit.log does not store EOE)
+    // or when newer records exceed the timeout.
+    pub fn push(&mut self, record: SHashMap) -> Vec<Vec<SHashMap>> {
+        let (id, timestamp) = match record.get("msg").and_then(|m| get_audit_id(m)) {
+            Some(value) => value,
+            None => {
+                debug!("Audit record without identifier discarded");
+                return Vec::new()
+            }
+        };
+        let mut completed = self.expire(timestamp);
+        let record_type = record.get("type").cloned().unwrap_or_default();
+
+        if ! self.groups.contains_key(&id) {
+            self.order.push(id.clone());
+            self.groups.insert(id.clone(), Group { timestamp, updated: Instant::now(), records: Vec::new() });
+        }
+        let group = self.groups.get_mut(&id).unwrap();
+        group.updated = Instant::now();
+        if record_type != "EOE" {
+            group.records.push(record);
+        }
+        if record_type == "EOE" || record_type == "PROCTITLE" {
+            completed.extend(self.remove(&id));
+        }
+        completed
+    }
+
+    // ------------------------------------------------------------------------
+
+    pub fn pending(&self) -> usize {
+        self.groups.len()
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Emit all the pending events in order, used at the end of a finite input
+    pub fn flush(&mut self) -> Vec<Vec<SHashMap>> {
+        let order = std::mem::take(&mut self.order);
+        order.iter().filter_map(|id| self.groups.remove(id).map(|g| g.records)).collect()
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Emit events older than timeout compared to the given audit timestamp
+    fn expire(&mut self, timestamp: u128) -> Vec<Vec<SHashMap>> {
+        let expired: Vec<String> = self.order.iter()
+            .filter(|id| timestamp.saturating_sub(self.groups[*id].timestamp) > self.timeout)
+            .cloned().collect();
+        expired.iter().filter_map(|id| {
+            debug!("Audit event '{}' completed by timeout", id);
+            self.remove(id)
+        }).collect()
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Emit events that received no records during the timeout until 'now',
+    // newer records may never arrive on a quiet host
+    pub fn expire_idle(&mut self, now: Instant) -> Vec<Vec<SHashMap>> {
+        let timeout = Duration::from_millis(self.timeout as u64);
+        let expired: Vec<String> = self.order.iter()
+            .filter(|id| now.saturating_duration_since(self.groups[*id].updated) > timeout)
+            .cloned().collect();
+        expired.iter().filter_map(|id| {
+            debug!("Audit event '{}' completed by idle timeout", id);
+            self.remove(id)
+        }).collect()
+    }
+
+    // ------------------------------------------------------------------------
+
+    fn remove(&mut self, id: &str) -> Option<Vec<SHashMap>> {
+        self.order.retain(|o| o != id);
+        self.groups.remove(id).map(|g| g.records)
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+impl Default for Assembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Get the identifier and timestamp in milliseconds of 'audit(1659026449.689:6434):'
+pub fn get_audit_id(msg: &str) -> Option<(String, u128)> {
+    let id = msg.strip_prefix("audit(")?.split(')').next()?;
+    let (timestamp, _serial) = id.split_once(':')?;
+    let (seconds, millis) = timestamp.split_once('.')?;
+    let time = seconds.parse::<u128>().ok()? * 1000 + millis.parse::<u128>().ok()?;
+    Some((String::from(id), time))
+}
+
+// ----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_record(record_type: &str, id: &str) -> SHashMap {
+        HashMap::from([
+            (String::from("type"), String::from(record_type)),
+            (String::from("msg"), format!("audit({}):", id))
+        ])
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_get_audit_id() {
+        assert_eq!(get_audit_id("audit(1659026449.689:6434):"),
+            Some((String::from("1659026449.689:6434"), 1659026449689)));
+        assert_eq!(get_audit_id("audit(1659026449.689:6434)"),
+            Some((String::from("1659026449.689:6434"), 1659026449689)));
+        assert_eq!(get_audit_id("audit(1659026449:6434):"), None);
+        assert_eq!(get_audit_id("UNKNOWN"), None);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_push_interleaved() {
+        let mut assembler = Assembler::new();
+        assert!(assembler.push(create_record("SYSCALL", "10.000:1")).is_empty());
+        assert!(assembler.push(create_record("SYSCALL", "10.001:2")).is_empty());
+        assert!(assembler.push(create_record("CWD", "10.000:1")).is_empty());
+        assert!(assembler.push(create_record("PATH", "10.001:2")).is_empty());
+        assert_eq!(assembler.pending(), 2);
+
+        let events = assembler.push(create_record("PROCTITLE", "10.001:2"));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].len(), 3);
+        assert_eq!(events[0][0]["type"], "SYSCALL");
+        assert_eq!(events[0][1]["type"], "PATH");
+        assert_eq!(events[0][2]["type"], "PROCTITLE");
+
+        let events = assembler.push(create_record("EOE", "10.000:1"));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].len(), 2);
+        assert_eq!(events[0][1]["type"], "CWD");
+        assert_eq!(assembler.pending(), 0);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_push_timeout() {
+        let mut assembler = Assembler::new();
+        assert!(assembler.push(create_record("SYSCALL", "10.000:1")).is_empty());
+        assert!(assembler.push(create_record("SYSCALL", "11.000:2")).is_empty());
+        let events = assembler.push(create_record("SYSCALL", "12.500:3"));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0][0]["msg"], "audit(10.000:1):");
+        assert_eq!(assembler.pending(), 2);
+
+        let events = assembler.push(create_record("SYSCALL", "20.000:4"));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0][0]["msg"], "audit(11.000:2):");
+        assert_eq!(events[1][0]["msg"], "audit(12.500:3):");
+        assert_eq!(assembler.pending(), 1);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_expire_idle() {
+        let mut assembler = Assembler::new();
+        assembler.push(create_record("SYSCALL", "10.000:1"));
+        let now = Instant::now();
+        assert!(assembler.expire_idle(now).is_empty());
+        assembler.push(create_record("SYSCALL", "10.001:2"));
+        let events = assembler.expire_idle(now + Duration::from_millis(ASSEMBLY_TIMEOUT as u64 + 100));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0][0]["msg"], "audit(10.000:1):");
+        assert_eq!(assembler.pending(), 0);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_flush() {
+        let mut assembler = Assembler::new();
+        assembler.push(create_record("SYSCALL", "10.000:1"));
+        assembler.push(create_record("SYSCALL", "10.001:2"));
+        assembler.push(create_record("PATH", "10.000:1"));
+        let events = assembler.flush();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].len(), 2);
+        assert_eq!(events[1][0]["msg"], "audit(10.001:2):");
+        assert_eq!(assembler.pending(), 0);
+        assert!(assembler.flush().is_empty());
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_push_invalid() {
+        let mut assembler = Assembler::new();
+        assert!(assembler.push(HashMap::new()).is_empty());
+        assert_eq!(assembler.pending(), 0);
+    }
+}
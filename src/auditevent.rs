@@ -2,19 +2,14 @@
 
 // To implement Debug and fmt method
 use std::fmt;
-// To handle files
-use std::fs::OpenOptions;
-use std::io::Write;
-// Handle time intervals
-use std::time::Duration;
-// To log the program procedure
-use log::*;
-// To handle JSON objects
-use serde_json::{json, to_string};
-// To manage HTTP requests
-use reqwest::Client;
+// To parse numeric fields
+use std::str::FromStr;
+// To serialize the events
+use serde::Serialize;
 // To use HashMap
 use std::collections::HashMap;
+// To handle the configured paths
+use yaml_rust::yaml::Array;
 
 
 // To get configuration constants
@@ -23,9 +18,26 @@ use crate::config;
 use crate::utils;
 // To manage checksums and conversions
 use crate::hash;
+// To translate syscall numbers into names
+use crate::syscalls;
+// To get the arguments of EXECVE records
+use crate::logreader;
+// To identify the events of monitor paths with whodata
+use crate::whodata;
+// To decode the mode of files
+use crate::filemode;
+// To parse SELinux and AppArmor labels
+use crate::seccontext;
+// To number the produced events
+use crate::sequence;
+// To output the events
+use crate::fimevent::FimEvent;
+// To report the parents of the process
+use crate::ancestry;
 
 // ----------------------------------------------------------------------------
 
+#[derive(Clone, Default, Serialize)]
 pub struct Event {
     pub id: String,
     pub timestamp: String,
@@ -41,11 +53,11 @@ pub struct Event {
     pub system: String,
     pub command: String,
 
-    pub ogid: String,
+    pub ogid: Option<u32>,
     pub rdev: String,
     pub proctitle: String,
     pub cap_fver: String,
-    pub inode: String,
+    pub inode: Option<u64>,
     pub cap_fp: String,
     pub cap_fe: String,
     pub item: String,
@@ -53,373 +65,338 @@ pub struct Event {
     pub dev: String,
     pub mode: String,
     pub cap_frootid: String,
-    pub ouid: String,
+    pub ouid: Option<u32>,
     pub paths: Vec<HashMap<String, String>>,
     pub cwd: String,
     pub syscall: String,
-    pub ppid: String,
+    pub ppid: Option<u32>,
     pub comm: String,
-    pub fsuid: String,
-    pub pid: String,
+    pub fsuid: Option<u32>,
+    pub pid: Option<u32>,
     pub a0: String,
     pub a1: String,
     pub a2: String,
     pub a3: String,
     pub arch: String,
-    pub auid: String,
-    pub items: String,
-    pub gid: String,
-    pub euid: String,
-    pub sgid: String,
-    pub uid: String,
+    pub auid: Option<u32>,
+    pub items: Option<u32>,
+    pub gid: Option<u32>,
+    pub euid: Option<u32>,
+    pub sgid: Option<u32>,
+    pub uid: Option<u32>,
     pub tty: String,
     pub success: String,
-    pub exit: String,
-    pub ses: String,
+    pub exit: Option<i64>,
+    pub ses: Option<u32>,
     pub key: String,
-    pub suid: String,
-    pub egid: String,
-    pub fsgid: String,
+    pub suid: Option<u32>,
+    pub egid: Option<u32>,
+    pub fsgid: Option<u32>,
     pub exe: String,
+    pub entropy: f64,
+    pub file_type: String,
+    pub file_type_changed: bool,
+    pub severity: String,
+    pub rule_id: String,
+    pub rule_description: String,
+    pub tags: Vec<String>,
+    pub expected: bool,
+    pub package: String,
+    pub package_version: String,
+    pub uid_name: String,
+    pub euid_name: String,
+    pub auid_name: String,
+    pub ouid_name: String,
+    pub gid_name: String,
+    pub egid_name: String,
+    pub ogid_name: String,
+    pub syscall_name: String,
+    pub arch_name: String,
+    pub action: String,
+    pub argv: Vec<String>,
+    pub process_ancestry: Vec<ancestry::Process>,
+    pub inode_type: String,
+    pub permissions: String,
+    pub setuid: bool,
+    pub setgid: bool,
+    pub sticky: bool,
+    pub subj: String,
+    pub subj_user: String,
+    pub subj_role: String,
+    pub subj_type: String,
+    pub subj_level: String,
+    pub obj: String,
+    pub obj_user: String,
+    pub obj_role: String,
+    pub obj_type: String,
+    pub obj_level: String,
+    #[serde(rename = "@timestamp")]
+    pub iso_timestamp: String,
+    pub serial: String,
+    pub sequence: u64,
     pub source: String,
 }
 
 impl Event {
-    pub fn new() -> Self {
-        let empty = String::from("0");
-        Event{
-            id: empty.clone(), timestamp: empty.clone(), hostname: empty.clone(),
-            node: empty.clone(), version: empty.clone(), path: empty.clone(),
-            file: empty.clone(), labels: Vec::new(), operation: empty.clone(),
-            checksum: empty.clone(), fpid: 0, system: empty.clone(),
-            command: empty.clone(), ogid: empty.clone(), rdev: empty.clone(),
-            proctitle: empty.clone(), cap_fver: empty.clone(),
-            inode: empty.clone(), cap_fp: empty.clone(), cap_fe: empty.clone(),
-            item: empty.clone(), cap_fi: empty.clone(), dev: empty.clone(),
-            mode: empty.clone(), cap_frootid: empty.clone(), ouid: empty.clone(),
-            paths: Vec::new(), cwd: empty.clone(), syscall: empty.clone(),
-            ppid: empty.clone(), comm: empty.clone(), fsuid: empty.clone(),
-            pid: empty.clone(), a0: empty.clone(), a1: empty.clone(),
-            a2: empty.clone(), a3: empty.clone(), arch: empty.clone(),
-            auid: empty.clone(), items: empty.clone(), gid: empty.clone(),
-            euid: empty.clone(), sgid: empty.clone(), uid: empty.clone(),
-            tty: empty.clone(), success: empty.clone(), exit: empty.clone(),
-            ses: empty.clone(), key: empty.clone(), suid: empty.clone(),
-            egid: empty.clone(), fsgid: empty.clone(), exe: empty.clone(),
-            source: empty,
-        }
-    }
-
-    // ------------------------------------------------------------------------
-
     pub fn from(syscall: HashMap<String, String>,
         cwd: HashMap<String, String>, proctitle: HashMap<String, String>,
-        paths: Vec<HashMap<String, String>>,
+        execve: HashMap<String, String>, paths: Vec<HashMap<String, String>>,
         config: config::Config) -> Self {
 
-        let parent = get_parent(paths.clone(),  cwd["cwd"].as_str(), config.clone());
-        let path = get_item_path(paths.clone(), cwd["cwd"].as_str(), config.clone());
-
-        let command = if proctitle["proctitle"].contains('/') ||
-            proctitle["proctitle"].contains("bash") {
-            proctitle["proctitle"].clone()
+        // Whodata events belong to monitor paths
+        let watched = match syscall.get("key") {
+            Some(key) if key == whodata::WHODATA_KEY => config.monitor.clone(),
+            _ => config.audit.clone()
+        };
+        let parent = get_parent(paths.clone(), cwd["cwd"].as_str(), config.clone(), &watched);
+        let path = get_item_path(paths.clone(), cwd["cwd"].as_str(), config.clone(), &watched);
+
+        // Records of events completed by timeout or at the end of the input
+        // can be missing, PROCTITLE is usually the last one
+        let title = proctitle.get("proctitle").cloned().unwrap_or_default();
+        let msg = proctitle.get("msg").or(syscall.get("msg")).cloned().unwrap_or_default();
+
+        // Arguments of EXECVE are complete, proctitle is truncated by the kernel
+        let argv = logreader::get_argv(&execve);
+        let command = if argv.is_empty() {
+            title.clone()
         }else{
-            hash::hex_to_ascii(proctitle["proctitle"].clone())
+            argv.join(" ")
         };
 
-        let clean_timestamp: String = String::from(proctitle["msg"].clone()
+        let clean_timestamp: String = String::from(msg.clone()
             .replace("audit(", "")
             .replace('.', "")
             .split(':').collect::<Vec<&str>>()[0]); // Getting the 13 digits timestamp
 
-        let event_path = parent["name"].clone();
-        let index = config.get_index(event_path.as_str(),
-            cwd["cwd"].as_str(), config.audit.clone().to_vec());
-        let labels = config.get_labels(index, config.audit.clone());
+        let event_path = parent.get("name").cloned().unwrap_or_default();
+        let item_name = path.get("name").cloned().unwrap_or_default();
+        let (checksum, entropy, file_type) = hash::get_file_info(format!("{}/{}",
+            event_path, item_name),
+            config.events_max_file_checksum);
+        let index = config.get_audit_index(&get_field(&syscall, "key"),
+            event_path.as_str(), cwd["cwd"].as_str());
+        let syscall_name = syscalls::get_syscall_name(&get_field(&syscall, "arch"),
+            &get_field(&syscall, "syscall"));
+        let arch_name = syscalls::get_arch_name(&get_field(&syscall, "arch"));
+        let create = syscalls::is_create(&syscall_name, &syscall, &get_field(&path, "nametype"));
+        let action = syscalls::get_action(&syscall_name, &get_field(&syscall, "success"),
+            &get_field(&syscall, "exit"), create);
+        let action = match seccontext::is_capability_change(&action, &get_field(&syscall, "comm"),
+            &command, &path, &format!("{}/{}", event_path, item_name)) {
+            true => seccontext::get_capability_action(&action),
+            false => action
+        };
+        let subj = syscall.get("subj").cloned().unwrap_or_default();
+        let subj_context = seccontext::parse_context(&subj);
+        let obj = path.get("obj").cloned().unwrap_or_default();
+        let obj_context = seccontext::parse_context(&obj);
+        // Decode the mode after the change, chmod carries the new one in its arguments
+        let previous_mode = filemode::parse_mode(&get_field(&path, "mode"));
+        let current_mode = match (previous_mode, action.as_str()) {
+            (Some(mode), "chmod") => filemode::apply_chmod(mode, &syscall_name, &syscall).or(previous_mode),
+            _ => previous_mode
+        };
+        let file_mode = match current_mode {
+            Some(mode) => filemode::decode(mode),
+            None => filemode::FileMode { inode_type: String::new(), permissions: String::new(),
+                setuid: false, setgid: false, sticky: false }
+        };
+        let risky = match (action.as_str(), current_mode) {
+            ("chmod", Some(mode)) => filemode::adds_risky_bits(previous_mode, mode),
+            ("create", Some(mode)) => filemode::adds_risky_bits(None, mode),
+            _ => false
+        };
+        let tags = match risky {
+            true => vec![String::from(filemode::RISKY_PERMISSIONS_TAG)],
+            false => Vec::new()
+        };
+        let labels = if index == usize::MAX { Vec::new() }
+            else { config.get_labels(index, config.audit.clone()) };
 
         Event{
             id: utils::get_uuid(),
-            proctitle: proctitle["proctitle"].clone(),
+            proctitle: title,
             command,
-            timestamp: clean_timestamp,
+            timestamp: clean_timestamp.clone(),
             hostname: utils::get_hostname(),
             node: config.node,
             version: String::from(config::VERSION),
             labels,
             operation: utils::get_field(path.clone(), "nametype"),
             path: utils::clean_path(&event_path),
-            file: utils::get_filename_path(path["name"].clone().as_str()),
-            checksum: hash::get_checksum(format!("{}/{}",
-                parent["name"].clone(), path["name"].clone()),
-                config.events_max_file_checksum),
+            file: match item_name.is_empty() {
+                true => String::new(),
+                false => utils::get_filename_path(item_name.as_str())
+            },
+            checksum,
             fpid: utils::get_pid(),
             system: utils::get_os(),
 
 
-            ogid: get_field(path.clone(), "ogid"),
-            rdev: get_field(path.clone(), "rdev"),
-            cap_fver: get_field(path.clone(), "cap_fver"),
-            inode: get_field(path.clone(), "inode"),
-            cap_fp: get_field(path.clone(), "cap_fp"),
-            cap_fe: get_field(path.clone(), "cap_fe"),
-            item: get_field(path.clone(), "item"),
-            cap_fi: get_field(path.clone(), "cap_fi"),
-            dev: get_field(path.clone(), "dev"),
-            mode: get_field(path.clone(), "mode"),
-            cap_frootid: get_field(path.clone(), "cap_frootid"),
-            ouid: get_field(path.clone(), "ouid"),
+            ogid: get_number(&path, "ogid"),
+            rdev: get_field(&path, "rdev"),
+            cap_fver: get_field(&path, "cap_fver"),
+            inode: get_number(&path, "inode"),
+            cap_fp: get_field(&path, "cap_fp"),
+            cap_fe: get_field(&path, "cap_fe"),
+            item: get_field(&path, "item"),
+            cap_fi: get_field(&path, "cap_fi"),
+            dev: get_field(&path, "dev"),
+            mode: get_field(&path, "mode"),
+            cap_frootid: get_field(&path, "cap_frootid"),
+            ouid: get_number(&path, "ouid"),
 
             paths,
             cwd: cwd["cwd"].clone(),
 
-            syscall: syscall["syscall"].clone(),
-            ppid: syscall["ppid"].clone(),
-            comm: syscall["comm"].clone(),
-            fsuid: syscall["fsuid"].clone(),
-            pid: syscall["pid"].clone(),
-            a0: syscall["a0"].clone(),
-            a1: syscall["a1"].clone(),
-            a2: syscall["a2"].clone(),
-            a3: syscall["a3"].clone(),
-            arch: syscall["arch"].clone(),
-            auid: syscall["auid"].clone(),
-            items: syscall["items"].clone(),
-            gid: syscall["gid"].clone(),
-            euid: syscall["euid"].clone(),
-            sgid: syscall["sgid"].clone(),
-            uid: syscall["uid"].clone(),
-            tty: syscall["tty"].clone(),
-            success: syscall["success"].clone(),
-            exit: syscall["exit"].clone(),
-            ses: syscall["ses"].clone(),
-            key: syscall["key"].clone(),
-            suid: syscall["suid"].clone(),
-            egid: syscall["egid"].clone(),
-            fsgid: syscall["fsgid"].clone(),
-            exe: syscall["exe"].clone(),
+            syscall: get_field(&syscall, "syscall"),
+            ppid: get_number(&syscall, "ppid"),
+            comm: get_field(&syscall, "comm"),
+            fsuid: get_number(&syscall, "fsuid"),
+            pid: get_number(&syscall, "pid"),
+            a0: get_field(&syscall, "a0"),
+            a1: get_field(&syscall, "a1"),
+            a2: get_field(&syscall, "a2"),
+            a3: get_field(&syscall, "a3"),
+            arch: get_field(&syscall, "arch"),
+            auid: get_number(&syscall, "auid"),
+            items: get_number(&syscall, "items"),
+            gid: get_number(&syscall, "gid"),
+            euid: get_number(&syscall, "euid"),
+            sgid: get_number(&syscall, "sgid"),
+            uid: get_number(&syscall, "uid"),
+            tty: get_field(&syscall, "tty"),
+            success: get_field(&syscall, "success"),
+            exit: get_number(&syscall, "exit"),
+            ses: get_number(&syscall, "ses"),
+            key: get_field(&syscall, "key"),
+            suid: get_number(&syscall, "suid"),
+            egid: get_number(&syscall, "egid"),
+            fsgid: get_number(&syscall, "fsgid"),
+            exe: get_field(&syscall, "exe"),
+            entropy,
+            file_type,
+            file_type_changed: false,
+            severity: String::new(),
+            rule_id: String::new(),
+            rule_description: String::new(),
+            tags,
+            expected: false,
+            package: String::new(),
+            package_version: String::new(),
+            uid_name: String::new(),
+            euid_name: String::new(),
+            auid_name: String::new(),
+            ouid_name: String::new(),
+            gid_name: String::new(),
+            egid_name: String::new(),
+            ogid_name: String::new(),
+            syscall_name,
+            arch_name,
+            action,
+            argv,
+            process_ancestry: Vec::new(),
+            inode_type: file_mode.inode_type,
+            permissions: file_mode.permissions,
+            setuid: file_mode.setuid,
+            setgid: file_mode.setgid,
+            sticky: file_mode.sticky,
+            subj,
+            subj_user: subj_context.user,
+            subj_role: subj_context.role,
+            subj_type: subj_context.context_type,
+            subj_level: subj_context.level,
+            obj,
+            obj_user: obj_context.user,
+            obj_role: obj_context.role,
+            obj_type: obj_context.context_type,
+            obj_level: obj_context.level,
+            iso_timestamp: utils::get_iso_timestamp(clean_timestamp.parse::<u128>().unwrap_or(0)),
+            serial: sequence::get_audit_serial(&msg),
+            sequence: 0,
             source: String::from("audit")
         }
     }
 
     // ------------------------------------------------------------------------
 
-    pub fn clone(&self) -> Self {
+    // Event of the execve rule, only the process is decoded because it is
+    // used to follow the process tree and it is not reported
+    pub fn from_execve(syscall: HashMap<String, String>) -> Self {
         Event {
-            id: self.id.clone(),
-            timestamp: self.timestamp.clone(),
-            hostname: self.hostname.clone(),
-            node: self.node.clone(),
-            version: self.version.clone(),
-            path: self.path.clone(),
-            file: self.file.clone(),
-            labels: self.labels.clone(),
-            operation: self.operation.clone(),
-            checksum: self.checksum.clone(),
-            fpid: self.fpid,
-            system: self.system.clone(),
-            command: self.command.clone(),
-            ogid: self.ogid.clone(),
-            rdev: self.rdev.clone(),
-            proctitle: self.proctitle.clone(),
-            cap_fver: self.cap_fver.clone(),
-            inode: self.inode.clone(),
-            cap_fp: self.cap_fp.clone(),
-            cap_fe: self.cap_fe.clone(),
-            item: self.item.clone(),
-            cap_fi: self.cap_fi.clone(),
-            dev: self.dev.clone(),
-            mode: self.mode.clone(),
-            cap_frootid: self.cap_frootid.clone(),
-            ouid: self.ouid.clone(),
-            paths: self.paths.clone(),
-            cwd: self.cwd.clone(),
-            syscall: self.syscall.clone(),
-            ppid: self.ppid.clone(),
-            comm: self.comm.clone(),
-            fsuid: self.fsuid.clone(),
-            pid: self.pid.clone(),
-            a0: self.a0.clone(),
-            a1: self.a1.clone(),
-            a2: self.a2.clone(),
-            a3: self.a3.clone(),
-            arch: self.arch.clone(),
-            auid: self.auid.clone(),
-            items: self.items.clone(),
-            gid: self.gid.clone(),
-            euid: self.euid.clone(),
-            sgid: self.sgid.clone(),
-            uid: self.uid.clone(),
-            tty: self.tty.clone(),
-            success: self.success.clone(),
-            exit: self.exit.clone(),
-            ses: self.ses.clone(),
-            key: self.key.clone(),
-            suid: self.suid.clone(),
-            egid: self.egid.clone(),
-            fsgid: self.fsgid.clone(),
-            exe: self.exe.clone(),
-            source: self.source.clone(),
+            pid: get_number(&syscall, "pid"),
+            ppid: get_number(&syscall, "ppid"),
+            uid: get_number(&syscall, "uid"),
+            exe: get_field(&syscall, "exe"),
+            key: get_field(&syscall, "key"),
+            source: String::from("audit"),
+            ..Default::default()
         }
     }
 
     // ------------------------------------------------------------------------
 
     pub fn is_empty(&self) -> bool { self.path == *"" }
+}
 
-    // ------------------------------------------------------------------------
-
-    fn get_json(&self) -> serde_json::Value {
-        json!({
-            "id": self.id.clone(),
-            "timestamp": self.timestamp.clone(),
-            "hostname": self.hostname.clone(),
-            "node": self.node.clone(),
-            "version": self.version.clone(),
-            "path": self.path.clone(),
-            "file": self.file.clone(),
-            "labels": self.labels.clone(),
-            "operation": self.operation.clone(),
-            "checksum": self.checksum.clone(),
-            "fpid": self.fpid.clone(),
-            "system": self.system.clone(),
-            "command": self.command.clone(),
-
-            "ogid": self.ogid.clone(),
-            "rdev": self.rdev.clone(),
-            "proctitle": self.proctitle.clone(),
-            "cap_fver": self.cap_fver.clone(),
-            "inode": self.inode.clone(),
-            "cap_fp": self.cap_fp.clone(),
-            "cap_fe": self.cap_fe.clone(),
-            "item": self.item.clone(),
-            "cap_fi": self.cap_fi.clone(),
-            "dev": self.dev.clone(),
-            "mode": self.mode.clone(),
-            "cap_frootid": self.cap_frootid.clone(),
-            "ouid": self.ouid.clone(),
-            "paths": self.paths.clone(),
-            "cwd": self.cwd.clone(),
-            "syscall": self.syscall.clone(),
-            "ppid": self.ppid.clone(),
-            "comm": self.comm.clone(),
-            "fsuid": self.fsuid.clone(),
-            "pid": self.pid.clone(),
-            "a0": self.a0.clone(),
-            "a1": self.a1.clone(),
-            "a2": self.a2.clone(),
-            "a3": self.a3.clone(),
-            "arch": self.arch.clone(),
-            "auid": self.auid.clone(),
-            "items": self.items.clone(),
-            "gid": self.gid.clone(),
-            "euid": self.euid.clone(),
-            "sgid": self.sgid.clone(),
-            "uid": self.uid.clone(),
-            "tty": self.tty.clone(),
-            "success": self.success.clone(),
-            "exit": self.exit.clone(),
-            "ses": self.ses.clone(),
-            "key": self.key.clone(),
-            "suid": self.suid.clone(),
-            "egid": self.egid.clone(),
-            "fsgid": self.fsgid.clone(),
-            "exe": self.exe.clone(),
-            "source": self.source.clone()
-        })
-    }
-
-    // ------------------------------------------------------------------------
+// ----------------------------------------------------------------------------
 
-    // Get formatted string with all required data
-    fn format_json(&self) -> String { to_string(&self.get_json()).unwrap() }
+impl FimEvent for Event {
+    fn get_id(&self) -> &str { &self.id }
 
     // ------------------------------------------------------------------------
 
-    // Function to write the received events to file
-    pub fn log(&self, file: &str){
-        let mut events_file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .append(true)
-            .open(file)
-            .expect("(auditevent::log) Unable to open events log file.");
-
-            match writeln!(events_file, "{}", self.format_json()) {
-                Ok(_d) => debug!("Audit event log written"),
-                Err(e) => error!("Audit event could not be written, Err: [{}]", e)
-            };
-    }
+    fn set_sequence(&mut self, sequence: u64) { self.sequence = sequence }
+}
 
-    // ------------------------------------------------------------------------
+// ----------------------------------------------------------------------------
 
-    // Function to send events through network
-    pub async fn send(&self, index: String, address: String, user: String, pass: String, insecure: bool) {
-        let data = self.get_json();
-
-        let request_url = format!("{}/{}/_doc/{}", address, index, self.id);
-        let client = Client::builder()
-            .danger_accept_invalid_certs(insecure)
-            .timeout(Duration::from_secs(30))
-            .build().unwrap();
-        match client
-            .post(request_url)
-            .basic_auth(user, Some(pass))
-            .json(&data)
-            .send()
-            .await{
-            Ok(response) => debug!("Response received: {:?}", response),
-            Err(e) => debug!("Error on request: {:?}", e)
-        };
+fn get_field(map: &HashMap<String, String>, field: &str) -> String {
+    match map.get(field) {
+        Some(value) => value.clone(),
+        None => String::from("UNKNOWN")
     }
+}
 
-    // ------------------------------------------------------------------------
+// ----------------------------------------------------------------------------
 
-    // Function to manage event destination
-    pub async fn process(&self, destination: &str, index_name: String, config: config::Config){
-        match destination {
-            config::BOTH_MODE => {
-                self.log(&config.events_file);
-                self.send( index_name, config.endpoint_address, config.endpoint_user, config.endpoint_pass, config.insecure).await;
-            },
-            config::NETWORK_MODE => {
-                self.send( index_name, config.endpoint_address, config.endpoint_user, config.endpoint_pass, config.insecure).await;
-            },
-            _ => self.log(&config.events_file)
-        }
-    }
+// Numeric value of a record field, None if it is missing or not a number
+fn get_number<T: FromStr>(map: &HashMap<String, String>, field: &str) -> Option<T> {
+    map.get(field).and_then(|value| value.parse::<T>().ok())
 }
 
 // ----------------------------------------------------------------------------
-fn get_field(map: HashMap<String, String>,field: &str) -> String {
-    if map.contains_key(field) {
-        map[field].clone()
-    }else{
-        String::from("UNKNOWN")
-    }
+
+pub fn get_parent(paths: Vec<HashMap<String, String>>, cwd: &str, config: config::Config, watched: &Array) -> HashMap<String, String> {
+    find_path(&paths, cwd, &config, watched, true)
+        .or_else(|| find_path(&paths, cwd, &config, watched, false))
+        .unwrap_or_default()
 }
 
 // ----------------------------------------------------------------------------
 
-pub fn get_parent(paths: Vec<HashMap<String, String>>, cwd: &str, config: config::Config) -> HashMap<String, String> {
-    match paths.iter().find(|p|{
-        utils::get_field((*p).clone(), "nametype") == "PARENT" &&
-        config.path_in(p["name"].as_str(), cwd, config.audit.clone())
-    }){
-        Some(p) => p.clone(),
-        None => get_item_path(paths.clone(), cwd, config.clone())
-    }
+pub fn get_item_path(paths: Vec<HashMap<String, String>>, cwd: &str, config: config::Config, watched: &Array) -> HashMap<String, String> {
+    find_path(&paths, cwd, &config, watched, false)
+        .or_else(|| find_path(&paths, cwd, &config, watched, true))
+        .unwrap_or_default()
 }
 
 // ----------------------------------------------------------------------------
 
-pub fn get_item_path(paths: Vec<HashMap<String, String>>, cwd: &str, config: config::Config) -> HashMap<String, String> {
-    match paths.iter().rfind(|p|{
-        utils::get_field((*p).clone(), "nametype") != "PARENT" &&
-        utils::get_field((*p).clone(), "nametype") != "UNKNOWN" &&
-        config.path_in(p["name"].as_str(), cwd, config.audit.clone())
-    }){
-        Some(p) => p.clone(),
-        None => get_parent(paths.clone(), cwd, config.clone())
+// Find the watched PARENT path record or the last watched item record
+fn find_path(paths: &[HashMap<String, String>], cwd: &str, config: &config::Config, watched: &Array, parent: bool) -> Option<HashMap<String, String>> {
+    let is_watched = |p: &&HashMap<String, String>| {
+        let nametype = utils::get_field((*p).clone(), "nametype");
+        let matches = match parent {
+            true => nametype == "PARENT",
+            false => nametype != "PARENT" && nametype != "UNKNOWN"
+        };
+        matches && config.path_in(p.get("name").map(|n| n.as_str()).unwrap_or_default(), cwd, watched.clone())
+    };
+    match parent {
+        true => paths.iter().find(is_watched).cloned(),
+        false => paths.iter().rfind(is_watched).cloned()
     }
 }
 
@@ -485,6 +462,8 @@ mod tests {
     use crate::config::Config;
     use tokio_test::block_on;
     use std::fs;
+    use crate::schema;
+    use crate::ecs;
 
     // ------------------------------------------------------------------------
 
@@ -500,26 +479,66 @@ mod tests {
             file: String::from(""), labels: Vec::new(),
             operation: String::from(""), checksum: String::from(""), fpid: 0,
             system: String::from(""), command: String::from(""),
-            ogid: String::from(""), rdev: String::from(""),
+            ogid: None, rdev: String::from(""),
             proctitle: String::from(""), cap_fver: String::from(""),
-            inode: String::from(""), cap_fp: String::from(""),
+            inode: None, cap_fp: String::from(""),
             cap_fe: String::from(""), item: String::from(""),
             cap_fi: String::from(""), dev: String::from(""),
             mode: String::from(""), cap_frootid: String::from(""),
-            ouid: String::from(""), paths: Vec::new(),
+            ouid: None, paths: Vec::new(),
             cwd: String::from(""), syscall: String::from(""),
-            ppid: String::from(""), comm: String::from(""),
-            fsuid: String::from(""), pid: String::from(""),
+            ppid: None, comm: String::from(""),
+            fsuid: None, pid: None,
             a0: String::from(""), a1: String::from(""),
             a2: String::from(""), a3: String::from(""),
-            arch: String::from(""), auid: String::from(""),
-            items: String::from(""), gid: String::from(""),
-            euid: String::from(""), sgid: String::from(""),
-            uid: String::from(""), tty: String::from(""),
-            success: String::from(""), exit: String::from(""),
-            ses: String::from(""), key: String::from(""),
-            suid: String::from(""), egid: String::from(""),
-            fsgid: String::from(""), exe: String::from(""),
+            arch: String::from(""), auid: None,
+            items: None, gid: None,
+            euid: None, sgid: None,
+            uid: None, tty: String::from(""),
+            success: String::from(""), exit: None,
+            ses: None, key: String::from(""),
+            suid: None, egid: None,
+            fsgid: None, exe: String::from(""),
+            entropy: 0.0,
+            file_type: String::from(""),
+            file_type_changed: false,
+            severity: String::from(""),
+            rule_id: String::from(""),
+            rule_description: String::from(""),
+            tags: Vec::new(),
+            expected: false,
+            package: String::from(""),
+            package_version: String::from(""),
+            uid_name: String::from(""),
+            euid_name: String::from(""),
+            auid_name: String::from(""),
+            ouid_name: String::from(""),
+            gid_name: String::from(""),
+            egid_name: String::from(""),
+            ogid_name: String::from(""),
+            syscall_name: String::from(""),
+            arch_name: String::from(""),
+            action: String::from(""),
+            argv: Vec::new(),
+            process_ancestry: Vec::new(),
+            inode_type: String::from(""),
+            permissions: String::from(""),
+            setuid: false,
+            setgid: false,
+            sticky: false,
+            subj: String::from(""),
+            subj_user: String::from(""),
+            subj_role: String::from(""),
+            subj_type: String::from(""),
+            subj_level: String::from(""),
+            obj: String::from(""),
+            obj_user: String::from(""),
+            obj_role: String::from(""),
+            obj_type: String::from(""),
+            obj_level: String::from(""),
+            iso_timestamp: String::from(""),
+            serial: String::from(""),
+            sequence: 0,
             source: String::from("")
         }
     }
@@ -533,26 +552,67 @@ mod tests {
             operation: String::from("OPERATION"), checksum: String::from("CHECKSUM"),
             fpid: 0,
             system: String::from("SYSTEM"), command: String::from("COMMAND"),
-            ogid: String::from("OGID"), rdev: String::from("RDEV"),
+            ogid: Some(1), rdev: String::from("RDEV"),
             proctitle: String::from("PROCTITLE"), cap_fver: String::from("CAP_FVER"),
-            inode: String::from("INODE"), cap_fp: String::from("CAP_FP"),
+            inode: Some(2), cap_fp: String::from("CAP_FP"),
             cap_fe: String::from("CAP_FE"), item: String::from("ITEM"),
             cap_fi: String::from("CAP_FI"), dev: String::from("DEV"),
             mode: String::from("MODE"), cap_frootid: String::from("CAP_FROOTID"),
-            ouid: String::from("OUID"), paths: Vec::new(),
+            ouid: Some(3), paths: Vec::new(),
             cwd: String::from("CWD"), syscall: String::from("SYSCALL"),
-            ppid: String::from("PPID"), comm: String::from("COMM"),
-            fsuid: String::from("FSUID"), pid: String::from("PID"),
+            ppid: Some(4), comm: String::from("COMM"),
+            fsuid: Some(5), pid: Some(6),
             a0: String::from("A0"), a1: String::from("A1"),
             a2: String::from("A2"), a3: String::from("A3"),
-            arch: String::from("ARCH"), auid: String::from("AUID"),
-            items: String::from("ITEMS"), gid: String::from("GID"),
-            euid: String::from("EUID"), sgid: String::from("SGID"),
-            uid: String::from("UID"), tty: String::from("TTY"),
-            success: String::from("SUCCESS"), exit: String::from("EXIT"),
-            ses: String::from("SES"), key: String::from("KEY"),
-            suid: String::from("SUID"), egid: String::from("EGID"),
-            fsgid: String::from("FSGID"), exe: String::from("EXE"),
+            arch: String::from("ARCH"), auid: Some(7),
+            items: Some(15), gid: Some(8),
+            euid: Some(9), sgid: Some(10),
+            uid: Some(11), tty: String::from("TTY"),
+            success: String::from("SUCCESS"), exit: Some(-13),
+            ses: Some(16), key: String::from("KEY"),
+            suid: Some(12), egid: Some(13),
+            fsgid: Some(14), exe: String::from("EXE"),
+            entropy: 0.5,
+            file_type: String::from("FILE_TYPE"),
+            file_type_changed: false,
+            severity: String::from("low"),
+            rule_id: String::from("100001"),
+            rule_description: String::from("Test rule"),
+            tags: vec![String::from("test")],
+            expected: true,
+            package: String::from("PACKAGE"),
+            package_version: String::from("PACKAGE_VERSION"),
+            uid_name: String::from("UID_NAME"),
+            euid_name: String::from("EUID_NAME"),
+            auid_name: String::from("AUID_NAME"),
+            ouid_name: String::from("OUID_NAME"),
+            gid_name: String::from("GID_NAME"),
+            egid_name: String::from("EGID_NAME"),
+            ogid_name: String::from("OGID_NAME"),
+            syscall_name: String::from("SYSCALL_NAME"),
+            arch_name: String::from("ARCH_NAME"),
+            action: String::from("ACTION"),
+            argv: vec![String::from("ls"), String::from("-l")],
+            process_ancestry: vec![ancestry::Process { pid: 1, ppid: 0,
+                exe: String::from("/usr/lib/systemd/systemd"), uid: Some(0) }],
+            inode_type: String::from("INODE_TYPE"),
+            permissions: String::from("PERMISSIONS"),
+            setuid: false,
+            setgid: false,
+            sticky: false,
+            subj: String::from("SUBJ"),
+            subj_user: String::from("SUBJ_USER"),
+            subj_role: String::from("SUBJ_ROLE"),
+            subj_type: String::from("SUBJ_TYPE"),
+            subj_level: String::from("SUBJ_LEVEL"),
+            obj: String::from("OBJ"),
+            obj_user: String::from("OBJ_USER"),
+            obj_role: String::from("OBJ_ROLE"),
+            obj_type: String::from("OBJ_TYPE"),
+            obj_level: String::from("OBJ_LEVEL"),
+            iso_timestamp: String::from("ISO_TIMESTAMP"),
+            serial: String::from("SERIAL"),
+            sequence: 0,
             source: String::from("SOURCE")
         }
     }
@@ -565,29 +625,29 @@ mod tests {
             let config = Config::new(&utils::get_os(), None);
             let syscall = HashMap::<String, String>::from([
                 (String::from("syscall"), String::from("syscall")),
-                (String::from("ppid"), String::from("ppid")),
+                (String::from("ppid"), String::from("4")),
                 (String::from("comm"), String::from("comm")),
-                (String::from("fsuid"), String::from("fsuid")),
-                (String::from("pid"), String::from("pid")),
+                (String::from("fsuid"), String::from("5")),
+                (String::from("pid"), String::from("6")),
                 (String::from("a0"), String::from("a0")),
                 (String::from("a1"), String::from("a1")),
                 (String::from("a2"), String::from("a2")),
                 (String::from("a3"), String::from("a3")),
                 (String::from("arch"), String::from("arch")),
-                (String::from("auid"), String::from("auid")),
-                (String::from("items"), String::from("items")),
-                (String::from("gid"), String::from("gid")),
-                (String::from("euid"), String::from("euid")),
-                (String::from("sgid"), String::from("sgid")),
-                (String::from("uid"), String::from("uid")),
+                (String::from("auid"), String::from("7")),
+                (String::from("items"), String::from("2")),
+                (String::from("gid"), String::from("8")),
+                (String::from("euid"), String::from("9")),
+                (String::from("sgid"), String::from("10")),
+                (String::from("uid"), String::from("11")),
                 (String::from("tty"), String::from("tty")),
                 (String::from("success"), String::from("success")),
-                (String::from("exit"), String::from("exit")),
-                (String::from("ses"), String::from("ses")),
+                (String::from("exit"), String::from("-13")),
+                (String::from("ses"), String::from("807")),
                 (String::from("key"), String::from("key")),
-                (String::from("suid"), String::from("suid")),
-                (String::from("egid"), String::from("egid")),
-                (String::from("fsgid"), String::from("fsgid")),
+                (String::from("suid"), String::from("12")),
+                (String::from("egid"), String::from("13")),
+                (String::from("fsgid"), String::from("14")),
                 (String::from("exe"), String::from("exe"))
             ]);
 
@@ -606,10 +666,10 @@ mod tests {
                 HashMap::<String, String>::from([
                     (String::from("nametype"), String::from("nametype")),
                     (String::from("name"), String::from("/tmp")),
-                    (String::from("ogid"), String::from("ogid")),
+                    (String::from("ogid"), String::from("1")),
                     (String::from("rdev"), String::from("rdev")),
                     (String::from("cap_fver"), String::from("cap_fver")),
-                    (String::from("inode"), String::from("inode")),
+                    (String::from("inode"), String::from("2")),
                     (String::from("cap_fp"), String::from("cap_fp")),
                     (String::from("cap_fe"), String::from("cap_fe")),
                     (String::from("item"), String::from("item")),
@@ -617,7 +677,7 @@ mod tests {
                     (String::from("dev"), String::from("dev")),
                     (String::from("mode"), String::from("mode")),
                     (String::from("cap_frootid"), String::from("cap_frootid")),
-                    (String::from("ouid"), String::from("ouid")),
+                    (String::from("ouid"), String::from("3")),
 
                 ])
             ]);
@@ -625,10 +685,10 @@ mod tests {
             /*let path = HashMap::<String, String>::from([
                 (String::from("nametype"), String::from("nametype")),
                 (String::from("name"), String::from("name")),
-                (String::from("ogid"), String::from("ogid")),
+                (String::from("ogid"), String::from("1")),
                 (String::from("rdev"), String::from("rdev")),
                 (String::from("cap_fver"), String::from("cap_fver")),
-                (String::from("inode"), String::from("inode")),
+                (String::from("inode"), String::from("2")),
                 (String::from("cap_fp"), String::from("cap_fp")),
                 (String::from("cap_fe"), String::from("cap_fe")),
                 (String::from("item"), String::from("item")),
@@ -636,15 +696,17 @@ mod tests {
                 (String::from("dev"), String::from("dev")),
                 (String::from("mode"), String::from("mode")),
                 (String::from("cap_frootid"), String::from("cap_frootid")),
-                (String::from("ouid"), String::from("ouid")),
+                (String::from("ouid"), String::from("3")),
             ]);*/
 
             let proctitle = HashMap::<String, String>::from([
-                (String::from("proctitle"), String::from("736564002D6900737C68656C6C6F7C4849217C670066696C6531302E747874")),
+                (String::from("proctitle"), String::from("sed -i s|hello|HI!|g file10.txt")),
                 (String::from("msg"), String::from("audit(1659026449.689:6434)"))
             ]);
 
-            let event = Event::from(syscall.clone(), cwd.clone(), proctitle, paths.clone(), config.clone());
+            let event = Event::from(syscall.clone(), cwd.clone(), proctitle, HashMap::new(),
+                paths.clone(), config.clone());
+            assert_eq!(schema::validate(&event.get_json()), Vec::<String>::new());
             assert_eq!(String::from("1659026449689"), event.timestamp);
             assert_eq!(utils::get_hostname(), event.hostname);
             assert_eq!(String::from("FIM"), event.node);
@@ -655,55 +717,69 @@ mod tests {
             //assert_eq!(..., event.parent);
             assert_eq!(String::from("nametype"), event.operation);
             assert_eq!(String::from("UNKNOWN"), event.checksum);
+            assert_eq!(0.0, event.entropy);
+            assert_eq!(String::from("UNKNOWN"), event.file_type);
+            assert!(!event.file_type_changed);
             assert_eq!(utils::get_pid(), event.fpid);
             assert_eq!(utils::get_os(), event.system);
             assert_eq!(String::from("sed -i s|hello|HI!|g file10.txt"), event.command);
-            assert_eq!(String::from("ogid"), event.ogid);
+            assert_eq!(Some(1), event.ogid);
             assert_eq!(String::from("rdev"), event.rdev);
-            assert_eq!(String::from("736564002D6900737C68656C6C6F7C4849217C670066696C6531302E747874"), event.proctitle);
+            assert_eq!(String::from("sed -i s|hello|HI!|g file10.txt"), event.proctitle);
             assert_eq!(String::from("cap_fver"), event.cap_fver);
-            assert_eq!(String::from("inode"), event.inode);
+            assert_eq!(Some(2), event.inode);
             assert_eq!(String::from("cap_fp"), event.cap_fp);
             assert_eq!(String::from("cap_fe"), event.cap_fe);
             assert_eq!(String::from("item"), event.item);
             assert_eq!(String::from("cap_fi"), event.cap_fi);
             assert_eq!(String::from("dev"), event.dev);
             assert_eq!(String::from("mode"), event.mode);
-            assert_eq!(String::from("ouid"), event.ouid);
+            assert_eq!(Some(3), event.ouid);
             assert_eq!(String::from("cwd"), event.cwd);
             assert_eq!(String::from("syscall"), event.syscall);
-            assert_eq!(String::from("ppid"), event.ppid);
+            assert_eq!(Some(4), event.ppid);
             assert_eq!(String::from("comm"), event.comm);
-            assert_eq!(String::from("fsuid"), event.fsuid);
-            assert_eq!(String::from("pid"), event.pid);
+            assert_eq!(Some(5), event.fsuid);
+            assert_eq!(Some(6), event.pid);
             assert_eq!(String::from("a0"), event.a0);
             assert_eq!(String::from("a1"), event.a1);
             assert_eq!(String::from("a2"), event.a2);
             assert_eq!(String::from("a3"), event.a3);
             assert_eq!(String::from("arch"), event.arch);
-            assert_eq!(String::from("auid"), event.auid);
-            assert_eq!(String::from("items"), event.items);
-            assert_eq!(String::from("gid"), event.gid);
-            assert_eq!(String::from("euid"), event.euid);
-            assert_eq!(String::from("sgid"), event.sgid);
-            assert_eq!(String::from("uid"), event.uid);
+            assert_eq!(Some(7), event.auid);
+            assert_eq!(Some(2), event.items);
+            assert_eq!(Some(8), event.gid);
+            assert_eq!(Some(9), event.euid);
+            assert_eq!(Some(10), event.sgid);
+            assert_eq!(Some(11), event.uid);
             assert_eq!(String::from("tty"), event.tty);
             assert_eq!(String::from("success"), event.success);
-            assert_eq!(String::from("exit"), event.exit);
-            assert_eq!(String::from("ses"), event.ses);
+            assert_eq!(Some(-13), event.exit);
+            assert_eq!(Some(807), event.ses);
             assert_eq!(String::from("key"), event.key);
-            assert_eq!(String::from("suid"), event.suid);
-            assert_eq!(String::from("egid"), event.egid);
-            assert_eq!(String::from("fsgid"), event.fsgid);
+            assert_eq!(Some(12), event.suid);
+            assert_eq!(Some(13), event.egid);
+            assert_eq!(Some(14), event.fsgid);
             assert_eq!(String::from("exe"), event.exe);
             assert_eq!(String::from("audit"), event.source);
+            assert_eq!(String::new(), event.syscall_name);
+            assert_eq!(String::new(), event.arch_name);
+            assert_eq!(String::new(), event.action);
 
             let proctitle = HashMap::<String, String>::from([
                 (String::from("proctitle"), String::from("bash")),
                 (String::from("msg"), String::from("audit(1659026449.689:6434)"))
             ]);
-            let event = Event::from(syscall, cwd, proctitle, paths.clone(), config.clone());
+            let execve = HashMap::<String, String>::from([
+                (String::from("argc"), String::from("3")),
+                (String::from("a0"), String::from("bash")),
+                (String::from("a1"), String::from("-c")),
+                (String::from("a2"), String::from("echo 1 > /tmp/file"))
+            ]);
+            let event = Event::from(syscall, cwd, proctitle, execve, paths.clone(), config.clone());
             assert_eq!(String::from("bash"), event.proctitle);
+            assert_eq!(vec!["bash", "-c", "echo 1 > /tmp/file"], event.argv);
+            assert_eq!(String::from("bash -c echo 1 > /tmp/file"), event.command);
 
         }
     }
@@ -768,17 +844,78 @@ mod tests {
         assert_eq!(event.egid, cloned.egid);
         assert_eq!(event.fsgid, cloned.fsgid);
         assert_eq!(event.exe, cloned.exe);
+        assert_eq!(event.entropy, cloned.entropy);
+        assert_eq!(event.file_type, cloned.file_type);
+        assert_eq!(event.file_type_changed, cloned.file_type_changed);
+        assert_eq!(event.severity, cloned.severity);
+        assert_eq!(event.rule_id, cloned.rule_id);
+        assert_eq!(event.rule_description, cloned.rule_description);
+        assert_eq!(event.tags, cloned.tags);
+        assert_eq!(event.expected, cloned.expected);
+        assert_eq!(event.package, cloned.package);
+        assert_eq!(event.package_version, cloned.package_version);
+        assert_eq!(event.uid_name, cloned.uid_name);
+        assert_eq!(event.euid_name, cloned.euid_name);
+        assert_eq!(event.auid_name, cloned.auid_name);
+        assert_eq!(event.ouid_name, cloned.ouid_name);
+        assert_eq!(event.gid_name, cloned.gid_name);
+        assert_eq!(event.egid_name, cloned.egid_name);
+        assert_eq!(event.ogid_name, cloned.ogid_name);
+        assert_eq!(event.syscall_name, cloned.syscall_name);
+        assert_eq!(event.arch_name, cloned.arch_name);
+        assert_eq!(event.action, cloned.action);
+        assert_eq!(event.argv, cloned.argv);
+        assert_eq!(event.process_ancestry, cloned.process_ancestry);
+        assert_eq!(event.inode_type, cloned.inode_type);
+        assert_eq!(event.permissions, cloned.permissions);
+        assert_eq!(event.setuid, cloned.setuid);
+        assert_eq!(event.setgid, cloned.setgid);
+        assert_eq!(event.sticky, cloned.sticky);
+        assert_eq!(event.subj, cloned.subj);
+        assert_eq!(event.subj_user, cloned.subj_user);
+        assert_eq!(event.subj_role, cloned.subj_role);
+        assert_eq!(event.subj_type, cloned.subj_type);
+        assert_eq!(event.subj_level, cloned.subj_level);
+        assert_eq!(event.obj, cloned.obj);
+        assert_eq!(event.obj_user, cloned.obj_user);
+        assert_eq!(event.obj_role, cloned.obj_role);
+        assert_eq!(event.obj_type, cloned.obj_type);
+        assert_eq!(event.obj_level, cloned.obj_level);
+        assert_eq!(event.iso_timestamp, cloned.iso_timestamp);
+        assert_eq!(event.serial, cloned.serial);
+        assert_eq!(event.sequence, cloned.sequence);
         assert_eq!(event.source, cloned.source);
     }
 
     // ------------------------------------------------------------------------
 
+    #[test]
+    fn test_from_execve() {
+        let syscall = HashMap::from([
+            (String::from("type"), String::from("SYSCALL")),
+            (String::from("pid"), String::from("300")),
+            (String::from("ppid"), String::from("200")),
+            (String::from("uid"), String::from("33")),
+            (String::from("exe"), String::from("/usr/bin/sh")),
+            (String::from("key"), String::from("fim_execve"))
+        ]);
+        let event = Event::from_execve(syscall);
+        assert_eq!(event.pid, Some(300));
+        assert_eq!(event.ppid, Some(200));
+        assert_eq!(event.uid, Some(33));
+        assert_eq!(event.exe, "/usr/bin/sh");
+        assert_eq!(event.key, ancestry::EXECVE_KEY);
+        assert!(event.is_empty());
+    }
+
+    // ------------------------------------------------------------------------
+
     #[test]
     fn test_is_empty() {
         let empty = create_empty_event();
         let event = create_test_event();
-        assert_eq!(empty.is_empty(), true);
-        assert_eq!(event.is_empty(), false);
+        assert!(empty.is_empty());
+        assert!(!event.is_empty());
     }
 
     // ------------------------------------------------------------------------
@@ -796,14 +933,14 @@ mod tests {
         //assert_eq!(event["labels"], Vec::<String>::new());
         assert_eq!(event["operation"], "OPERATION");
         assert_eq!(event["checksum"], "CHECKSUM");
-        assert_eq!(event["fpid"], 0 as i8);
+        assert_eq!(event["fpid"], 0);
         assert_eq!(event["system"], "SYSTEM");
         assert_eq!(event["command"], "COMMAND");
-        assert_eq!(event["ogid"], "OGID");
+        assert_eq!(event["ogid"], 1);
         assert_eq!(event["rdev"], "RDEV");
         assert_eq!(event["proctitle"], "PROCTITLE");
         assert_eq!(event["cap_fver"], "CAP_FVER");
-        assert_eq!(event["inode"], "INODE");
+        assert_eq!(event["inode"], 2);
         assert_eq!(event["cap_fp"], "CAP_FP");
         assert_eq!(event["cap_fe"], "CAP_FE");
         assert_eq!(event["item"], "ITEM");
@@ -811,58 +948,130 @@ mod tests {
         assert_eq!(event["dev"], "DEV");
         assert_eq!(event["mode"], "MODE");
         assert_eq!(event["cap_frootid"], "CAP_FROOTID");
-        assert_eq!(event["ouid"], "OUID");
+        assert_eq!(event["ouid"], 3);
         //assert_eq!(event["parent"], HashMap::new());
         assert_eq!(event["cwd"], "CWD");
         assert_eq!(event["syscall"], "SYSCALL");
-        assert_eq!(event["ppid"], "PPID");
+        assert_eq!(event["ppid"], 4);
         assert_eq!(event["comm"], "COMM");
-        assert_eq!(event["fsuid"], "FSUID");
-        assert_eq!(event["pid"], "PID");
+        assert_eq!(event["fsuid"], 5);
+        assert_eq!(event["pid"], 6);
         assert_eq!(event["a0"], "A0");
         assert_eq!(event["a1"], "A1");
         assert_eq!(event["a2"], "A2");
         assert_eq!(event["a3"], "A3");
         assert_eq!(event["arch"], "ARCH");
-        assert_eq!(event["auid"], "AUID");
-        assert_eq!(event["items"], "ITEMS");
-        assert_eq!(event["gid"], "GID");
-        assert_eq!(event["euid"], "EUID");
-        assert_eq!(event["sgid"], "SGID");
-        assert_eq!(event["uid"], "UID");
+        assert_eq!(event["auid"], 7);
+        assert_eq!(event["items"], 15);
+        assert_eq!(event["gid"], 8);
+        assert_eq!(event["euid"], 9);
+        assert_eq!(event["sgid"], 10);
+        assert_eq!(event["uid"], 11);
         assert_eq!(event["tty"], "TTY");
         assert_eq!(event["success"], "SUCCESS");
-        assert_eq!(event["exit"], "EXIT");
-        assert_eq!(event["ses"], "SES");
+        assert_eq!(event["exit"], -13);
+        assert_eq!(event["ses"], 16);
         assert_eq!(event["key"], "KEY");
-        assert_eq!(event["suid"], "SUID");
-        assert_eq!(event["egid"], "EGID");
-        assert_eq!(event["fsgid"], "FSGID");
+        assert_eq!(event["suid"], 12);
+        assert_eq!(event["egid"], 13);
+        assert_eq!(event["fsgid"], 14);
         assert_eq!(event["exe"], "EXE");
+        assert_eq!(event["entropy"], 0.5);
+        assert_eq!(event["file_type"], "FILE_TYPE");
+        assert_eq!(event["file_type_changed"], false);
+        assert_eq!(event["severity"], "low");
+        assert_eq!(event["rule_id"], "100001");
+        assert_eq!(event["rule_description"], "Test rule");
+        assert_eq!(event["expected"], true);
+        assert_eq!(event["package"], "PACKAGE");
+        assert_eq!(event["package_version"], "PACKAGE_VERSION");
+        assert_eq!(event["uid_name"], "UID_NAME");
+        assert_eq!(event["euid_name"], "EUID_NAME");
+        assert_eq!(event["auid_name"], "AUID_NAME");
+        assert_eq!(event["ouid_name"], "OUID_NAME");
+        assert_eq!(event["gid_name"], "GID_NAME");
+        assert_eq!(event["egid_name"], "EGID_NAME");
+        assert_eq!(event["ogid_name"], "OGID_NAME");
+        assert_eq!(event["syscall_name"], "SYSCALL_NAME");
+        assert_eq!(event["arch_name"], "ARCH_NAME");
+        assert_eq!(event["action"], "ACTION");
+        assert_eq!(event["inode_type"], "INODE_TYPE");
+        assert_eq!(event["permissions"], "PERMISSIONS");
+        assert_eq!(event["setuid"], false);
+        assert_eq!(event["setgid"], false);
+        assert_eq!(event["sticky"], false);
+        assert_eq!(event["subj"], "SUBJ");
+        assert_eq!(event["subj_user"], "SUBJ_USER");
+        assert_eq!(event["subj_role"], "SUBJ_ROLE");
+        assert_eq!(event["subj_type"], "SUBJ_TYPE");
+        assert_eq!(event["subj_level"], "SUBJ_LEVEL");
+        assert_eq!(event["obj"], "OBJ");
+        assert_eq!(event["obj_user"], "OBJ_USER");
+        assert_eq!(event["obj_role"], "OBJ_ROLE");
+        assert_eq!(event["obj_type"], "OBJ_TYPE");
+        assert_eq!(event["obj_level"], "OBJ_LEVEL");
+        assert_eq!(event["@timestamp"], "ISO_TIMESTAMP");
+        assert_eq!(event["serial"], "SERIAL");
+        assert_eq!(event["sequence"], 0);
         assert_eq!(event["source"], "SOURCE");
     }
 
     // ------------------------------------------------------------------------
 
+    #[test]
+    fn test_get_json_schema() {
+        let json = create_test_event().get_json();
+        assert_eq!(json["schema_version"], schema::SCHEMA_VERSION);
+        assert_eq!(schema::validate(&json), Vec::<String>::new());
+        let ecs = create_test_event().get_output(config::ECS_FORMAT);
+        assert_eq!(ecs["ecs"]["version"], ecs::ECS_VERSION);
+        assert_eq!(ecs::validate(&ecs), Vec::<String>::new());
+    }
+
+    // ------------------------------------------------------------------------
+
     #[test]
     fn test_format_json() {
-        let json = create_test_event().format_json();
-        let string = String::from("{\"a0\":\"A0\",\"a1\":\"A1\",\"a2\":\"A2\",\
-            \"a3\":\"A3\",\"arch\":\"ARCH\",\"auid\":\"AUID\",\"cap_fe\":\"CAP_FE\",\
-            \"cap_fi\":\"CAP_FI\",\"cap_fp\":\"CAP_FP\",\"cap_frootid\":\"CAP_FROOTID\",\
-            \"cap_fver\":\"CAP_FVER\",\"checksum\":\"CHECKSUM\",\"comm\":\"COMM\",\
-            \"command\":\"COMMAND\",\"cwd\":\"CWD\",\"dev\":\"DEV\",\"egid\":\"EGID\",\
-            \"euid\":\"EUID\",\"exe\":\"EXE\",\"exit\":\"EXIT\",\"file\":\"FILE\",\
-            \"fpid\":0,\"fsgid\":\"FSGID\",\"fsuid\":\"FSUID\",\"gid\":\"GID\",\
-            \"hostname\":\"HOSTNAME\",\"id\":\"ID\",\"inode\":\"INODE\",\
-            \"item\":\"ITEM\",\"items\":\"ITEMS\",\"key\":\"KEY\",\"labels\":[],\
-            \"mode\":\"MODE\",\"node\":\"NODE\",\"ogid\":\"OGID\",\
-            \"operation\":\"OPERATION\",\"ouid\":\"OUID\",\"path\":\"PATH\",\
-            \"paths\":[],\"pid\":\"PID\",\"ppid\":\"PPID\",\"proctitle\":\"PROCTITLE\",\
-            \"rdev\":\"RDEV\",\"ses\":\"SES\",\"sgid\":\"SGID\",\"source\":\"SOURCE\",\
-            \"success\":\"SUCCESS\",\"suid\":\"SUID\",\"syscall\":\"SYSCALL\",\
-            \"system\":\"SYSTEM\",\"timestamp\":\"TIMESTAMP\",\"tty\":\"TTY\",\
-            \"uid\":\"UID\",\"version\":\"VERSION\"}");
+        let json = create_test_event().format_json(config::FIM_FORMAT);
+        let string = String::from("{\"@timestamp\":\"ISO_TIMESTAMP\",\"a0\":\"A0\",\"a1\":\"A1\",\
+            \"a2\":\"A2\",\"a3\":\"A3\",\"action\":\"ACTION\",\
+            \"arch\":\"ARCH\",\"arch_name\":\"ARCH_NAME\",\"argv\":[\"ls\",\
+            \"-l\"],\"auid\":7,\"auid_name\":\"AUID_NAME\",\
+            \"cap_fe\":\"CAP_FE\",\"cap_fi\":\"CAP_FI\",\"cap_fp\":\"CAP_FP\",\
+            \"cap_frootid\":\"CAP_FROOTID\",\"cap_fver\":\"CAP_FVER\",\
+            \"checksum\":\"CHECKSUM\",\"comm\":\"COMM\",\
+            \"command\":\"COMMAND\",\"cwd\":\"CWD\",\"dev\":\"DEV\",\
+            \"egid\":13,\"egid_name\":\"EGID_NAME\",\"entropy\":0.5,\
+            \"euid\":9,\"euid_name\":\"EUID_NAME\",\"exe\":\"EXE\",\
+            \"exit\":-13,\"expected\":true,\"file\":\"FILE\",\
+            \"file_type\":\"FILE_TYPE\",\"file_type_changed\":false,\
+            \"fpid\":0,\"fsgid\":14,\"fsuid\":5,\
+            \"gid\":8,\"gid_name\":\"GID_NAME\",\
+            \"hostname\":\"HOSTNAME\",\"id\":\"ID\",\"inode\":2,\
+            \"inode_type\":\"INODE_TYPE\",\"item\":\"ITEM\",\
+            \"items\":15,\"key\":\"KEY\",\"labels\":[],\
+            \"mode\":\"MODE\",\"node\":\"NODE\",\"obj\":\"OBJ\",\
+            \"obj_level\":\"OBJ_LEVEL\",\"obj_role\":\"OBJ_ROLE\",\
+            \"obj_type\":\"OBJ_TYPE\",\"obj_user\":\"OBJ_USER\",\
+            \"ogid\":1,\"ogid_name\":\"OGID_NAME\",\
+            \"operation\":\"OPERATION\",\"ouid\":3,\
+            \"ouid_name\":\"OUID_NAME\",\"package\":\"PACKAGE\",\
+            \"package_version\":\"PACKAGE_VERSION\",\"path\":\"PATH\",\
+            \"paths\":[],\"permissions\":\"PERMISSIONS\",\"pid\":6,\
+            \"ppid\":4,\"process_ancestry\":[{\"exe\":\"/usr/lib/systemd/systemd\",\"pid\":1,\"ppid\":0,\"uid\":0}],\
+            \"proctitle\":\"PROCTITLE\",\"rdev\":\"RDEV\",\
+            \"rule_description\":\"Test rule\",\"rule_id\":\"100001\",\
+            \"schema_version\":\"1.1\",\
+            \"sequence\":0,\"serial\":\"SERIAL\",\"ses\":16,\
+            \"setgid\":false,\"setuid\":false,\"severity\":\"low\",\
+            \"sgid\":10,\"source\":\"SOURCE\",\"sticky\":false,\
+            \"subj\":\"SUBJ\",\"subj_level\":\"SUBJ_LEVEL\",\
+            \"subj_role\":\"SUBJ_ROLE\",\"subj_type\":\"SUBJ_TYPE\",\
+            \"subj_user\":\"SUBJ_USER\",\"success\":\"SUCCESS\",\
+            \"suid\":12,\"syscall\":\"SYSCALL\",\
+            \"syscall_name\":\"SYSCALL_NAME\",\"system\":\"SYSTEM\",\
+            \"tags\":[\"test\"],\"timestamp\":\"TIMESTAMP\",\"tty\":\"TTY\",\
+            \"uid\":11,\"uid_name\":\"UID_NAME\",\"version\":\"VERSION\"}");
         assert_eq!(json, string);
     }
 
@@ -872,25 +1081,47 @@ mod tests {
     fn test_log() {
         let filename = "test_log.json";
         let event = create_test_event();
-        event.log(filename);
+        event.log(filename, config::FIM_FORMAT);
 
-        let expected = "{\"a0\":\"A0\",\"a1\":\"A1\",\"a2\":\"A2\",\"a3\":\"A3\",\
-            \"arch\":\"ARCH\",\"auid\":\"AUID\",\"cap_fe\":\"CAP_FE\",\
-            \"cap_fi\":\"CAP_FI\",\"cap_fp\":\"CAP_FP\",\
+        let expected = "{\"@timestamp\":\"ISO_TIMESTAMP\",\"a0\":\"A0\",\"a1\":\"A1\",\
+            \"a2\":\"A2\",\"a3\":\"A3\",\"action\":\"ACTION\",\
+            \"arch\":\"ARCH\",\"arch_name\":\"ARCH_NAME\",\"argv\":[\"ls\",\
+            \"-l\"],\"auid\":7,\"auid_name\":\"AUID_NAME\",\
+            \"cap_fe\":\"CAP_FE\",\"cap_fi\":\"CAP_FI\",\"cap_fp\":\"CAP_FP\",\
             \"cap_frootid\":\"CAP_FROOTID\",\"cap_fver\":\"CAP_FVER\",\
-            \"checksum\":\"CHECKSUM\",\"comm\":\"COMM\",\"command\":\"COMMAND\",\
-            \"cwd\":\"CWD\",\"dev\":\"DEV\",\"egid\":\"EGID\",\"euid\":\"EUID\",\
-            \"exe\":\"EXE\",\"exit\":\"EXIT\",\"file\":\"FILE\",\"fpid\":0,\
-            \"fsgid\":\"FSGID\",\"fsuid\":\"FSUID\",\"gid\":\"GID\",\
-            \"hostname\":\"HOSTNAME\",\"id\":\"ID\",\"inode\":\"INODE\",\
-            \"item\":\"ITEM\",\"items\":\"ITEMS\",\"key\":\"KEY\",\"labels\":[],\
-            \"mode\":\"MODE\",\"node\":\"NODE\",\"ogid\":\"OGID\",\
-            \"operation\":\"OPERATION\",\"ouid\":\"OUID\",\"path\":\"PATH\",\
-            \"paths\":[],\"pid\":\"PID\",\"ppid\":\"PPID\",\
-            \"proctitle\":\"PROCTITLE\",\"rdev\":\"RDEV\",\"ses\":\"SES\",\
-            \"sgid\":\"SGID\",\"source\":\"SOURCE\",\"success\":\"SUCCESS\",\
-            \"suid\":\"SUID\",\"syscall\":\"SYSCALL\",\"system\":\"SYSTEM\",\
-            \"timestamp\":\"TIMESTAMP\",\"tty\":\"TTY\",\"uid\":\"UID\",\
+            \"checksum\":\"CHECKSUM\",\"comm\":\"COMM\",\
+            \"command\":\"COMMAND\",\"cwd\":\"CWD\",\"dev\":\"DEV\",\
+            \"egid\":13,\"egid_name\":\"EGID_NAME\",\"entropy\":0.5,\
+            \"euid\":9,\"euid_name\":\"EUID_NAME\",\"exe\":\"EXE\",\
+            \"exit\":-13,\"expected\":true,\"file\":\"FILE\",\
+            \"file_type\":\"FILE_TYPE\",\"file_type_changed\":false,\
+            \"fpid\":0,\"fsgid\":14,\"fsuid\":5,\
+            \"gid\":8,\"gid_name\":\"GID_NAME\",\
+            \"hostname\":\"HOSTNAME\",\"id\":\"ID\",\"inode\":2,\
+            \"inode_type\":\"INODE_TYPE\",\"item\":\"ITEM\",\
+            \"items\":15,\"key\":\"KEY\",\"labels\":[],\
+            \"mode\":\"MODE\",\"node\":\"NODE\",\"obj\":\"OBJ\",\
+            \"obj_level\":\"OBJ_LEVEL\",\"obj_role\":\"OBJ_ROLE\",\
+            \"obj_type\":\"OBJ_TYPE\",\"obj_user\":\"OBJ_USER\",\
+            \"ogid\":1,\"ogid_name\":\"OGID_NAME\",\
+            \"operation\":\"OPERATION\",\"ouid\":3,\
+            \"ouid_name\":\"OUID_NAME\",\"package\":\"PACKAGE\",\
+            \"package_version\":\"PACKAGE_VERSION\",\"path\":\"PATH\",\
+            \"paths\":[],\"permissions\":\"PERMISSIONS\",\"pid\":6,\
+            \"ppid\":4,\"process_ancestry\":[{\"exe\":\"/usr/lib/systemd/systemd\",\"pid\":1,\"ppid\":0,\"uid\":0}],\
+            \"proctitle\":\"PROCTITLE\",\"rdev\":\"RDEV\",\
+            \"rule_description\":\"Test rule\",\"rule_id\":\"100001\",\
+            \"schema_version\":\"1.1\",\
+            \"sequence\":0,\"serial\":\"SERIAL\",\"ses\":16,\
+            \"setgid\":false,\"setuid\":false,\"severity\":\"low\",\
+            \"sgid\":10,\"source\":\"SOURCE\",\"sticky\":false,\
+            \"subj\":\"SUBJ\",\"subj_level\":\"SUBJ_LEVEL\",\
+            \"subj_role\":\"SUBJ_ROLE\",\"subj_type\":\"SUBJ_TYPE\",\
+            \"subj_user\":\"SUBJ_USER\",\"success\":\"SUCCESS\",\
+            \"suid\":12,\"syscall\":\"SYSCALL\",\
+            \"syscall_name\":\"SYSCALL_NAME\",\"system\":\"SYSTEM\",\
+            \"tags\":[\"test\"],\"timestamp\":\"TIMESTAMP\",\"tty\":\"TTY\",\
+            \"uid\":11,\"uid_name\":\"UID_NAME\",\
             \"version\":\"VERSION\"}\n";
 
         let log = utils::read_file(filename);
@@ -904,7 +1135,7 @@ mod tests {
     #[test]
     #[should_panic]
     fn test_log_panic() {
-        create_empty_event().log("");
+        create_empty_event().log("", config::FIM_FORMAT);
     }
 
     // ------------------------------------------------------------------------
@@ -914,7 +1145,7 @@ mod tests {
         let event = create_test_event();
         block_on( event.send(
             String::from("test"), String::from("https://127.0.0.1:9200"),
-            String::from("admin"), String::from("admin"), true) );
+            String::from("admin"), String::from("admin"), true, config::FIM_FORMAT) );
     }
 
     // ------------------------------------------------------------------------
@@ -922,10 +1153,13 @@ mod tests {
     #[test]
     fn test_process() {
         let config = Config::new(&utils::get_os(), None);
-        let event = create_test_event();
+        let mut event = create_test_event();
 
         block_on(event.process(config::NETWORK_MODE, String::from("test"), config.clone()));
+        let sequence = event.sequence;
+        assert!(sequence > 0);
         block_on(event.process(config::FILE_MODE, String::from("test2"), config.clone()));
+        assert!(event.sequence > sequence);
         block_on(event.process(config::BOTH_MODE, String::from("test3"), config.clone()));
     }
 
@@ -936,15 +1170,15 @@ mod tests {
         let out = format!("{:?}", create_test_event());
         let expected = " { id: \"ID\", path: \"PATH\", operation: \"OPERATION\", \
             file: \"FILE\", timestamp: \"TIMESTAMP\", proctitle: \"PROCTITLE\", \
-            cap_fver: \"CAP_FVER\", inode: \"INODE\", cap_fp: \"CAP_FP\", \
+            cap_fver: \"CAP_FVER\", inode: Some(2), cap_fp: \"CAP_FP\", \
             cap_fe: \"CAP_FE\", item: \"ITEM\", cap_fi: \"CAP_FI\", dev: \"DEV\", \
-            mode: \"MODE\", cap_frootid: \"CAP_FROOTID\", ouid: \"OUID\", paths: [], \
-            cwd: \"CWD\", syscall: \"SYSCALL\", ppid: \"PPID\", comm: \"COMM\", \
-            fsuid: \"FSUID\", pid: \"PID\", a0: \"A0\", a1: \"A1\", a2: \"A2\", \
-            a3: \"A3\", arch: \"ARCH\", auid: \"AUID\", items: \"ITEMS\", \
-            gid: \"GID\", euid: \"EUID\", sgid: \"SGID\", uid: \"UID\", \
-            tty: \"TTY\", success: \"SUCCESS\", exit: \"EXIT\", ses: \"SES\", \
-            key: \"KEY\", suid: \"SUID\", egid: \"EGID\", fsgid: \"FSGID\", exe: \"EXE\" }";
+            mode: \"MODE\", cap_frootid: \"CAP_FROOTID\", ouid: Some(3), paths: [], \
+            cwd: \"CWD\", syscall: \"SYSCALL\", ppid: Some(4), comm: \"COMM\", \
+            fsuid: Some(5), pid: Some(6), a0: \"A0\", a1: \"A1\", a2: \"A2\", \
+            a3: \"A3\", arch: \"ARCH\", auid: Some(7), items: Some(15), \
+            gid: Some(8), euid: Some(9), sgid: Some(10), uid: Some(11), \
+            tty: \"TTY\", success: \"SUCCESS\", exit: Some(-13), ses: Some(16), \
+            key: \"KEY\", suid: Some(12), egid: Some(13), fsgid: Some(14), exe: \"EXE\" }";
 
         assert_eq!(out, expected);
     }
@@ -0,0 +1,555 @@
+// Copyright (C) 2023, Achiefs.
+
+// Global constants definitions
+pub const AUDITCTL: &str = "/usr/sbin/auditctl";
+pub const AUGENRULES: &str = "/usr/sbin/augenrules";
+pub const RULES_D_FILE: &str = "/etc/audit/rules.d/fim.rules";
+
+// To run commands
+use std::process::Command;
+// To manage the rules.d file
+use std::fs;
+use std::path::Path;
+// To print the rules in auditctl format
+use std::fmt;
+// To log the program process
+use log::{debug, error, info, warn};
+
+// To get configuration values
+use crate::config;
+// To manage common functions
+use crate::utils;
+// To add the rules of monitor paths with whodata
+use crate::whodata;
+// To add the rule that follows the process executions
+use crate::ancestry;
+// To get the syscall numbers of this architecture
+use crate::syscalls;
+// To manage rules through the kernel netlink interface
+#[cfg(target_os = "linux")]
+use crate::netlink;
+
+// ----------------------------------------------------------------------------
+
+// Audit watch rule of a path, or syscall rule without path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditRule {
+    pub path: String,
+    pub permissions: String,
+    pub key: String,
+    pub syscall: String
+}
+
+impl fmt::Display for AuditRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.path.is_empty() {
+            true => write!(f, "-a always,exit -F arch={} -S {} -k {}", get_arch_filter(),
+                self.syscall, self.key),
+            false => write!(f, "-w {} -p {} -k {}", self.path, self.permissions, self.key)
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Rule of the process executions of the native architecture, they are used
+// to build the ancestry of the audit events and they are not reported
+pub fn get_execve_rule() -> Option<AuditRule> {
+    syscalls::get_syscall_number(syscalls::get_native_arch(), ancestry::EXECVE_SYSCALL)?;
+    Some(AuditRule {
+        path: String::new(),
+        permissions: String::new(),
+        key: String::from(ancestry::EXECVE_KEY),
+        syscall: String::from(ancestry::EXECVE_SYSCALL)
+    })
+}
+
+// ----------------------------------------------------------------------------
+
+// Architecture filter of auditctl for the native architecture
+fn get_arch_filter() -> &'static str {
+    if cfg!(target_pointer_width = "64") { "b64" } else { "b32" }
+}
+
+// ----------------------------------------------------------------------------
+
+// Rules that the configuration requires, audit paths and monitor paths
+// with whodata enabled
+pub fn get_config_rules(config: &config::Config) -> Vec<AuditRule> {
+    let mut rules: Vec<AuditRule> = config.audit.iter().enumerate().map(|(index, element)| AuditRule {
+        path: String::from(element["path"].as_str().unwrap()),
+        permissions: config.get_audit_permissions(index),
+        key: config.get_audit_key(index),
+        syscall: String::new()
+    }).collect();
+    for (index, element) in config.monitor.iter().enumerate() {
+        if config.match_whodata(index) {
+            rules.push(AuditRule {
+                path: String::from(element["path"].as_str().unwrap()),
+                permissions: String::from(whodata::WHODATA_PERMISSIONS),
+                key: String::from(whodata::WHODATA_KEY),
+                syscall: String::new()
+            });
+        }
+    }
+    if ! rules.is_empty() {
+        rules.extend(get_execve_rule());
+    }
+    rules
+}
+
+// ----------------------------------------------------------------------------
+
+// Keys that identify the rules owned by FIM
+pub fn get_managed_keys(config: &config::Config) -> Vec<String> {
+    let mut keys = vec![String::from(config::AUDIT_DEFAULT_KEY), String::from(whodata::WHODATA_KEY),
+        String::from(ancestry::EXECVE_KEY)];
+    for index in 0..config.audit.len() {
+        let key = config.get_audit_key(index);
+        if ! keys.contains(&key) { keys.push(key) }
+    }
+    keys
+}
+
+// ----------------------------------------------------------------------------
+
+// Parse a rule of 'auditctl -l' output, both '-w /tmp -p wa -k fim' and
+// '-a always,exit -F dir=/tmp -F perm=wa -F key=fim' formats of watches and
+// '-a always,exit -F arch=b64 -S execve -F key=fim_execve' syscall rules
+pub fn parse_rule(line: &str) -> Option<AuditRule> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let mut path = None;
+    let mut permissions = String::new();
+    let mut key = String::new();
+    let mut syscalls: Vec<&str> = Vec::new();
+    let mut iter = fields.iter();
+    while let Some(field) = iter.next() {
+        match *field {
+            "-w" => path = iter.next().map(|p| String::from(*p)),
+            "-p" => permissions = iter.next().map(|p| String::from(*p)).unwrap_or_default(),
+            "-k" => key = iter.next().map(|k| String::from(*k)).unwrap_or_default(),
+            "-S" => syscalls.extend(iter.next()),
+            "-F" => match iter.next().and_then(|f| f.split_once('=')) {
+                Some(("dir", value)) | Some(("path", value)) => path = Some(String::from(value)),
+                Some(("perm", value)) => permissions = String::from(value),
+                Some(("key", value)) => key = String::from(value),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+    match path {
+        Some(path) => Some(build_rule(path, &permissions, key, String::new())),
+        None if ! syscalls.is_empty() => Some(build_rule(String::new(), "", key, syscalls.join(","))),
+        None => None
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Listed rule with the path and permissions in a comparable format
+fn build_rule(path: String, permissions: &str, key: String, syscall: String) -> AuditRule {
+    // Kernel lists the directory watches without the trailing slash
+    let path = if path.len() > 1 { String::from(path.trim_end_matches('/')) } else { path };
+    AuditRule { path, permissions: sort_permissions(permissions), key, syscall }
+}
+
+// ----------------------------------------------------------------------------
+
+// Native architecture and syscall number of a syscall rule, for netlink
+#[cfg(target_os = "linux")]
+fn get_native_syscall(name: &str) -> Option<(u32, u32)> {
+    let arch = syscalls::get_native_arch();
+    Some((u32::from_str_radix(arch, 16).ok()?, syscalls::get_syscall_number(arch, name)?))
+}
+
+// ----------------------------------------------------------------------------
+
+// Permissions in the order used by auditctl, to compare rules
+fn sort_permissions(permissions: &str) -> String {
+    config::AUDIT_PERMISSIONS.chars().filter(|c| permissions.contains(*c)).collect()
+}
+
+// ----------------------------------------------------------------------------
+
+// Current audit watch rules, through netlink or auditctl, empty if they
+// cannot be listed
+pub fn list_rules(config: &config::Config) -> Vec<AuditRule> {
+    #[cfg(target_os = "linux")]
+    if config.audit_source == config::AUDIT_NETLINK_SOURCE {
+        return match netlink::Client::new().and_then(|mut client| client.list_rules()) {
+            Ok(rules) => rules.into_iter().map(|rule| {
+                let syscall = rule.syscalls.iter()
+                    .map(|s| syscalls::get_syscall_name(syscalls::get_native_arch(), &s.to_string()))
+                    .collect::<Vec<String>>().join(",");
+                build_rule(rule.path, &rule.permissions, rule.key, syscall)
+            }).collect(),
+            Err(e) => {
+                error!("Could not list netlink audit rules, error: {}", e);
+                Vec::new()
+            }
+        }
+    }
+    match Command::new(AUDITCTL).arg("-l").output() {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .lines().filter_map(parse_rule).collect(),
+        Ok(output) => {
+            debug!("Could not list audit rules: {}", String::from_utf8_lossy(&output.stderr));
+            Vec::new()
+        },
+        Err(e) => {
+            error!("Could not list audit rules, error: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Get the rules to remove and to add to go from 'current' rules to the
+// 'desired' ones, only rules with a managed key are removed
+pub fn get_changes(current: &[AuditRule], desired: &[AuditRule], keys: &[String]) -> (Vec<AuditRule>, Vec<AuditRule>) {
+    let stale = current.iter()
+        .filter(|r| keys.contains(&r.key) && ! desired.iter().any(|d| is_same(r, d)))
+        .cloned().collect();
+    let missing = desired.iter()
+        .filter(|d| ! current.iter().any(|r| is_same(r, d)))
+        .cloned().collect();
+    (stale, missing)
+}
+
+// ----------------------------------------------------------------------------
+
+fn is_same(rule: &AuditRule, other: &AuditRule) -> bool {
+    let path = if other.path.len() > 1 { other.path.trim_end_matches('/') } else { other.path.as_str() };
+    rule.path == path && rule.key == other.key && rule.syscall == other.syscall &&
+        rule.permissions == sort_permissions(&other.permissions)
+}
+
+// ----------------------------------------------------------------------------
+
+// Make the audit rules match the configuration, stale rules left by a
+// previous run are removed and rules already loaded are not duplicated
+pub fn reconcile(config: &config::Config) {
+    if sync_rules_file(config, RULES_D_FILE) && Path::new(AUGENRULES).exists() {
+        load_rules_file();
+    }
+    let (stale, missing) = get_changes(&list_rules(config), &get_config_rules(config),
+        &get_managed_keys(config));
+    for rule in stale {
+        info!("Removing stale audit rule: {}", rule);
+        remove_rule(config, &rule);
+    }
+    for rule in missing {
+        add_rule(config, &rule);
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Remove all the audit rules of FIM, configured or left by previous runs
+pub fn cleanup(config: &config::Config) {
+    let keys = get_managed_keys(config);
+    let mut rules: Vec<AuditRule> = list_rules(config).into_iter()
+        .filter(|r| keys.contains(&r.key)).collect();
+    for rule in get_config_rules(config) {
+        if ! rules.iter().any(|r| is_same(r, &rule)) { rules.push(rule) }
+    }
+    for rule in rules {
+        remove_rule(config, &rule);
+    }
+    if Path::new(RULES_D_FILE).exists() {
+        remove_rules_file(RULES_D_FILE);
+        // Keep the loaded rules in sync with the rules.d files
+        if Path::new(AUGENRULES).exists() {
+            load_rules_file();
+        }
+    }
+    info!("Audit rules removed");
+}
+
+// ----------------------------------------------------------------------------
+
+// Remove the audit rules at exit, persistent rules are kept loaded. With
+// netlink FIM stops being the audit daemon.
+pub fn shutdown(config: &config::Config) {
+    #[cfg(target_os = "linux")]
+    if config.audit_source == config::AUDIT_NETLINK_SOURCE {
+        match netlink::Client::new().and_then(|mut client| client.clear_pid()) {
+            Ok(_) => debug!("Netlink audit daemon unregistered"),
+            Err(e) => error!("Could not unregister netlink audit daemon, error: {}", e)
+        };
+    }
+    if config.audit_persistent {
+        info!("Persistent audit rules kept in: {}", RULES_D_FILE);
+    }else{
+        cleanup(config);
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Content of the rules.d file for the given rules
+pub fn format_rules_file(rules: &[AuditRule]) -> String {
+    let mut content = String::from("## Audit rules managed by FIM, changes will be overwritten\n");
+    for rule in rules {
+        content.push_str(&format!("{}\n", rule));
+    }
+    content
+}
+
+// ----------------------------------------------------------------------------
+
+// Keep the rules.d file in sync with the configuration, it is written when
+// persistence is enabled and removed otherwise. Returns if it changed.
+pub fn sync_rules_file(config: &config::Config, file: &str) -> bool {
+    if ! config.audit_persistent || config.audit_source == config::AUDIT_NETLINK_SOURCE {
+        if Path::new(file).exists() {
+            remove_rules_file(file);
+            return true
+        }
+        return false
+    }
+    let content = format_rules_file(&get_config_rules(config));
+    if fs::read_to_string(file).map(|c| c == content).unwrap_or(false) {
+        debug!("Persistent audit rules file up to date: {}", file);
+        return false
+    }
+    match fs::write(file, content) {
+        Ok(_) => {
+            info!("Persistent audit rules written in: {}", file);
+            true
+        },
+        Err(e) => {
+            error!("Could not write persistent audit rules file '{}', error: {}", file, e);
+            false
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+fn remove_rules_file(file: &str) {
+    match fs::remove_file(file) {
+        Ok(_) => info!("Persistent audit rules file removed: {}", file),
+        Err(e) => error!("Could not remove persistent audit rules file '{}', error: {}", file, e)
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Load the rules.d files into the kernel
+pub fn load_rules_file() {
+    match Command::new(AUGENRULES).arg("--load").output() {
+        Ok(output) if output.status.success() => debug!("Augenrules loaded audit rules"),
+        Ok(output) => warn!("Augenrules could not load audit rules: {}",
+            String::from_utf8_lossy(&output.stderr)),
+        Err(e) => error!("Augenrules command error: {}", e)
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Add an audit rule, through netlink or auditctl
+pub fn add_rule(config: &config::Config, rule: &AuditRule) {
+    #[cfg(target_os = "linux")]
+    if config.audit_source == config::AUDIT_NETLINK_SOURCE {
+        let result = match rule.path.is_empty() {
+            true => get_native_syscall(&rule.syscall).ok_or(std::io::ErrorKind::Unsupported.into())
+                .and_then(|(arch, syscall)| netlink::Client::new()
+                    .and_then(|mut client| client.add_syscall(arch, syscall, &rule.key))),
+            false => netlink::Client::new().and_then(|mut client|
+                client.add_watch(&rule.path, &rule.permissions, &rule.key))
+        };
+        match result {
+            Ok(_) => debug!("Netlink audit rule added: {}", rule),
+            Err(e) => error!("Netlink audit rule error: {}", e)
+        };
+        return;
+    }
+    let arch = format!("arch={}", get_arch_filter());
+    let args = match rule.path.is_empty() {
+        true => vec!["-a", "always,exit", "-F", &arch, "-S", &rule.syscall, "-k", &rule.key],
+        false => vec!["-w", &rule.path, "-k", &rule.key, "-p", &rule.permissions]
+    };
+    match Command::new(AUDITCTL).args(args).output() {
+        Ok(d) => debug!("Auditctl command info: {:?}", d),
+        Err(e) => error!("Auditctl command error: {}", e)
+    };
+}
+
+// ----------------------------------------------------------------------------
+
+pub fn remove_rule(config: &config::Config, rule: &AuditRule) {
+    #[cfg(target_os = "linux")]
+    if config.audit_source == config::AUDIT_NETLINK_SOURCE {
+        let result = match rule.path.is_empty() {
+            true => get_native_syscall(&rule.syscall).ok_or(std::io::ErrorKind::Unsupported.into())
+                .and_then(|(arch, syscall)| netlink::Client::new()
+                    .and_then(|mut client| client.delete_syscall(arch, syscall, &rule.key))),
+            false => netlink::Client::new().and_then(|mut client|
+                client.delete_watch(&rule.path, &rule.permissions, &rule.key))
+        };
+        match result {
+            Ok(_) => debug!("Netlink audit rule removed: {}", rule),
+            Err(e) => error!("Netlink audit rule error: {}", e)
+        };
+        return;
+    }
+    let arch = format!("arch={}", get_arch_filter());
+    let args = match rule.path.is_empty() {
+        true => vec!["-d", "always,exit", "-F", &arch, "-S", &rule.syscall, "-k", &rule.key],
+        false => vec!["-W", &rule.path, "-k", &rule.key, "-p", &rule.permissions]
+    };
+    match Command::new(AUDITCTL).args(args).output() {
+        Ok(d) => debug!("Auditctl command info: {:?}", d),
+        Err(e) => error!("Auditctl command error: {}", e)
+    };
+}
+
+// ----------------------------------------------------------------------------
+
+// Check if audit rules can be managed, auditd userspace is not needed with netlink
+pub fn check_audit(config: &config::Config) -> bool {
+    #[cfg(target_os = "linux")]
+    if config.audit_source == config::AUDIT_NETLINK_SOURCE {
+        return netlink::is_available()
+    }
+    utils::check_auditd()
+}
+
+// ----------------------------------------------------------------------------
+
+// Remove the audit rules when any thread panics and stop the program, a
+// panic in a reader thread or runtime worker would leave it running without
+// them. The previous hook (panic logging) is still called.
+pub fn set_panic_cleanup(config: config::Config) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        previous(info);
+        shutdown(&config);
+        std::process::exit(101);
+    }));
+}
+
+// ----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_rule(path: &str, permissions: &str, key: &str) -> AuditRule {
+        AuditRule {
+            path: String::from(path),
+            permissions: String::from(permissions),
+            key: String::from(key),
+            syscall: String::new()
+        }
+    }
+
+    // ------------------------------------------------------------------------
+
+    fn create_syscall_rule(syscall: &str, key: &str) -> AuditRule {
+        AuditRule {
+            path: String::new(),
+            permissions: String::new(),
+            key: String::from(key),
+            syscall: String::from(syscall)
+        }
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_parse_rule() {
+        assert_eq!(parse_rule("-w /tmp/ -p wa -k fim"), Some(create_rule("/tmp", "wa", "fim")));
+        assert_eq!(parse_rule("-a always,exit -F dir=/etc -F perm=xwa -F key=fim"),
+            Some(create_rule("/etc", "wxa", "fim")));
+        assert_eq!(parse_rule("-a always,exit -F path=/etc/shadow -F perm=rwxa -F key=fim_shadow"),
+            Some(create_rule("/etc/shadow", "rwxa", "fim_shadow")));
+        assert_eq!(parse_rule("-w / -p r"), Some(create_rule("/", "r", "")));
+        assert_eq!(parse_rule("-a always,exit -F arch=b64 -S adjtimex -F key=time-change"),
+            Some(create_syscall_rule("adjtimex", "time-change")));
+        assert_eq!(parse_rule("-a always,exit -F arch=b64 -S execve -F key=fim_execve"),
+            Some(create_syscall_rule("execve", "fim_execve")));
+        assert_eq!(parse_rule("-a always,exit -S adjtimex -S settimeofday"),
+            Some(create_syscall_rule("adjtimex,settimeofday", "")));
+        assert_eq!(parse_rule("-a never,task"), None);
+        assert_eq!(parse_rule("No rules"), None);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_get_config_rules() {
+        let config = config::Config::new("linux", Some("test/unit/config/linux/audit_keys.yml"));
+        let rules = get_config_rules(&config);
+        assert_eq!(rules.len(), 5);
+        assert_eq!(rules[0], create_rule("/etc/shadow", "rwxa", "fim_shadow"));
+        assert_eq!(rules[1], create_rule("/etc/ssh", "wax", "fim"));
+        assert_eq!(rules[4], create_syscall_rule("execve", "fim_execve"));
+        assert_eq!(get_managed_keys(&config), vec!["fim", "fim_whodata", "fim_execve", "fim_shadow"]);
+
+        let config = config::Config::new("linux", Some("test/unit/config/linux/whodata.yml"));
+        assert_eq!(get_config_rules(&config), vec![create_rule("/etc", "wa", "fim_whodata"),
+            create_syscall_rule("execve", "fim_execve")]);
+
+        let config = config::Config::new("linux", Some("test/unit/config/linux/audit_none.yml"));
+        assert!(get_config_rules(&config).is_empty());
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_get_changes() {
+        let keys = vec![String::from("fim")];
+        let current = vec![
+            create_rule("/tmp", "wxa", "fim"),
+            create_rule("/old", "wxa", "fim"),
+            create_rule("/etc", "wxa", "fim"),
+            create_rule("/var", "wa", "other")
+        ];
+        let desired = vec![
+            create_rule("/tmp/", "wax", "fim"),
+            create_rule("/etc", "rwxa", "fim"),
+            create_rule("/home", "wax", "fim")
+        ];
+        let (stale, missing) = get_changes(&current, &desired, &keys);
+        assert_eq!(stale, vec![create_rule("/old", "wxa", "fim"), create_rule("/etc", "wxa", "fim")]);
+        assert_eq!(missing, vec![create_rule("/etc", "rwxa", "fim"), create_rule("/home", "wax", "fim")]);
+
+        let (stale, missing) = get_changes(&[], &desired, &keys);
+        assert!(stale.is_empty());
+        assert_eq!(missing.len(), 3);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_format_rules_file() {
+        let rules = vec![create_rule("/tmp", "wax", "fim"), create_rule("/etc/shadow", "rwxa", "fim_shadow"),
+            create_syscall_rule("execve", "fim_execve")];
+        assert_eq!(format_rules_file(&rules), format!("## Audit rules managed by FIM, changes will be overwritten\n\
+            -w /tmp -p wax -k fim\n-w /etc/shadow -p rwxa -k fim_shadow\n\
+            -a always,exit -F arch={} -S execve -k fim_execve\n", get_arch_filter()));
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_sync_rules_file() {
+        let file = "test_auditrules_fim.rules";
+        let mut config = config::Config::new("linux", Some("test/unit/config/linux/audit_keys.yml"));
+        config.audit_persistent = true;
+        assert!(sync_rules_file(&config, file));
+        assert_eq!(fs::read_to_string(file).unwrap(), format_rules_file(&get_config_rules(&config)));
+        assert!(!sync_rules_file(&config, file));
+
+        config.audit_persistent = false;
+        assert!(sync_rules_file(&config, file));
+        assert!(!Path::new(file).exists());
+        assert!(!sync_rules_file(&config, file));
+    }
+}
@@ -5,7 +5,16 @@ pub const VERSION: &str = "0.4.6";
 pub const NETWORK_MODE: &str = "NETWORK";
 pub const FILE_MODE: &str = "FILE";
 pub const BOTH_MODE: &str = "BOTH";
+pub const FIM_FORMAT: &str = "fim";
+pub const ECS_FORMAT: &str = "ecs";
 pub const MACHINE_ID_PATH: &str = "/etc/machine-id";
+pub const AUDIT_FILE_SOURCE: &str = "file";
+pub const AUDIT_STDIN_SOURCE: &str = "stdin";
+pub const AUDIT_NETLINK_SOURCE: &str = "netlink";
+pub const AUDIT_LOG_PATH: &str = "/var/log/audit/audit.log";
+pub const AUDIT_PERMISSIONS: &str = "rwxa";
+pub const AUDIT_DEFAULT_PERMISSIONS: &str = "wax";
+pub const AUDIT_DEFAULT_KEY: &str = "fim";
 const CONFIG_MACOS_PATH: &str = "/Applications/FileMonitor.app/config.yml";
 const CONFIG_LINUX_PATH: &str = "/etc/fim/config.yml";
 const CONFIG_WINDOWS_PATH: &str = "C:\\Program Files\\File Integrity Monitor\\config.yml";
@@ -41,7 +50,21 @@ pub struct Config {
     pub log_file: String,
     pub log_level: String,
     pub system: String,
-    pub insecure: bool
+    pub insecure: bool,
+    pub storm_threshold: usize,
+    pub storm_process_threshold: usize,
+    pub storm_window: u64,
+    pub storm_entropy: bool,
+    pub rules_file: String,
+    pub package_enabled: bool,
+    pub package_window: u64,
+    pub package_suppress: bool,
+    pub audit_source: String,
+    pub audit_log: String,
+    pub audit_persistent: bool,
+    pub events_sequence_file: String,
+    pub events_format: String,
+    pub events_audit_state_file: String
 }
 
 impl Config {
@@ -62,7 +85,21 @@ impl Config {
             log_file: self.log_file.clone(),
             log_level: self.log_level.clone(),
             system: self.system.clone(),
-            insecure: self.insecure
+            insecure: self.insecure,
+            storm_threshold: self.storm_threshold,
+            storm_process_threshold: self.storm_process_threshold,
+            storm_window: self.storm_window,
+            storm_entropy: self.storm_entropy,
+            rules_file: self.rules_file.clone(),
+            package_enabled: self.package_enabled,
+            package_window: self.package_window,
+            package_suppress: self.package_suppress,
+            audit_source: self.audit_source.clone(),
+            audit_log: self.audit_log.clone(),
+            audit_persistent: self.audit_persistent,
+            events_sequence_file: self.events_sequence_file.clone(),
+            events_format: self.events_format.clone(),
+            events_audit_state_file: self.events_audit_state_file.clone()
         }
     }
 
@@ -97,6 +134,34 @@ impl Config {
             }
         };
 
+        // Manage null value on events->sequence_file value
+        let events_sequence_file = match yaml[0]["events"]["sequence_file"].as_str() {
+            Some(value) => String::from(value),
+            None => match system {
+                "windows" => String::from("C:\\ProgramData\\fim\\sequence"),
+                _ => String::from("/var/lib/fim/sequence")
+            }
+        };
+
+        // Manage null value on events->audit_state_file value
+        let events_audit_state_file = match yaml[0]["events"]["audit_state_file"].as_str() {
+            Some(value) => String::from(value),
+            None => match system {
+                "windows" => String::from("C:\\ProgramData\\fim\\audit.state"),
+                _ => String::from("/var/lib/fim/audit.state")
+            }
+        };
+
+        // Manage null value on events->format value
+        let events_format = match yaml[0]["events"]["format"].as_str() {
+            Some(ECS_FORMAT) => String::from(ECS_FORMAT),
+            Some(FIM_FORMAT) | None => String::from(FIM_FORMAT),
+            Some(value) => {
+                println!("[WARN] events->format '{}' not supported, using '{}'.", value, FIM_FORMAT);
+                String::from(FIM_FORMAT)
+            }
+        };
+
         // Manage null value on events->max_file_checksum value
         let events_max_file_checksum = match yaml[0]["events"]["max_file_checksum"].as_i64() {
             Some(value) => usize::try_from(value).unwrap(),
@@ -214,6 +279,59 @@ impl Config {
             }
         };
 
+        // Manage null value on storm->threshold value, zero disables the detector
+        let storm_threshold = match yaml[0]["storm"]["threshold"].as_i64() {
+            Some(value) => usize::try_from(value).unwrap(),
+            None => 0
+        };
+
+        // Manage null value on storm->process_threshold value
+        let storm_process_threshold = match yaml[0]["storm"]["process_threshold"].as_i64() {
+            Some(value) => usize::try_from(value).unwrap(),
+            None => storm_threshold
+        };
+
+        // Manage null value on storm->window value
+        let storm_window = match yaml[0]["storm"]["window"].as_i64() {
+            Some(value) => u64::try_from(value).unwrap(),
+            None => 60
+        };
+
+        // Manage null value on storm->entropy value
+        let storm_entropy = yaml[0]["storm"]["entropy"].as_bool().unwrap_or(false);
+
+        // Manage null value on packages->enabled value
+        let package_enabled = yaml[0]["packages"]["enabled"].as_bool().unwrap_or(false);
+
+        // Manage null value on packages->window value
+        let package_window = match yaml[0]["packages"]["window"].as_i64() {
+            Some(value) => u64::try_from(value).unwrap(),
+            None => 300
+        };
+
+        // Manage null value on packages->suppress value
+        let package_suppress = yaml[0]["packages"]["suppress"].as_bool().unwrap_or(false);
+
+        // Manage null value on audit_log->source value
+        // The stdin source is only selected by auditd with the --audit-plugin argument
+        let audit_source = match yaml[0]["audit_log"]["source"].as_str() {
+            Some(value) if [AUDIT_FILE_SOURCE, AUDIT_NETLINK_SOURCE].contains(&value) => String::from(value),
+            Some(value) => {
+                println!("[WARN] audit_log->source '{}' not valid, using 'file'.", value);
+                String::from(AUDIT_FILE_SOURCE)
+            },
+            None => String::from(AUDIT_FILE_SOURCE)
+        };
+
+        // Manage null value on audit_log->path value
+        let audit_log = String::from(yaml[0]["audit_log"]["path"].as_str().unwrap_or(AUDIT_LOG_PATH));
+
+        // Manage null value on audit_log->persistent value
+        let audit_persistent = yaml[0]["audit_log"]["persistent"].as_bool().unwrap_or(false);
+
+        // Manage null value on rules value, empty means no rules evaluation
+        let rules_file = String::from(yaml[0]["rules"].as_str().unwrap_or(""));
+
         Config {
             version: String::from(VERSION),
             path: cfg,
@@ -229,7 +347,21 @@ impl Config {
             log_file,
             log_level,
             system: String::from(system),
-            insecure
+            insecure,
+            storm_threshold,
+            storm_process_threshold,
+            storm_window,
+            storm_entropy,
+            rules_file,
+            package_enabled,
+            package_window,
+            package_suppress,
+            audit_source,
+            audit_log,
+            audit_persistent,
+            events_sequence_file,
+            events_format,
+            events_audit_state_file
         }
     }
 
@@ -239,7 +371,6 @@ impl Config {
     pub fn get_level_filter(&self) -> LevelFilter {
         let mut log = OpenOptions::new()
             .create(true)
-            .write(true)
             .append(true)
             .open(self.log_file.clone())
             .expect("(get_level_filter) Unable to open events log file.");
@@ -288,10 +419,8 @@ impl Config {
     // ------------------------------------------------------------------------
 
     pub fn get_labels(&self, index: usize, array: Array) -> Vec<String> {
-        match array[index]["labels"].clone().into_vec() {
-            Some(labels) => labels,
-            None => Vec::new()
-        }.to_vec().iter().map(|element| String::from(element.as_str().unwrap()) ).collect()
+        array[index]["labels"].clone().into_vec().unwrap_or_default()
+            .iter().map(|element| String::from(element.as_str().unwrap()) ).collect()
     }
 
     // ------------------------------------------------------------------------
@@ -305,6 +434,79 @@ impl Config {
 
     // ------------------------------------------------------------------------
 
+    // Returns if a monitor entry joins audit information to its events
+    pub fn match_whodata(&self, index: usize) -> bool {
+        self.monitor[index]["whodata"].as_bool().unwrap_or(false)
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Returns if any monitor entry requires audit information
+    pub fn has_whodata(&self) -> bool {
+        (0..self.monitor.len()).any(|i| self.match_whodata(i))
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Returns the audit permissions filter of an entry, any of 'rwxa'
+    pub fn get_audit_permissions(&self, index: usize) -> String {
+        match self.audit[index]["permissions"].as_str() {
+            Some(value) if !value.is_empty() && value.chars().all(|c| AUDIT_PERMISSIONS.contains(c)) =>
+                String::from(value),
+            Some(value) => {
+                println!("[WARN] audit permissions '{}' not valid, using '{}'.", value, AUDIT_DEFAULT_PERMISSIONS);
+                String::from(AUDIT_DEFAULT_PERMISSIONS)
+            },
+            None => String::from(AUDIT_DEFAULT_PERMISSIONS)
+        }
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Returns the audit key of an entry, used to route events back to it
+    pub fn get_audit_key(&self, index: usize) -> String {
+        String::from(self.audit[index]["key"].as_str().unwrap_or(AUDIT_DEFAULT_KEY))
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Returns the position of the audit entry of an event. The audit key
+    // selects the entry, path matching is only used between entries that
+    // share the same key. Events with a key not managed by FIM return MAX.
+    pub fn get_audit_index(&self, key: &str, raw_path: &str, cwd: &str) -> usize {
+        let candidates: Vec<usize> = (0..self.audit.len())
+            .filter(|i| self.get_audit_key(*i) == key).collect();
+        match candidates.len() {
+            0 => usize::MAX,
+            1 => candidates[0],
+            _ => {
+                let array = candidates.iter().map(|i| self.audit[*i].clone()).collect();
+                match self.get_index(raw_path, cwd, array) {
+                    usize::MAX => usize::MAX,
+                    pos => candidates[pos]
+                }
+            }
+        }
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Returns if the process or user of an audit event is in the audit ignore lists
+    pub fn match_ignore_process(&self, index: usize, exe: &str, comm: &str, uid: &str, auid: &str) -> bool {
+        [("ignore_exe", exe), ("ignore_comm", comm), ("ignore_uid", uid), ("ignore_auid", auid)]
+            .iter().any(|(key, value)| {
+                match self.audit[index][*key].as_vec() {
+                    Some(igv) => igv.iter().any(|ignore| match ignore {
+                        Yaml::Integer(number) => number.to_string() == *value,
+                        _ => ignore.as_str() == Some(*value)
+                    }),
+                    None => false
+                }
+            })
+    }
+
+    // ------------------------------------------------------------------------
+
     // Returns if a given path and filename is in the configuration paths
     pub fn path_in(&self, raw_path: &str, cwd: &str, vector: Vec<Yaml>) -> bool {
         // Iterate over monitoring paths to match ignore string and ignore event or not
@@ -395,7 +597,21 @@ mod tests {
             log_file: String::from("./test.log"),
             log_level: String::from(filter),
             system: String::from("test"),
-            insecure: true
+            insecure: true,
+            storm_threshold: 0,
+            storm_process_threshold: 0,
+            storm_window: 60,
+            storm_entropy: false,
+            rules_file: String::from(""),
+            package_enabled: false,
+            package_window: 300,
+            package_suppress: false,
+            audit_source: String::from("file"),
+            audit_log: String::from("/var/log/audit/audit.log"),
+            audit_persistent: false,
+            events_sequence_file: String::from(""),
+            events_format: String::from(FIM_FORMAT),
+            events_audit_state_file: String::from("")
         }
     }
 
@@ -420,6 +636,20 @@ mod tests {
         assert_eq!(config.log_level, cloned.log_level);
         assert_eq!(config.system, cloned.system);
         assert_eq!(config.insecure, cloned.insecure);
+        assert_eq!(config.storm_threshold, cloned.storm_threshold);
+        assert_eq!(config.storm_process_threshold, cloned.storm_process_threshold);
+        assert_eq!(config.storm_window, cloned.storm_window);
+        assert_eq!(config.storm_entropy, cloned.storm_entropy);
+        assert_eq!(config.rules_file, cloned.rules_file);
+        assert_eq!(config.package_enabled, cloned.package_enabled);
+        assert_eq!(config.package_window, cloned.package_window);
+        assert_eq!(config.package_suppress, cloned.package_suppress);
+        assert_eq!(config.audit_source, cloned.audit_source);
+        assert_eq!(config.audit_log, cloned.audit_log);
+        assert_eq!(config.audit_persistent, cloned.audit_persistent);
+        assert_eq!(config.events_sequence_file, cloned.events_sequence_file);
+        assert_eq!(config.events_format, cloned.events_format);
+        assert_eq!(config.events_audit_state_file, cloned.events_audit_state_file);
     }
 
     // ------------------------------------------------------------------------
@@ -440,7 +670,7 @@ mod tests {
         assert_eq!(config.log_file, String::from("C:\\ProgramData\\fim\\fim.log"));
         assert_eq!(config.log_level, String::from("info"));
         assert_eq!(config.system, String::from("windows"));
-        assert_eq!(config.insecure, false);
+        assert!(!config.insecure);
     }
 
     // ------------------------------------------------------------------------
@@ -485,7 +715,7 @@ mod tests {
     #[test]
     fn test_new_config_windows_events_endpoint_insecure() {
         let config = Config::new("windows", Some("test/unit/config/windows/events_endpoint_insecure.yml"));
-        assert_eq!(config.insecure, true);
+        assert!(config.insecure);
     }
 
     // ------------------------------------------------------------------------
@@ -494,7 +724,7 @@ mod tests {
     #[test]
     fn test_new_config_windows_events_endpoint_insecure_none() {
         let config = Config::new("windows", Some("test/unit/config/windows/events_endpoint_insecure_none.yml"));
-        assert_eq!(config.insecure, false);
+        assert!(!config.insecure);
     }
 
     // ------------------------------------------------------------------------
@@ -616,6 +846,17 @@ mod tests {
 
     // ------------------------------------------------------------------------
 
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_new_config_linux_events_format() {
+        let config = Config::new("linux", Some("test/unit/config/linux/events_format.yml"));
+        assert_eq!(config.events_format, ECS_FORMAT);
+        let config = Config::new("linux", Some("test/unit/config/linux/events_max_file_checksum.yml"));
+        assert_eq!(config.events_format, FIM_FORMAT);
+    }
+
+    // ------------------------------------------------------------------------
+
     #[cfg(target_os = "linux")]
     #[test]
     fn test_new_config_linux_events_max_file_checksum() {
@@ -629,7 +870,7 @@ mod tests {
     #[test]
     fn test_new_config_linux_events_endpoint_insecure() {
         let config = Config::new("linux", Some("test/unit/config/linux/events_endpoint_insecure.yml"));
-        assert_eq!(config.insecure, true);
+        assert!(config.insecure);
     }
 
     // ------------------------------------------------------------------------
@@ -638,7 +879,7 @@ mod tests {
     #[test]
     fn test_new_config_linux_events_endpoint_insecure_none() {
         let config = Config::new("linux", Some("test/unit/config/linux/events_endpoint_insecure_none.yml"));
-        assert_eq!(config.insecure, false);
+        assert!(!config.insecure);
     }
 
     // ------------------------------------------------------------------------
@@ -755,6 +996,71 @@ mod tests {
 
     // ------------------------------------------------------------------------
 
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_new_config_linux_storm() {
+        let config = Config::new("linux", Some("test/unit/config/linux/storm.yml"));
+        assert_eq!(config.storm_threshold, 100);
+        assert_eq!(config.storm_process_threshold, 100);
+        assert_eq!(config.storm_window, 30);
+        assert!(config.storm_entropy);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_new_config_linux_storm_none() {
+        let config = Config::new("linux", Some("test/unit/config/linux/audit_none.yml"));
+        assert_eq!(config.storm_threshold, 0);
+        assert_eq!(config.storm_process_threshold, 0);
+        assert_eq!(config.storm_window, 60);
+        assert!(!config.storm_entropy);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_new_config_linux_packages() {
+        let config = Config::new("linux", Some("test/unit/config/linux/packages.yml"));
+        assert!(config.package_enabled);
+        assert_eq!(config.package_window, 120);
+        assert!(config.package_suppress);
+        let config = Config::new("linux", Some("test/unit/config/linux/audit_none.yml"));
+        assert!(!config.package_enabled);
+        assert_eq!(config.package_window, 300);
+        assert!(!config.package_suppress);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_new_config_linux_audit_log() {
+        let config = Config::new("linux", Some("test/unit/config/linux/audit_log.yml"));
+        assert_eq!(config.audit_source, "netlink");
+        assert_eq!(config.audit_log, "/var/log/audit/custom.log");
+        assert!(config.audit_persistent);
+        let config = Config::new("linux", Some("test/unit/config/linux/audit_none.yml"));
+        assert_eq!(config.audit_source, "file");
+        assert_eq!(config.audit_log, "/var/log/audit/audit.log");
+        assert!(!config.audit_persistent);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_new_config_linux_rules() {
+        let config = Config::new("linux", Some("test/unit/config/linux/rules.yml"));
+        assert_eq!(config.rules_file, "test/unit/rules.yml");
+        let config = Config::new("linux", Some("test/unit/config/linux/audit_none.yml"));
+        assert_eq!(config.rules_file, "");
+    }
+
+    // ------------------------------------------------------------------------
+
     #[test]
     fn test_new_config_linux() {
         if utils::get_os() == "linux" {
@@ -765,18 +1071,31 @@ mod tests {
             assert_eq!(config.endpoint_user, String::from("Not_used"));
             assert_eq!(config.endpoint_pass, String::from("Not_used"));
             assert_eq!(config.events_file, String::from("/var/lib/fim/events.json"));
+            assert_eq!(config.events_sequence_file, String::from("/var/lib/fim/sequence"));
+            assert_eq!(config.events_audit_state_file, String::from("/var/lib/fim/audit.state"));
+            assert_eq!(config.events_format, String::from(FIM_FORMAT));
             // monitor
             // audit
             assert_eq!(config.node, String::from("FIM"));
             assert_eq!(config.log_file, String::from("/var/log/fim/fim.log"));
             assert_eq!(config.log_level, String::from("info"));
             assert_eq!(config.system, String::from("linux"));
-            assert_eq!(config.insecure, false);
+            assert!(!config.insecure);
         }
     }
 
     // ------------------------------------------------------------------------
 
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_new_config_sequence_file() {
+        let config = Config::new("linux", Some("test/unit/config/linux/replay.yml"));
+        assert_eq!(config.events_sequence_file, "test_replay_sequence");
+        assert_eq!(config.events_audit_state_file, "test_replay_audit.state");
+    }
+
+    // ------------------------------------------------------------------------
+
     #[test]
     fn test_new_config_macos() {
         let config = Config::new("macos", None);
@@ -792,7 +1111,7 @@ mod tests {
         assert_eq!(config.log_file, String::from("/var/log/fim/fim.log"));
         assert_eq!(config.log_level, String::from("info"));
         assert_eq!(config.system, String::from("macos"));
-        assert_eq!(config.insecure, false);
+        assert!(!config.insecure);
     }
 
     // ------------------------------------------------------------------------
@@ -1034,4 +1353,61 @@ mod tests {
         }
     }
 
+    // ------------------------------------------------------------------------
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_match_ignore_process() {
+        let config = Config::new("linux", Some("test/unit/config/linux/audit_ignore_process.yml"));
+        assert!(config.match_ignore_process(0, "/usr/bin/dpkg", "sed", "1000", "1000"));
+        assert!(config.match_ignore_process(0, "/usr/bin/sed", "puppet", "1000", "1000"));
+        assert!(config.match_ignore_process(0, "/usr/bin/sed", "sed", "998", "1000"));
+        assert!(config.match_ignore_process(0, "/usr/bin/sed", "sed", "1000", "4294967295"));
+        assert!(!config.match_ignore_process(0, "/usr/bin/sed", "sed", "1000", "1000"));
+        assert!(!config.match_ignore_process(1, "/usr/bin/dpkg", "puppet", "998", "4294967295"));
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_get_audit_permissions_key() {
+        let config = Config::new("linux", Some("test/unit/config/linux/audit_keys.yml"));
+        assert_eq!(config.get_audit_permissions(0), "rwxa");
+        assert_eq!(config.get_audit_key(0), "fim_shadow");
+        assert_eq!(config.get_audit_permissions(1), "wax");
+        assert_eq!(config.get_audit_key(1), "fim");
+        assert_eq!(config.get_audit_permissions(2), "wa");
+        assert_eq!(config.get_audit_key(2), "fim");
+        assert_eq!(config.get_audit_permissions(3), "wax");
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_match_whodata() {
+        let config = Config::new("linux", Some("test/unit/config/linux/whodata.yml"));
+        assert!(config.match_whodata(0));
+        assert!(!config.match_whodata(1));
+        assert!(config.has_whodata());
+        let config = Config::new("linux", Some("test/unit/config/linux/audit_keys.yml"));
+        assert!(!config.has_whodata());
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_get_audit_index() {
+        let config = Config::new("linux", Some("test/unit/config/linux/audit_keys.yml"));
+        assert_eq!(config.get_audit_index("fim_shadow", "/etc", ""), 0);
+        assert_eq!(config.get_audit_index("fim_shadow", "/other", ""), 0);
+        assert_eq!(config.get_audit_index("fim", "/etc/ssh", ""), 1);
+        assert_eq!(config.get_audit_index("fim", "/tmp", ""), 2);
+        assert_eq!(config.get_audit_index("fim", "./", "/tmp"), 2);
+        assert_eq!(config.get_audit_index("fim", "/other", ""), usize::MAX);
+        assert_eq!(config.get_audit_index("other", "/tmp", ""), usize::MAX);
+    }
+
 }
@@ -0,0 +1,359 @@
+// Copyright (C) 2023, Achiefs.
+
+// Global constants definitions
+pub const ECS_VERSION: &str = "8.11.0";
+
+// To build JSON documents
+use serde_json::{json, Map, Value};
+// To split monitored file paths
+use std::path::Path;
+
+// To reuse the events fields definition
+use crate::schema;
+use crate::schema::FieldType;
+// To detect mass change alerts
+use crate::storm;
+
+// ----------------------------------------------------------------------------
+
+// FIM fields with an Elastic Common Schema equivalent, the rest of fields
+// are kept under the 'fim' object
+const MAPPING: &[(&str, &str, FieldType)] = &[
+    ("@timestamp", "@timestamp", FieldType::Date),
+    ("id", "event.id", FieldType::Keyword),
+    ("sequence", "event.sequence", FieldType::Long),
+    ("hostname", "host.hostname", FieldType::Keyword),
+    ("system", "host.os.type", FieldType::Keyword),
+    ("node", "agent.name", FieldType::Keyword),
+    ("version", "agent.version", FieldType::Keyword),
+    ("rule_id", "rule.id", FieldType::Keyword),
+    ("rule_description", "rule.description", FieldType::Keyword),
+    ("tags", "tags", FieldType::KeywordList),
+    ("package", "package.name", FieldType::Keyword),
+    ("package_version", "package.version", FieldType::Keyword),
+    ("inode", "file.inode", FieldType::Keyword),
+    ("dev", "file.device", FieldType::Keyword),
+    ("mode", "file.mode", FieldType::Keyword),
+    ("inode_type", "file.type", FieldType::Keyword),
+    ("ouid", "file.uid", FieldType::Keyword),
+    ("ogid", "file.gid", FieldType::Keyword),
+    ("ouid_name", "file.owner", FieldType::Keyword),
+    ("ogid_name", "file.group", FieldType::Keyword),
+    ("pid", "process.pid", FieldType::OptionalLong),
+    ("ppid", "process.parent.pid", FieldType::OptionalLong),
+    ("process", "process.executable", FieldType::Keyword),
+    ("exe", "process.executable", FieldType::Keyword),
+    ("comm", "process.name", FieldType::Keyword),
+    ("command", "process.command_line", FieldType::Keyword),
+    ("argv", "process.args", FieldType::KeywordList),
+    ("cwd", "process.working_directory", FieldType::Keyword),
+    ("user", "user.name", FieldType::Keyword),
+    ("uid", "user.id", FieldType::Keyword),
+    ("uid_name", "user.name", FieldType::Keyword),
+    ("euid", "user.effective.id", FieldType::Keyword),
+    ("euid_name", "user.effective.name", FieldType::Keyword),
+    ("auid", "user.audit.id", FieldType::Keyword),
+    ("auid_name", "user.audit.name", FieldType::Keyword),
+    ("gid", "user.group.id", FieldType::Keyword),
+    ("gid_name", "user.group.name", FieldType::Keyword),
+    ("egid", "user.effective.group.id", FieldType::Keyword),
+    ("egid_name", "user.effective.group.name", FieldType::Keyword)
+];
+
+// ECS fields computed from several FIM fields
+const DERIVED: &[(&str, FieldType)] = &[
+    ("ecs.version", FieldType::Keyword),
+    ("event.kind", FieldType::Keyword),
+    ("event.module", FieldType::Keyword),
+    ("event.action", FieldType::Keyword),
+    ("event.category", FieldType::KeywordList),
+    ("event.type", FieldType::KeywordList),
+    ("event.outcome", FieldType::Keyword),
+    ("host.name", FieldType::Keyword),
+    ("file.path", FieldType::Keyword),
+    ("file.name", FieldType::Keyword),
+    ("file.directory", FieldType::Keyword)
+];
+
+// FIM fields consumed by the derived ECS fields
+const CONSUMED: [&str; 5] = ["file", "path", "action", "detailed_operation", "success"];
+
+// ----------------------------------------------------------------------------
+
+// All fields of the ECS documents with their type
+pub fn get_fields() -> Vec<(String, FieldType)> {
+    let mut fields: Vec<(String, FieldType)> = Vec::new();
+    let ecs = MAPPING.iter().map(|(_, ecs, field_type)| (*ecs, *field_type))
+        .chain(DERIVED.iter().copied());
+    for (name, field_type) in ecs {
+        if ! fields.iter().any(|(field, _)| field == name) {
+            fields.push((String::from(name), field_type));
+        }
+    }
+    fields.extend(schema::FIELDS.iter()
+        .filter(|(name, _)| ! is_translated(name))
+        .map(|(name, field_type)| (format!("fim.{}", name), *field_type)));
+    fields
+}
+
+// ----------------------------------------------------------------------------
+
+// Index template of the ECS documents
+pub fn get_template() -> Value {
+    schema::build_template(&get_fields(), json!({
+        "schema_version": schema::SCHEMA_VERSION,
+        "ecs_version": ECS_VERSION
+    }))
+}
+
+// ----------------------------------------------------------------------------
+
+fn is_translated(name: &str) -> bool {
+    CONSUMED.contains(&name) || MAPPING.iter().any(|(field, _, _)| *field == name)
+}
+
+// ----------------------------------------------------------------------------
+
+// Insert a value in a nested object following a dotted path
+fn insert(object: &mut Map<String, Value>, path: &str, value: Value) {
+    match path.split_once('.') {
+        Some((parent, child)) => {
+            let entry = object.entry(parent).or_insert_with(|| json!({}));
+            if let Some(child_object) = entry.as_object_mut() {
+                insert(child_object, child, value);
+            }
+        },
+        None => {
+            // First mapped field wins, i.e 'exe' over 'process'
+            object.entry(path).or_insert(value);
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Get ECS event.type value from the event action
+fn get_type(action: &str) -> &'static str {
+    match action {
+        a if a.contains("create") => "creation",
+        a if a.contains("unlink") || a.contains("remove") || a.contains("delete") => "deletion",
+        a if a.contains("open") || a.contains("access") => "access",
+        a if a.contains("write") || a.contains("modify") || a.contains("rename") ||
+            a.contains("chmod") || a.contains("chown") || a.contains("xattr") ||
+            a.contains("capability") => "change",
+        _ => "info"
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Translate a serialized FIM event into an Elastic Common Schema document,
+// empty and null values are not included
+pub fn from_event(event: &Value) -> Value {
+    let mut document = Map::new();
+    let fields = match event.as_object() {
+        Some(fields) => fields,
+        None => return Value::Object(document)
+    };
+    let get = |name: &str| fields.get(name).and_then(|v| v.as_str()).unwrap_or_default();
+
+    for (name, value) in fields {
+        if value.is_null() || value.as_str().map(|v| v.is_empty()).unwrap_or(false) ||
+            CONSUMED.contains(&name.as_str()) {
+            continue;
+        }
+        match MAPPING.iter().find(|(field, _, _)| field == name) {
+            Some((_, ecs, FieldType::Keyword)) if value.is_number() =>
+                insert(&mut document, ecs, Value::from(value.to_string())),
+            Some((_, ecs, _)) => insert(&mut document, ecs, value.clone()),
+            None => insert(&mut document, &format!("fim.{}", name), value.clone())
+        }
+    }
+
+    // Audit events split the path in directory and name
+    let (path, directory, name) = match (get("path"), get("file")) {
+        ("", "") => (String::new(), String::new(), String::new()),
+        (path, "") => (String::from(path), String::new(), String::new()),
+        ("", file) => {
+            let file_path = Path::new(file);
+            (String::from(file),
+                file_path.parent().map(|p| p.to_string_lossy().to_string()).unwrap_or_default(),
+                file_path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default())
+        },
+        (path, file) => (format!("{}/{}", path.trim_end_matches('/'), file),
+            String::from(path), String::from(file))
+    };
+    for (field, value) in [("file.path", path), ("file.directory", directory), ("file.name", name)] {
+        if ! value.is_empty() { insert(&mut document, field, Value::from(value)); }
+    }
+
+    let operation = get("operation");
+    let action = [get("action"), get("detailed_operation"), operation].into_iter()
+        .find(|v| ! v.is_empty()).unwrap_or_default().to_lowercase();
+    let kind = match operation == storm::STORM_OPERATION {
+        true => "alert",
+        false => "event"
+    };
+    insert(&mut document, "ecs.version", Value::from(ECS_VERSION));
+    insert(&mut document, "event.kind", Value::from(kind));
+    insert(&mut document, "event.module", Value::from("fim"));
+    insert(&mut document, "event.category", json!(["file"]));
+    insert(&mut document, "event.type", json!([get_type(&action)]));
+    if ! action.is_empty() {
+        insert(&mut document, "event.action", Value::from(action));
+    }
+    match get("success") {
+        "yes" => insert(&mut document, "event.outcome", Value::from("success")),
+        "no" => insert(&mut document, "event.outcome", Value::from("failure")),
+        _ => ()
+    }
+    if let Some(hostname) = fields.get("hostname").filter(|v| v.is_string()) {
+        insert(&mut document, "host.name", hostname.clone());
+    }
+    Value::Object(document)
+}
+
+// ----------------------------------------------------------------------------
+
+// Dotted fields of an ECS document that are not defined in the ECS template
+// or whose value does not match their type
+pub fn validate(document: &Value) -> Vec<String> {
+    let fields = get_fields();
+    let mut errors = Vec::new();
+    let mut pending: Vec<(String, &Value)> = vec![(String::new(), document)];
+    while let Some((prefix, value)) = pending.pop() {
+        match (value.as_object(), fields.iter().find(|(field, _)| *field == prefix)) {
+            (_, Some((_, field_type))) => if ! schema::match_type(*field_type, value) {
+                errors.push(format!("Field '{}' value {} is not {:?}", prefix, value, field_type));
+            },
+            (Some(object), None) => pending.extend(object.iter().map(|(name, child)| {
+                match prefix.is_empty() {
+                    true => (name.clone(), child),
+                    false => (format!("{}.{}", prefix, name), child)
+                }
+            })),
+            (None, None) => errors.push(format!("Field '{}' is not defined", prefix))
+        }
+    }
+    errors
+}
+
+// ----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_fields() {
+        let fields = get_fields();
+        for (name, _) in &fields {
+            assert_eq!(fields.iter().filter(|(field, _)| field == name).count(), 1, "{}", name);
+        }
+        assert!(fields.contains(&(String::from("process.pid"), FieldType::OptionalLong)));
+        assert!(fields.contains(&(String::from("fim.checksum"), FieldType::Keyword)));
+        assert!(!fields.iter().any(|(name, _)| name == "fim.file" || name == "fim.uid"));
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_get_template() {
+        let template = get_template();
+        assert_eq!(template["mappings"]["_meta"]["ecs_version"], ECS_VERSION);
+        let properties = &template["mappings"]["properties"];
+        assert_eq!(properties["file"]["properties"]["path"]["type"], "keyword");
+        assert_eq!(properties["user"]["properties"]["effective"]["properties"]["group"]
+            ["properties"]["id"]["type"], "keyword");
+        assert_eq!(properties["fim"]["properties"]["paths"]["type"], "nested");
+        assert_eq!(properties["@timestamp"]["type"], "date");
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_get_type() {
+        assert_eq!(get_type("create_file"), "creation");
+        assert_eq!(get_type("unlink_denied"), "deletion");
+        assert_eq!(get_type("remove_file"), "deletion");
+        assert_eq!(get_type("access_close_write"), "access");
+        assert_eq!(get_type("open"), "access");
+        assert_eq!(get_type("modify_data_content"), "change");
+        assert_eq!(get_type("capability_change"), "change");
+        assert_eq!(get_type("normal"), "info");
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_from_event_monitor() {
+        let event = json!({ "id": "1", "@timestamp": "2022-07-28T16:40:49.689Z",
+            "file": "/etc/hosts", "operation": "CREATE", "detailed_operation": "CREATE_FILE",
+            "hostname": "host", "pid": 1000, "process": "/usr/bin/vim", "user": "alice",
+            "command": "", "checksum": "UNKNOWN", "tags": ["test"] });
+        let document = from_event(&event);
+        assert_eq!(document["file"]["path"], "/etc/hosts");
+        assert_eq!(document["file"]["directory"], "/etc");
+        assert_eq!(document["file"]["name"], "hosts");
+        assert_eq!(document["event"]["action"], "create_file");
+        assert_eq!(document["event"]["type"], json!(["creation"]));
+        assert_eq!(document["event"]["kind"], "event");
+        assert_eq!(document["process"]["pid"], 1000);
+        assert_eq!(document["process"]["executable"], "/usr/bin/vim");
+        assert_eq!(document["user"]["name"], "alice");
+        assert_eq!(document["host"]["name"], "host");
+        assert_eq!(document["fim"]["operation"], "CREATE");
+        assert_eq!(document["fim"]["checksum"], "UNKNOWN");
+        assert_eq!(document["tags"], json!(["test"]));
+        assert!(document["process"].get("command_line").is_none());
+        assert!(document.get("detailed_operation").is_none());
+        assert_eq!(validate(&document), Vec::<String>::new());
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_from_event_audit() {
+        let event = json!({ "id": "1", "path": "/tmp/", "file": "file.txt", "operation": "DELETE",
+            "action": "unlink_denied", "success": "no", "uid": 0, "uid_name": "root",
+            "euid": null, "inode": 123, "ppid": 1, "exe": "/usr/bin/rm", "syscall": "263" });
+        let document = from_event(&event);
+        assert_eq!(document["file"]["path"], "/tmp/file.txt");
+        assert_eq!(document["file"]["directory"], "/tmp/");
+        assert_eq!(document["file"]["name"], "file.txt");
+        assert_eq!(document["file"]["inode"], "123");
+        assert_eq!(document["event"]["action"], "unlink_denied");
+        assert_eq!(document["event"]["type"], json!(["deletion"]));
+        assert_eq!(document["event"]["outcome"], "failure");
+        assert_eq!(document["user"]["id"], "0");
+        assert_eq!(document["user"]["name"], "root");
+        assert!(document["user"].get("effective").is_none());
+        assert_eq!(document["process"]["parent"]["pid"], 1);
+        assert_eq!(document["fim"]["syscall"], "263");
+        assert_eq!(validate(&document), Vec::<String>::new());
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_from_event_storm() {
+        let event = json!({ "id": "1", "operation": storm::STORM_OPERATION, "path": "/tmp",
+            "count": 10, "files": ["/tmp/a"], "entropy": null });
+        let document = from_event(&event);
+        assert_eq!(document["event"]["kind"], "alert");
+        assert_eq!(document["event"]["action"], "storm");
+        assert_eq!(document["file"]["path"], "/tmp");
+        assert_eq!(document["fim"]["count"], 10);
+        assert_eq!(validate(&document), Vec::<String>::new());
+        assert_eq!(from_event(&json!([])), json!({}));
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_validate() {
+        let errors = validate(&json!({ "process": { "pid": "1" }, "unknown": 1 }));
+        assert_eq!(errors.len(), 2);
+        assert!(errors.contains(&String::from("Field 'unknown' is not defined")));
+    }
+}
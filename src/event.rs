@@ -2,128 +2,58 @@
 
 // To implement Debug and fmt method
 use std::fmt;
-// To handle files
-use std::fs::OpenOptions;
-use std::io::Write;
-// Handle time intervals
-use std::time::Duration;
 // Event handling
 use notify::event::*;
-// To log the program procedure
-use log::*;
-// To handle JSON objects
-use serde_json::{json, to_string};
 // To manage paths
 use std::path::PathBuf;
-// To manage HTTP requests
-use reqwest::Client;
+// To serialize the events
+use serde::Serialize;
 
-// To get configuration constants
-use crate::config;
+// To output the events
+use crate::fimevent::FimEvent;
 
+#[derive(Clone, Serialize)]
 pub struct Event {
     pub id: String,
     pub timestamp: String,
+    #[serde(rename = "@timestamp")]
+    pub iso_timestamp: String,
     pub hostname: String,
     pub node: String,
     pub version: String,
+    #[serde(rename = "file")]
     pub path: PathBuf,
+    #[serde(skip)]
     pub kind: EventKind,
     pub labels: Vec<String>,
     pub operation: String,
     pub detailed_operation: String,
     pub checksum: String,
+    pub entropy: f64,
+    pub file_type: String,
+    pub file_type_changed: bool,
+    pub severity: String,
+    pub rule_id: String,
+    pub rule_description: String,
+    pub tags: Vec<String>,
+    pub expected: bool,
+    pub package: String,
+    pub package_version: String,
+    pub user: String,
+    pub process: String,
+    pub pid: Option<u32>,
+    pub command: String,
     pub fpid: u32,
-    pub system: String
+    pub system: String,
+    pub sequence: u64
 }
 
-impl Event {
-    // Get formatted string with all required data
-    fn format_json(&self) -> String {
-        let obj = json!({
-            "id": self.id.clone(),
-            "timestamp": self.timestamp.clone(),
-            "hostname": self.hostname.clone(),
-            "node": self.node.clone(),
-            "fpid": self.fpid.clone(),
-            "version": self.version.clone(),
-            "labels": self.labels.clone(),
-            "operation": self.operation.clone(),
-            "detailed_operation": self.detailed_operation.clone(),
-            "file": String::from(self.path.clone().to_str().unwrap()),
-            "checksum": self.checksum.clone(),
-            "system": self.system.clone()
-        });
-        to_string(&obj).unwrap()
-    }
-
-    // ------------------------------------------------------------------------
-
-    // Function to write the received events to file
-    pub fn log(&self, file: String){
-        let mut events_file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .append(true)
-            .open(file)
-            .expect("(log) Unable to open events log file.");
-
-        match writeln!(events_file, "{}", self.format_json() ) {
-            Ok(_d) => debug!("Event log written"),
-            Err(e) => error!("Event could not be written, Err: [{}]", e)
-        }
-    }
-
-    // ------------------------------------------------------------------------
-
-    // Function to send events through network
-    pub async fn send(&self, index: String, address: String, user: String, pass: String, insecure: bool) {
-        let data = json!({
-            "timestamp": self.timestamp.clone(),
-            "hostname": self.hostname.clone(),
-            "node": self.node.clone(),
-            "fpid": self.fpid.clone(),
-            "version": self.version.clone(),
-            "labels": self.labels.clone(),
-            "operation": self.operation.clone(),
-            "detailed_operation": self.detailed_operation.clone(),
-            "file": String::from(self.path.clone().to_str().unwrap()),
-            "checksum": self.checksum.clone(),
-            "system": self.system.clone()
-        });
-
-        let request_url = format!("{}/{}/_doc/{}", address, index, self.id);
-        let client = Client::builder()
-            .danger_accept_invalid_certs(insecure)
-            .timeout(Duration::from_secs(30))
-            .build().unwrap();
-        match client
-            .post(request_url)
-            .basic_auth(user, Some(pass))
-            .json(&data)
-            .send()
-            .await {
-            Ok(response) => debug!("Response received: {:?}", response),
-            Err(e) => debug!("Error on request: {:?}", e)
-        };
-    }
+impl FimEvent for Event {
+    fn get_id(&self) -> &str { &self.id }
 
     // ------------------------------------------------------------------------
 
-    // Function to manage event destination
-    pub async fn process(&self, destination: &str, index_name: String, config: config::Config){
-        match destination {
-            config::BOTH_MODE => {
-                self.log(config.events_file);
-                self.send( index_name, config.endpoint_address, config.endpoint_user, config.endpoint_pass, config.insecure).await;
-            },
-            config::NETWORK_MODE => {
-                self.send( index_name, config.endpoint_address, config.endpoint_user, config.endpoint_pass, config.insecure).await;
-            },
-            _ => self.log(config.events_file)
-        }
-    }
-
+    fn set_sequence(&mut self, sequence: u64) { self.sequence = sequence }
 }
 
 // ----------------------------------------------------------------------------
@@ -217,11 +147,14 @@ pub fn get_detailed_operation(event_kind: EventKind) -> String {
 mod tests {
     use super::*;
     use crate::event::Event;
+    use crate::config;
     use crate::config::Config;
     use crate::utils;
     use std::path::PathBuf;
     use tokio_test::block_on;
     use std::fs;
+    use crate::schema;
+    use crate::ecs;
 
     // ------------------------------------------------------------------------
 
@@ -233,6 +166,7 @@ mod tests {
         Event {
             id: "Test_id".to_string(),
             timestamp: "Timestamp".to_string(),
+            iso_timestamp: "2022-07-28T16:40:49.689Z".to_string(),
             hostname: "Hostname".to_string(),
             node: "FIM".to_string(),
             version: "x.x.x".to_string(),
@@ -242,8 +176,23 @@ mod tests {
             operation: "CREATE".to_string(),
             detailed_operation: "CREATE_FILE".to_string(),
             checksum: "UNKNOWN".to_string(),
+            entropy: 0.0,
+            file_type: "UNKNOWN".to_string(),
+            file_type_changed: false,
+            severity: "low".to_string(),
+            rule_id: "100001".to_string(),
+            rule_description: "Test rule".to_string(),
+            tags: vec!["test".to_string()],
+            expected: false,
+            package: "".to_string(),
+            package_version: "".to_string(),
+            user: "alice".to_string(),
+            process: "/usr/bin/vim".to_string(),
+            pid: Some(1000),
+            command: "vim /etc/hosts".to_string(),
             fpid: 0,
-            system: "test".to_string()
+            system: "test".to_string(),
+            sequence: 0
         }
     }
 
@@ -262,6 +211,20 @@ mod tests {
         assert_eq!(evt.labels, Vec::<String>::new());
         assert_eq!(evt.operation, String::from("CREATE"));
         assert_eq!(evt.detailed_operation, String::from("CREATE_FILE"));
+        assert_eq!(evt.entropy, 0.0);
+        assert_eq!(evt.file_type, String::from("UNKNOWN"));
+        assert!(!evt.file_type_changed);
+        assert_eq!(evt.severity, String::from("low"));
+        assert_eq!(evt.rule_id, String::from("100001"));
+        assert_eq!(evt.rule_description, String::from("Test rule"));
+        assert_eq!(evt.tags, vec![String::from("test")]);
+        assert!(!evt.expected);
+        assert_eq!(evt.package, String::from(""));
+        assert_eq!(evt.package_version, String::from(""));
+        assert_eq!(evt.user, String::from("alice"));
+        assert_eq!(evt.process, String::from("/usr/bin/vim"));
+        assert_eq!(evt.pid, Some(1000));
+        assert_eq!(evt.command, String::from("vim /etc/hosts"));
         assert_eq!(evt.fpid, 0);
         assert_eq!(evt.system, String::from("test"));
     }
@@ -273,7 +236,7 @@ mod tests {
         let evt = create_test_event();
         block_on( evt.send(
             String::from("test"), String::from("https://127.0.0.1:9200"),
-            String::from("admin"), String::from("admin"), true) );
+            String::from("admin"), String::from("admin"), true, config::FIM_FORMAT) );
     }
 
     // ------------------------------------------------------------------------
@@ -389,10 +352,13 @@ mod tests {
     #[test]
     fn test_process() {
         let config = Config::new(&utils::get_os(), None);
-        let event = create_test_event();
+        let mut event = create_test_event();
 
         block_on(event.process(config::NETWORK_MODE, String::from("test"), config.clone()));
+        let sequence = event.sequence;
+        assert!(sequence > 0);
         block_on(event.process(config::FILE_MODE, String::from("test2"), config.clone()));
+        assert!(event.sequence > sequence);
         block_on(event.process(config::BOTH_MODE, String::from("test3"), config.clone()));
     }
 
@@ -406,14 +372,34 @@ mod tests {
 
     // ------------------------------------------------------------------------
 
+    #[test]
+    fn test_get_json_schema() {
+        let json = create_test_event().get_json();
+        assert_eq!(json["schema_version"], schema::SCHEMA_VERSION);
+        assert_eq!(schema::validate(&json), Vec::<String>::new());
+        let ecs = create_test_event().get_output(config::ECS_FORMAT);
+        assert_eq!(ecs["ecs"]["version"], ecs::ECS_VERSION);
+        assert_eq!(ecs::validate(&ecs), Vec::<String>::new());
+    }
+
+    // ------------------------------------------------------------------------
+
     #[test]
     fn test_format_json() {
-        let expected = "{\"checksum\":\"UNKNOWN\",\"detailed_operation\":\"CREATE_FILE\",\
-            \"file\":\"\",\"fpid\":0,\
+        let expected = "{\"@timestamp\":\"2022-07-28T16:40:49.689Z\",\
+            \"checksum\":\"UNKNOWN\",\"command\":\"vim /etc/hosts\",\
+            \"detailed_operation\":\"CREATE_FILE\",\
+            \"entropy\":0.0,\"expected\":false,\"file\":\"\",\"file_type\":\"UNKNOWN\",\
+            \"file_type_changed\":false,\"fpid\":0,\
             \"hostname\":\"Hostname\",\"id\":\"Test_id\",\"labels\":[],\
-            \"node\":\"FIM\",\"operation\":\"CREATE\",\"system\":\"test\",\
-            \"timestamp\":\"Timestamp\",\"version\":\"x.x.x\"}";
-        assert_eq!(create_test_event().format_json(), expected);
+            \"node\":\"FIM\",\"operation\":\"CREATE\",\
+            \"package\":\"\",\"package_version\":\"\",\"pid\":1000,\
+            \"process\":\"/usr/bin/vim\",\
+            \"rule_description\":\"Test rule\",\"rule_id\":\"100001\",\
+            \"schema_version\":\"1.1\",\
+            \"sequence\":0,\"severity\":\"low\",\"system\":\"test\",\"tags\":[\"test\"],\
+            \"timestamp\":\"Timestamp\",\"user\":\"alice\",\"version\":\"x.x.x\"}";
+        assert_eq!(create_test_event().format_json(config::FIM_FORMAT), expected);
     }
 
     // ------------------------------------------------------------------------
@@ -423,14 +409,21 @@ mod tests {
         let filename = String::from("test_event.json");
         let evt = create_test_event();
 
-        evt.log(filename.clone());
+        evt.log(&filename, config::FIM_FORMAT);
         let contents = fs::read_to_string(filename.clone());
-        let expected = "{\"checksum\":\"UNKNOWN\",\"detailed_operation\":\"CREATE_FILE\",\
-            \"file\":\"\",\"fpid\":0,\
+        let expected = "{\"@timestamp\":\"2022-07-28T16:40:49.689Z\",\
+            \"checksum\":\"UNKNOWN\",\"command\":\"vim /etc/hosts\",\
+            \"detailed_operation\":\"CREATE_FILE\",\
+            \"entropy\":0.0,\"expected\":false,\"file\":\"\",\"file_type\":\"UNKNOWN\",\
+            \"file_type_changed\":false,\"fpid\":0,\
             \"hostname\":\"Hostname\",\"id\":\"Test_id\",\"labels\":[],\
             \"node\":\"FIM\",\"operation\":\"CREATE\",\
-            \"system\":\"test\",\
-            \"timestamp\":\"Timestamp\",\"version\":\"x.x.x\"}\n";
+            \"package\":\"\",\"package_version\":\"\",\"pid\":1000,\
+            \"process\":\"/usr/bin/vim\",\
+            \"rule_description\":\"Test rule\",\"rule_id\":\"100001\",\
+            \"schema_version\":\"1.1\",\
+            \"sequence\":0,\"severity\":\"low\",\"system\":\"test\",\"tags\":[\"test\"],\
+            \"timestamp\":\"Timestamp\",\"user\":\"alice\",\"version\":\"x.x.x\"}\n";
         assert_eq!(contents.unwrap(), expected);
         remove_test_file(filename.clone());
     }
@@ -0,0 +1,171 @@
+// Copyright (C) 2023, Achiefs.
+
+// Global constants definitions
+// Tag of events that add setuid, setgid or world write permissions
+pub const RISKY_PERMISSIONS_TAG: &str = "risky_permissions";
+const S_IFMT: u32 = 0o170000;
+const S_ISUID: u32 = 0o4000;
+const S_ISGID: u32 = 0o2000;
+const S_ISVTX: u32 = 0o1000;
+const S_IWOTH: u32 = 0o0002;
+const RISKY_BITS: u32 = S_ISUID | S_ISGID | S_IWOTH;
+
+// To read syscall arguments
+use std::collections::HashMap;
+
+// ----------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMode {
+    // Reported as inode_type, the file_type field is the magic bytes type
+    pub inode_type: String,
+    pub permissions: String,
+    pub setuid: bool,
+    pub setgid: bool,
+    pub sticky: bool
+}
+
+// ----------------------------------------------------------------------------
+
+// Parse an octal audit mode like '0100644', None if it is not valid
+pub fn parse_mode(mode: &str) -> Option<u32> {
+    u32::from_str_radix(mode, 8).ok()
+}
+
+// ----------------------------------------------------------------------------
+
+// Decode the type, permissions and special bits of a mode
+pub fn decode(mode: u32) -> FileMode {
+    let inode_type = match mode & S_IFMT {
+        0o140000 => "socket",
+        0o120000 => "symlink",
+        0o100000 => "regular",
+        0o060000 => "block_device",
+        0o040000 => "directory",
+        0o020000 => "char_device",
+        0o010000 => "fifo",
+        _ => "unknown"
+    };
+    FileMode {
+        inode_type: String::from(inode_type),
+        permissions: get_permissions(mode),
+        setuid: mode & S_ISUID != 0,
+        setgid: mode & S_ISGID != 0,
+        sticky: mode & S_ISVTX != 0
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Permissions in 'ls' format, special bits replace the execute ones
+pub fn get_permissions(mode: u32) -> String {
+    let special = [(S_ISUID, 's'), (S_ISGID, 's'), (S_ISVTX, 't')];
+    (0..3).map(|i| {
+        let bits = (mode >> (6 - i * 3)) & 0o7;
+        let (flag, letter) = special[i as usize];
+        let execute = match (mode & flag != 0, bits & 1 != 0) {
+            (true, true) => letter,
+            (true, false) => letter.to_ascii_uppercase(),
+            (false, true) => 'x',
+            (false, false) => '-'
+        };
+        format!("{}{}{}", if bits & 4 != 0 { 'r' } else { '-' },
+            if bits & 2 != 0 { 'w' } else { '-' }, execute)
+    }).collect()
+}
+
+// ----------------------------------------------------------------------------
+
+// Mode after a chmod syscall, the new permissions are a hex syscall argument
+pub fn apply_chmod(previous: u32, syscall_name: &str, syscall: &HashMap<String, String>) -> Option<u32> {
+    let argument = match syscall_name {
+        "chmod" | "fchmod" => "a1",
+        "fchmodat" | "fchmodat2" => "a2",
+        _ => return None
+    };
+    let permissions = u32::from_str_radix(syscall.get(argument)?, 16).ok()? & 0o7777;
+    Some((previous & ! 0o7777) | permissions)
+}
+
+// ----------------------------------------------------------------------------
+
+// Check if the new mode adds setuid, setgid or world write permissions,
+// a missing previous mode means a new file
+pub fn adds_risky_bits(previous: Option<u32>, current: u32) -> bool {
+    let previous = previous.map(|p| p & RISKY_BITS).unwrap_or(0);
+    (current & RISKY_BITS) & ! previous != 0
+}
+
+// ----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(parse_mode("0100644"), Some(0o100644));
+        assert_eq!(parse_mode("040755"), Some(0o40755));
+        assert_eq!(parse_mode("mode"), None);
+        assert_eq!(parse_mode(""), None);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_decode() {
+        let mode = decode(0o100644);
+        assert_eq!(mode.inode_type, "regular");
+        assert_eq!(mode.permissions, "rw-r--r--");
+        assert!(!mode.setuid && !mode.setgid && !mode.sticky);
+        assert_eq!(decode(0o41777).inode_type, "directory");
+        assert!(decode(0o41777).sticky);
+        assert_eq!(decode(0o120777).inode_type, "symlink");
+        assert_eq!(decode(0o140755).inode_type, "socket");
+        assert_eq!(decode(0o20620).inode_type, "char_device");
+        assert_eq!(decode(0o60660).inode_type, "block_device");
+        assert_eq!(decode(0o10644).inode_type, "fifo");
+        assert_eq!(decode(0o644).inode_type, "unknown");
+        assert!(decode(0o104755).setuid);
+        assert!(decode(0o102755).setgid);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_get_permissions() {
+        assert_eq!(get_permissions(0o755), "rwxr-xr-x");
+        assert_eq!(get_permissions(0o4755), "rwsr-xr-x");
+        assert_eq!(get_permissions(0o4644), "rwSr--r--");
+        assert_eq!(get_permissions(0o2750), "rwxr-s---");
+        assert_eq!(get_permissions(0o1777), "rwxrwxrwt");
+        assert_eq!(get_permissions(0o1776), "rwxrwxrwT");
+        assert_eq!(get_permissions(0), "---------");
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_apply_chmod() {
+        let syscall = HashMap::from([ (String::from("a1"), String::from("9ed")),
+            (String::from("a2"), String::from("1ff")) ]);
+        assert_eq!(apply_chmod(0o100755, "chmod", &syscall), Some(0o104755));
+        assert_eq!(apply_chmod(0o100644, "fchmodat", &syscall), Some(0o100777));
+        assert_eq!(apply_chmod(0o100644, "unlink", &syscall), None);
+        assert_eq!(apply_chmod(0o100644, "fchmod", &HashMap::new()), None);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_adds_risky_bits() {
+        assert!(adds_risky_bits(Some(0o100755), 0o104755));
+        assert!(adds_risky_bits(Some(0o100644), 0o100646));
+        assert!(adds_risky_bits(Some(0o104755), 0o106755));
+        assert!(!adds_risky_bits(Some(0o104755), 0o104755));
+        assert!(!adds_risky_bits(Some(0o104755), 0o100755));
+        assert!(!adds_risky_bits(Some(0o100644), 0o100600));
+        assert!(adds_risky_bits(None, 0o102755));
+        assert!(!adds_risky_bits(None, 0o100644));
+    }
+}
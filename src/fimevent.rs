@@ -0,0 +1,111 @@
+// Copyright (C) 2023, Achiefs.
+
+// To handle files
+use std::fs::OpenOptions;
+use std::io::Write;
+// Handle time intervals
+use std::time::Duration;
+// To log the program procedure
+use log::{debug, error};
+// To serialize the events
+use serde::Serialize;
+use serde_json::to_string;
+// To manage HTTP requests
+use reqwest::Client;
+
+// To get configuration constants
+use crate::config;
+// To number the produced events
+use crate::sequence;
+// To tag events with the schema they follow
+use crate::schema;
+// To translate events to Elastic Common Schema
+use crate::ecs;
+
+// ----------------------------------------------------------------------------
+
+// Output of all produced events, the serialized struct is the document
+// written to the events file and sent to the indexer
+pub trait FimEvent: Serialize {
+    fn get_id(&self) -> &str;
+    fn set_sequence(&mut self, sequence: u64);
+
+    // ------------------------------------------------------------------------
+
+    // Serialized event tagged with the schema version it follows
+    fn get_json(&self) -> serde_json::Value {
+        let mut data = serde_json::to_value(self).unwrap();
+        data["schema_version"] = serde_json::Value::from(schema::SCHEMA_VERSION);
+        data
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Get the event document in the configured events format
+    fn get_output(&self, format: &str) -> serde_json::Value {
+        match format {
+            config::ECS_FORMAT => ecs::from_event(&self.get_json()),
+            _ => self.get_json()
+        }
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Get formatted string with all required data
+    fn format_json(&self, format: &str) -> String { to_string(&self.get_output(format)).unwrap() }
+
+    // ------------------------------------------------------------------------
+
+    // Function to write the event to file
+    fn log(&self, file: &str, format: &str) {
+        let mut events_file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(file)
+            .expect("(fimevent::log) Unable to open events log file.");
+
+        match writeln!(events_file, "{}", self.format_json(format)) {
+            Ok(_d) => debug!("Event log written"),
+            Err(e) => error!("Event could not be written, Err: [{}]", e)
+        };
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Function to send the event through network
+    async fn send(&self, index: String, address: String, user: String, pass: String, insecure: bool, format: &str) {
+        let data = self.get_output(format);
+
+        let request_url = format!("{}/{}/_doc/{}", address, index, self.get_id());
+        let client = Client::builder()
+            .danger_accept_invalid_certs(insecure)
+            .timeout(Duration::from_secs(30))
+            .build().unwrap();
+        match client
+            .post(request_url)
+            .basic_auth(user, Some(pass))
+            .json(&data)
+            .send()
+            .await {
+            Ok(response) => debug!("Response received: {:?}", response),
+            Err(e) => debug!("Error on request: {:?}", e)
+        };
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Function to manage event destination
+    async fn process(&mut self, destination: &str, index_name: String, config: config::Config) {
+        self.set_sequence(sequence::next(&config.events_sequence_file));
+        match destination {
+            config::BOTH_MODE => {
+                self.log(&config.events_file, &config.events_format);
+                self.send(index_name, config.endpoint_address, config.endpoint_user, config.endpoint_pass, config.insecure, &config.events_format).await;
+            },
+            config::NETWORK_MODE => {
+                self.send(index_name, config.endpoint_address, config.endpoint_user, config.endpoint_pass, config.insecure, &config.events_format).await;
+            },
+            _ => self.log(&config.events_file, &config.events_format)
+        }
+    }
+}
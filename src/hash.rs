@@ -2,6 +2,35 @@
 
 // Constants definitions
 const READ_CAPACITY: usize = 1024 * 1024 * 8; // Read file in chunks of 8MB
+const HEADER_SIZE: usize = 512; // Bytes used to detect the file type
+pub const MAX_KNOWN_TYPES: usize = 65536; // Paths tracked to detect file type changes
+// Known file signatures sorted to match the longest ones first
+const MAGIC_BYTES: [(&[u8], &str); 24] = [
+    (b"SQLite format 3\x00", "sqlite"),
+    (b"\xFD7zXZ\x00", "xz"),
+    (b"7z\xBC\xAF\x27\x1C", "7z"),
+    (b"Rar!\x1A\x07", "rar"),
+    (b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1", "ole"),
+    (b"\x89PNG", "png"),
+    (b"\x7FELF", "elf"),
+    (b"\xFE\xED\xFA\xCE", "macho"),
+    (b"\xFE\xED\xFA\xCF", "macho"),
+    (b"\xCE\xFA\xED\xFE", "macho"),
+    (b"\xCF\xFA\xED\xFE", "macho"),
+    (b"\xCA\xFE\xBA\xBE", "macho"),
+    (b"PK\x03\x04", "zip"),
+    (b"%PDF", "pdf"),
+    (b"GIF8", "gif"),
+    (b"OggS", "ogg"),
+    (b"RIFF", "riff"),
+    (b"{\\rtf", "rtf"),
+    (b"\xFF\xD8\xFF", "jpeg"),
+    (b"BZh", "bzip2"),
+    (b"ID3", "mp3"),
+    (b"\x1F\x8B", "gzip"),
+    (b"MZ", "pe"),
+    (b"#!", "script"),
+];
 
 // To get file checksums
 use hex::{encode, decode};
@@ -14,51 +43,187 @@ use std::str;
 use std::fs::File;
 use std::path::Path;
 // To read file content
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Read};
+// To keep the last known file types
+use std::collections::{HashMap, VecDeque};
 
-// To calculate file content hash in sha512 format (SHA3 implementation)
-pub fn get_checksum(filename: String, read_limit: usize) -> String {
+// To calculate in a single pass over the file content its hash in sha512
+// format (SHA3 implementation), its Shannon entropy and its magic bytes type.
+// Files above the read limit (in MB) have no hash and their entropy is
+// calculated over the first read limit bytes.
+pub fn get_file_info(filename: String, read_limit: usize) -> (String, f64, String) {
     let mut hasher = Sha3_512::new();
-    let mut length = 1;
-    let mut iteration = 0;
-    let mut data_read = 0;
-    
-    if Path::new(&filename).is_file() { 
+    let mut frequencies = [0u64; 256];
+    let mut header: Vec<u8> = Vec::new();
+
+    if Path::new(&filename).is_file() {
         debug!("Getting hash of file: {}", filename);
         match File::open(filename.clone()){
             Ok(file) => {
-                let mut reader = BufReader::with_capacity(READ_CAPACITY, file);
-
-                while length > 0 && data_read <= read_limit {
-                    if iteration == 2 {
-                        info!("Big file detected, the hash will take a while");
+                let limit = read_limit as u64 * 1024 * 1024;
+                let above_limit = file.metadata().map(|m| m.len() > limit).unwrap_or(false);
+                let total = read_content(file, limit, |buffer| {
+                    if ! above_limit { hasher.update(buffer) }
+                    buffer.iter().for_each(|byte| frequencies[*byte as usize] += 1);
+                    if header.len() < HEADER_SIZE {
+                        let missing = HEADER_SIZE - header.len();
+                        header.extend_from_slice(&buffer[..missing.min(buffer.len())]);
                     }
-                    
-                    length = {
-                        let buffer = reader.fill_buf().unwrap();
-                        hasher.update(buffer);
-                        buffer.len()
-                    };
-                    reader.consume(length);
-                    data_read += length / (1024 * 1024);
-                    iteration += 1;
-                };
-                if data_read > read_limit {
+                });
+                let entropy = calculate_entropy(&frequencies, total);
+                let file_type = get_file_type(&header);
+                if above_limit {
                     info!("File '{}' checksum skipped. File size is above limit", filename);
-                    String::from("UNKNOWN")
+                    (String::from("UNKNOWN"), entropy, file_type)
                 }else{
-                    encode(hasher.finalize())
+                    (encode(hasher.finalize()), entropy, file_type)
                 }
             },
             Err(e) => {
                 debug!("Cannot open file to get checksum, error: {:?}", e);
-                String::from("UNKNOWN")
+                (String::from("UNKNOWN"), 0.0, String::from("UNKNOWN"))
             }
         }
-    }else{
+    }else if Path::new(&filename).is_dir() {
         debug!("Cannot produce checksum of a directory");
-        String::from("UNKNOWN")
+        (String::from("UNKNOWN"), 0.0, String::from("directory"))
+    }else{
+        debug!("Cannot produce checksum of a missing file");
+        (String::from("UNKNOWN"), 0.0, String::from("UNKNOWN"))
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Read up to 'limit' bytes of a file in chunks, returns the bytes read
+fn read_content(file: File, limit: u64, mut process: impl FnMut(&[u8])) -> u64 {
+    let mut reader = BufReader::with_capacity(READ_CAPACITY, file.take(limit));
+    let mut total: u64 = 0;
+    let mut iteration = 0;
+    loop {
+        if iteration == 2 {
+            info!("Big file detected, the hash will take a while");
+        }
+        let length = match reader.fill_buf() {
+            Ok(buffer) => {
+                process(buffer);
+                buffer.len()
+            },
+            Err(e) => {
+                debug!("Cannot read file content, error: {:?}", e);
+                0
+            }
+        };
+        if length == 0 { return total }
+        reader.consume(length);
+        total += length as u64;
+        iteration += 1;
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// To calculate the Shannon entropy of file content in bits per byte (0 to 8),
+// without hashing it, over the first read limit (in MB) bytes
+pub fn get_entropy(filename: String, read_limit: usize) -> f64 {
+    if ! Path::new(&filename).is_file() {
+        return 0.0
+    }
+    match File::open(filename) {
+        Ok(file) => {
+            let mut frequencies = [0u64; 256];
+            let total = read_content(file, read_limit as u64 * 1024 * 1024,
+                |buffer| buffer.iter().for_each(|byte| frequencies[*byte as usize] += 1));
+            calculate_entropy(&frequencies, total)
+        },
+        Err(e) => {
+            debug!("Cannot open file to get entropy, error: {:?}", e);
+            0.0
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Detect the file type based on the magic bytes found at the file header
+pub fn get_file_type(header: &[u8]) -> String {
+    let file_type = if header.is_empty() { "empty" }
+    else if let Some((_, name)) = MAGIC_BYTES.iter().find(|(magic, _)| header.starts_with(magic)) { name }
+    else if header.len() > 262 && &header[257..262] == b"ustar" { "tar" }
+    else if header.len() > 11 && &header[4..8] == b"ftyp" { "mp4" }
+    else if is_text(header) { "text" }
+    else { "data" };
+    String::from(file_type)
+}
+
+// ----------------------------------------------------------------------------
+
+// Returns if the given bytes are printable UTF-8, ignoring a cut last character
+fn is_text(bytes: &[u8]) -> bool {
+    let text = match str::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => str::from_utf8(&bytes[..e.valid_up_to()]).unwrap(),
+        Err(_) => return false
+    };
+    text.chars().all(|c| !c.is_control() || c.is_whitespace())
+}
+
+// ----------------------------------------------------------------------------
+
+// Last known file type of each path, the oldest tracked paths are forgotten
+// once the capacity is reached to keep memory bounded
+pub struct KnownTypes {
+    types: HashMap<String, String>,
+    order: VecDeque<String>,
+    capacity: usize
+}
+
+impl KnownTypes {
+    pub fn new(capacity: usize) -> Self {
+        KnownTypes { types: HashMap::new(), order: VecDeque::new(), capacity }
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Store the type of a path returning the previous one
+    fn insert(&mut self, path: &str, file_type: &str) -> Option<String> {
+        let previous = self.types.insert(String::from(path), String::from(file_type));
+        if previous.is_none() {
+            self.order.push_back(String::from(path));
+            while self.order.len() > self.capacity {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.types.remove(&oldest);
+                }
+            }
+        }
+        previous
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Returns if the file type of a path is different from its last known type
+pub fn file_type_changed(known_types: &mut KnownTypes, path: &str, file_type: &str) -> bool {
+    if file_type == "UNKNOWN" || file_type == "empty" || file_type == "directory" {
+        return false
     }
+    match known_types.insert(path, file_type) {
+        Some(previous) if previous != file_type => {
+            warn!("File '{}' changed its type from '{}' to '{}'", path, previous, file_type);
+            true
+        },
+        _ => false
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+fn calculate_entropy(frequencies: &[u64; 256], total: u64) -> f64 {
+    if total == 0 { return 0.0 }
+    frequencies.iter().filter(|f| **f > 0).map(|f| {
+        let probability = *f as f64 / total as f64;
+        -probability * probability.log2()
+    }).sum()
 }
 
 // ----------------------------------------------------------------------------
@@ -72,7 +237,8 @@ pub fn hex_to_ascii(hex: String) -> String {
             Vec::new()
         }
     };
-    String::from(str::from_utf8(&bytes).unwrap())
+    // Decoded data could be any binary content, invalid characters are replaced
+    String::from_utf8_lossy(&bytes)
         .replace('\u{0000}', " ")
 }
 
@@ -101,7 +267,7 @@ mod tests {
     fn test_get_checksum_file() {
         let filename = String::from("test_get_checksum_file");
         create_test_file(filename.clone());
-        assert_eq!(get_checksum(filename.clone(), MAX_FILE_READ), String::from("46512636eeeb22dee0d60f3aba6473b1fb3258dc0c9ed6fbdbf26bed06df796bc70d4c1f6d50ca977b45f35b494e4bd9fb34e55a1576d6d9a3b5e1ab059953ee"));
+        assert_eq!(get_file_info(filename.clone(), MAX_FILE_READ).0, String::from("46512636eeeb22dee0d60f3aba6473b1fb3258dc0c9ed6fbdbf26bed06df796bc70d4c1f6d50ca977b45f35b494e4bd9fb34e55a1576d6d9a3b5e1ab059953ee"));
         remove_test_file(filename.clone());
     }
 
@@ -109,8 +275,8 @@ mod tests {
 
     #[test]
     fn test_get_checksum_not_exists() {
-        assert_ne!(get_checksum(String::from("not_exists"), MAX_FILE_READ), String::from("This is a test"));
-        assert_eq!(get_checksum(String::from("not_exists"), MAX_FILE_READ), String::from("UNKNOWN"));
+        assert_ne!(get_file_info(String::from("not_exists"), MAX_FILE_READ).0, String::from("This is a test"));
+        assert_eq!(get_file_info(String::from("not_exists"), MAX_FILE_READ).0, String::from("UNKNOWN"));
     }
 
     // ------------------------------------------------------------------------
@@ -119,8 +285,119 @@ mod tests {
     fn test_get_checksum_bad() {
         let filename = String::from("test_get_checksum_bad");
         create_test_file(filename.clone());
-        assert_ne!(get_checksum(filename.clone(), MAX_FILE_READ), String::from("This is a test"));
+        assert_ne!(get_file_info(filename.clone(), MAX_FILE_READ).0, String::from("This is a test"));
+        remove_test_file(filename.clone());
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_get_entropy() {
+        let filename = String::from("test_get_entropy");
+        File::create(filename.clone()).unwrap().write_all(b"aaaabbbb").unwrap();
+        assert_eq!(get_entropy(filename.clone(), MAX_FILE_READ), 1.0);
+        File::create(filename.clone()).unwrap().write_all(&[0u8; 32]).unwrap();
+        assert_eq!(get_entropy(filename.clone(), MAX_FILE_READ), 0.0);
+        let bytes: Vec<u8> = (0..=255).collect();
+        File::create(filename.clone()).unwrap().write_all(&bytes).unwrap();
+        assert_eq!(get_entropy(filename.clone(), MAX_FILE_READ), 8.0);
+        remove_test_file(filename.clone());
+        assert_eq!(get_entropy(String::from("not_exists"), MAX_FILE_READ), 0.0);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_get_file_info() {
+        let filename = String::from("test_get_file_info");
+        create_test_file(filename.clone());
+        let (checksum, entropy, file_type) = get_file_info(filename.clone(), MAX_FILE_READ);
+        assert_eq!(checksum.len(), 128);
+        assert!(entropy > 3.0 && entropy < 4.0);
+        assert_eq!(file_type, "text");
         remove_test_file(filename.clone());
+
+        assert_eq!(get_file_info(String::from("src"), MAX_FILE_READ),
+            (String::from("UNKNOWN"), 0.0, String::from("directory")));
+        assert_eq!(get_file_info(String::from("not_exists"), MAX_FILE_READ),
+            (String::from("UNKNOWN"), 0.0, String::from("UNKNOWN")));
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_get_file_info_limit() {
+        // Only the first MB is read, the checksum is skipped
+        let filename = String::from("test_get_file_info_limit");
+        let mut content = vec![0u8; 1024 * 1024];
+        content.extend((0..=255).collect::<Vec<u8>>());
+        File::create(filename.clone()).unwrap().write_all(&content).unwrap();
+        assert_eq!(get_file_info(filename.clone(), 1), (String::from("UNKNOWN"), 0.0, String::from("data")));
+        assert_eq!(get_file_info(filename.clone(), 2).0.len(), 128);
+        remove_test_file(filename);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_get_file_type() {
+        assert_eq!(get_file_type(b""), "empty");
+        assert_eq!(get_file_type(b"\x7FELF\x02\x01\x01"), "elf");
+        assert_eq!(get_file_type(b"\xFF\xD8\xFF\xE0\x00\x10JFIF"), "jpeg");
+        assert_eq!(get_file_type(b"\x89PNG\r\n\x1A\n"), "png");
+        assert_eq!(get_file_type(b"PK\x03\x04\x14\x00"), "zip");
+        assert_eq!(get_file_type(b"MZ\x90\x00"), "pe");
+        assert_eq!(get_file_type(b"#!/bin/sh\necho test\n"), "script");
+        assert_eq!(get_file_type(b"\x00\x00\x00\x18ftypmp42"), "mp4");
+        assert_eq!(get_file_type(b"Plain text\nwith lines\n"), "text");
+        assert_eq!(get_file_type("Texto en espa\u{f1}ol".as_bytes()), "text");
+        assert_eq!(get_file_type(b"\x01\x02\x03\xF0\x9F"), "data");
+
+        let mut tar = vec![0u8; 300];
+        tar[257..262].copy_from_slice(b"ustar");
+        assert_eq!(get_file_type(&tar), "tar");
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_is_text() {
+        assert!(is_text(b"text"));
+        assert!(is_text(b"text\twith\r\nwhitespace"));
+        // Multi-byte character cut at the end of the header
+        assert!(is_text(&"a\u{f1}".as_bytes()[..2]));
+        assert!(!is_text(b"\x00binary"));
+        assert!(!is_text(b"\xFF\xFE"));
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_file_type_changed() {
+        let mut known_types = KnownTypes::new(MAX_KNOWN_TYPES);
+        assert!(!file_type_changed(&mut known_types, "/tmp/a.jpg", "jpeg"));
+        assert!(!file_type_changed(&mut known_types, "/tmp/a.jpg", "jpeg"));
+        assert!(file_type_changed(&mut known_types, "/tmp/a.jpg", "elf"));
+        assert!(!file_type_changed(&mut known_types, "/tmp/a.jpg", "empty"));
+        assert!(!file_type_changed(&mut known_types, "/tmp/a.jpg", "elf"));
+        assert!(!file_type_changed(&mut known_types, "/tmp/b", "UNKNOWN"));
+        assert_eq!(known_types.types.len(), 1);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_known_types_capacity() {
+        let mut known_types = KnownTypes::new(2);
+        assert!(!file_type_changed(&mut known_types, "/tmp/a", "jpeg"));
+        assert!(!file_type_changed(&mut known_types, "/tmp/b", "jpeg"));
+        assert!(!file_type_changed(&mut known_types, "/tmp/b", "jpeg"));
+        assert!(!file_type_changed(&mut known_types, "/tmp/c", "jpeg"));
+        assert_eq!(known_types.types.len(), 2);
+        // Oldest path was forgotten
+        assert!(!file_type_changed(&mut known_types, "/tmp/a", "elf"));
+        assert!(file_type_changed(&mut known_types, "/tmp/c", "elf"));
+        assert_eq!(known_types.types.len(), 2);
     }
 
     // ------------------------------------------------------------------------
@@ -136,6 +413,7 @@ mod tests {
     #[test]
     fn test_hex_to_ascii_bad() {
         assert_eq!(hex_to_ascii(String::from("ABC")), "");
+        assert_eq!(hex_to_ascii(String::from("66FF6F")), "f\u{FFFD}o");
     }
 
 }
@@ -0,0 +1,166 @@
+// Copyright (C) 2023, Achiefs.
+
+// Global constants definitions
+pub const PASSWD_FILE: &str = "/etc/passwd";
+pub const GROUP_FILE: &str = "/etc/group";
+// Audit value of ids that are not set, like the auid of daemons
+pub const UNSET_ID: u32 = u32::MAX;
+pub const UNSET_NAME: &str = "unset";
+
+// To read the users and groups databases
+use std::fs;
+use std::time::SystemTime;
+// To store the names of each id
+use std::collections::HashMap;
+// To log the program process
+use log::debug;
+
+// ----------------------------------------------------------------------------
+
+// Id to name table of a passwd or group file, loaded again when it changes
+struct Table {
+    file: String,
+    modified: Option<SystemTime>,
+    names: HashMap<u32, String>
+}
+
+impl Table {
+    fn new(file: &str) -> Self {
+        Table { file: String::from(file), modified: None, names: HashMap::new() }
+    }
+
+    // ------------------------------------------------------------------------
+
+    fn get(&mut self, id: Option<u32>) -> String {
+        let id = match id {
+            Some(UNSET_ID) => return String::from(UNSET_NAME),
+            Some(id) => id,
+            None => return String::new()
+        };
+        self.refresh();
+        self.names.get(&id).cloned().unwrap_or_default()
+    }
+
+    // ------------------------------------------------------------------------
+
+    fn refresh(&mut self) {
+        let modified = fs::metadata(&self.file).and_then(|m| m.modified()).ok();
+        if modified.is_some() && modified == self.modified { return }
+        self.modified = modified;
+        self.names = match fs::read_to_string(&self.file) {
+            Ok(contents) => parse_names(&contents),
+            Err(_) => HashMap::new()
+        };
+        debug!("Loaded {} names from '{}'", self.names.len(), self.file);
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Resolve user and group ids into names through passwd and group files
+pub struct Resolver {
+    users: Table,
+    groups: Table
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Resolver::from_files(PASSWD_FILE, GROUP_FILE)
+    }
+
+    // ------------------------------------------------------------------------
+
+    pub fn from_files(passwd: &str, group: &str) -> Self {
+        Resolver { users: Table::new(passwd), groups: Table::new(group) }
+    }
+
+    // ------------------------------------------------------------------------
+
+    // User name of a uid, empty if it is unknown
+    pub fn get_user(&mut self, uid: Option<u32>) -> String {
+        self.users.get(uid)
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Group name of a gid, empty if it is unknown
+    pub fn get_group(&mut self, gid: Option<u32>) -> String {
+        self.groups.get(gid)
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Parse 'name:password:id:...' lines, the first name of an id is kept
+pub fn parse_names(contents: &str) -> HashMap<u32, String> {
+    let mut names = HashMap::new();
+    for line in contents.lines() {
+        if line.starts_with('#') { continue }
+        let fields: Vec<&str> = line.split(':').collect();
+        if fields.len() < 3 || fields[0].is_empty() { continue }
+        if let Ok(id) = fields[2].parse::<u32>() {
+            names.entry(id).or_insert_with(|| String::from(fields[0]));
+        }
+    }
+    names
+}
+
+// ----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_names() {
+        let names = parse_names("root:x:0:0:root:/root:/bin/bash\n\
+            # comment\n\
+            alice:x:1000:1000::/home/alice:/bin/bash\n\
+            toor:x:0:0::/root:/bin/sh\n\
+            broken\n");
+        assert_eq!(names.len(), 2);
+        assert_eq!(names[&0], "root");
+        assert_eq!(names[&1000], "alice");
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_resolver() {
+        let passwd = "test_identity_passwd";
+        let group = "test_identity_group";
+        fs::write(passwd, "root:x:0:0:root:/root:/bin/bash\n").unwrap();
+        fs::write(group, "root:x:0:\nadm:x:4:alice\n").unwrap();
+        let mut resolver = Resolver::from_files(passwd, group);
+        assert_eq!(resolver.get_user(Some(0)), "root");
+        assert_eq!(resolver.get_user(Some(1000)), "");
+        assert_eq!(resolver.get_user(Some(UNSET_ID)), UNSET_NAME);
+        assert_eq!(resolver.get_user(None), "");
+        assert_eq!(resolver.get_group(Some(4)), "adm");
+
+        // Changes are loaded when the file is modified
+        fs::write(passwd, "root:x:0:0:root:/root:/bin/bash\nalice:x:1000:1000::/home/alice:/bin/bash\n").unwrap();
+        let file = fs::File::options().write(true).open(passwd).unwrap();
+        file.set_modified(SystemTime::now() + std::time::Duration::from_secs(10)).unwrap();
+        assert_eq!(resolver.get_user(Some(1000)), "alice");
+        fs::remove_file(passwd).unwrap();
+        fs::remove_file(group).unwrap();
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_resolver_missing_files() {
+        let mut resolver = Resolver::from_files("test_identity_none", "test_identity_none");
+        assert_eq!(resolver.get_user(Some(0)), "");
+        assert_eq!(resolver.get_group(Some(0)), "");
+    }
+}
@@ -1,8 +1,9 @@
 // Copyright (C) 2022, Achiefs.
 
 // Global constants definitions
-pub const AUDIT_PATH: &str = "/var/log/audit";
-pub const AUDIT_LOG_PATH: &str = "/var/log/audit/audit.log";
+const ENRICHED_SEPARATOR: char = '\x1d';
+// Fields that auditd hex encodes when they contain spaces or special characters
+const HEX_FIELDS: [&str; 5] = ["name", "cwd", "exe", "comm", "proctitle"];
 
 // To manage file reading
 use std::io::{BufReader, SeekFrom};
@@ -10,36 +11,45 @@ use std::io::prelude::*;
 // To manage readed data into collection
 use std::collections::HashMap;
 // To log the program process
-use log::{debug, error};
+use log::{debug, error, info, warn};
 
 // Single event data management
 use crate::auditevent::Event;
+// To group the records of each audit event
+use crate::assembler::Assembler;
+// To keep the read position of the audit log
+use crate::readstate::{self, ReadState};
 // To manage common functions
 use crate::utils;
 // To get configuration constants
 use crate::config;
+// To decode hex encoded values
+use crate::hash;
+// To accept the events of monitor paths with whodata
+use crate::whodata;
+// To identify the events of process executions
+use crate::ancestry;
 
 // Defined type to simplify syntax
 type SHashMap = HashMap<String, String>;
 
 // ----------------------------------------------------------------------------
 
-// Read file to extract last data until the Audit ID changes
-pub fn read_log(file: String, config: config::Config, position: u64, itx: u64) -> (Event, u64) {
-    let mut event: Event = Event::new();
+// Read the full lines written after position, records are grouped by the
+// assembler so the file is never read twice. Returns the completed events
+// and the position after the last full line read.
+pub fn read_log(file: String, config: config::Config, position: u64, assembler: &mut Assembler) -> (Vec<Event>, u64) {
+    let mut events: Vec<Event> = Vec::new();
     let mut current_position = position;
     let log = utils::open_file(&file, 0);
     let mut buff = BufReader::new(log);
-    match buff.seek(SeekFrom::Current(position as i64)) {
+    match buff.seek(SeekFrom::Start(position)) {
         Ok(p) => debug!("Seek audit log file, position: {}", p),
         Err(e) => error!("{}", e)
     };
 
-    // Read from last registered position until we get event or the end
-    let mut data: Vec<HashMap<String, String>> = Vec::new();
     let mut line = String::new();
-    while current_position < utils::get_file_end(&file, 0) {
-        debug!("Reading start: {}", current_position);
+    loop {
         let bytes_read = match buff.read_line(&mut line){
             Ok(bytes) => {
                 debug!("Read string: '{}', bytes read: {}", line, bytes);
@@ -50,60 +60,99 @@ pub fn read_log(file: String, config: config::Config, position: u64, itx: u64) -
                 0
             }
         };
+        // Partial lines are read again once they are completed
+        if bytes_read == 0 || ! line.ends_with('\n') { break; }
         current_position += bytes_read;
-        debug!("End read position: {}", current_position);
-
-        let line_info = parse_audit_log(line.clone());
-        if line_info.contains_key("type") && (line_info["type"] == "SYSCALL" ||
-            line_info["type"] == "CWD" ||
-            line_info["type"] == "PATH" ||
-            line_info["type"] == "PROCTITLE") {
-            data.push(line_info.clone());
-            if line_info.contains_key("type") &&
-                line_info["type"] == "PROCTITLE" {
-                break;
-            }
-        }
+
+        events.extend(read_line(line.clone(), config.clone(), assembler));
         line = String::new();
     }
-    if !data.is_empty() {
-        let last = data.last().unwrap();
-        let first = data.first().unwrap();
-        if last.contains_key("type") &&
-            first.contains_key("type") &&
-            last["type"] == "PROCTITLE" &&
-            first["type"] == "SYSCALL" {
-            let (syscall, cwd, proctitle, paths) = extract_fields(data.clone());
-            let audit_vec = config.audit.to_vec();
-
-            // Skip the event generation of paths not monitored by FIM
-            if paths.iter().any(|p| {
-                let cwd_path = cwd["cwd"].as_str();
-                config.path_in(p["name"].as_str(), cwd_path, audit_vec.clone()) ||
-                config.path_in(cwd_path, "", audit_vec.clone())
-            }) {
-                event = Event::from(syscall, cwd, proctitle, paths, config.clone());
-            }
-        }else if data.iter().any(|line| {
-            line["type"] == "SYSCALL" ||
-            line["type"] == "CWD" ||
-            line["type"] == "PATH" ||
-            line["type"] == "PROCTITLE"
-        }) && itx < 120 {
-            current_position = position;
+    debug!("End read position: {}", current_position);
+    (events, current_position)
+}
+
+// ----------------------------------------------------------------------------
+
+// Add a single audit record line, returns the events completed by it
+pub fn read_line(line: String, config: config::Config, assembler: &mut Assembler) -> Vec<Event> {
+    assembler.push(parse_audit_log(line)).into_iter()
+        .filter_map(|data| build_event(data, config.clone()))
+        .collect()
+}
+
+// ----------------------------------------------------------------------------
+
+// Read the audit log from the saved state. When the log was rotated the
+// rotated file (file.1) is finished before reading the new one.
+pub fn read_audit_log(file: &str, state: &mut ReadState, config: config::Config, assembler: &mut Assembler) -> Vec<Event> {
+    let mut events: Vec<Event> = Vec::new();
+    if ! state.is_file(file) {
+        let rotated = format!("{}.1", file);
+        if state.inode != 0 && state.is_file(&rotated) {
+            info!("Audit log rotated, finishing '{}' from position: {}", rotated, state.position);
+            let (rotated_events, _) = read_log(rotated, config.clone(), state.position, assembler);
+            events.extend(rotated_events);
+        }else if state.inode != 0 {
+            warn!("Previous audit log not found, events after position {} could be lost", state.position);
         }
+        let (inode, dev) = readstate::get_file_id(file);
+        *state = ReadState { position: 0, inode, dev };
+    }
+    // The log was truncated
+    if state.position > utils::get_file_end(file, 0) {
+        state.position = 0;
+    }
+    let (new_events, position) = read_log(String::from(file), config, state.position, assembler);
+    state.position = position;
+    events.extend(new_events);
+    events
+}
+
+// ----------------------------------------------------------------------------
+
+// Build an event with the records of a single audit event, only for the
+// events with syscall information that affect paths monitored by FIM
+pub fn build_event(data: Vec<SHashMap>, config: config::Config) -> Option<Event> {
+    let data: Vec<SHashMap> = data.into_iter().filter(|line| {
+        line.contains_key("type") && (line["type"] == "SYSCALL" ||
+        line["type"] == "CWD" ||
+        line["type"] == "PATH" ||
+        line["type"] == "EXECVE" ||
+        line["type"] == "PROCTITLE")
+    }).collect();
+    if ! data.iter().any(|line| line["type"] == "SYSCALL") {
+        return None
+    }
+    let whodata = data.iter().any(|line| line["type"] == "SYSCALL" &&
+        line.get("key").map(|k| k == whodata::WHODATA_KEY).unwrap_or(false));
+    let (syscall, cwd, proctitle, execve, paths) = extract_fields(data);
+    // Process executions only feed the ancestry, their files are not read
+    if syscall.get("key").map(|k| k == ancestry::EXECVE_KEY).unwrap_or(false) {
+        return Some(Event::from_execve(syscall))
+    }
+    let audit_vec = config.audit.to_vec();
+
+    // Skip the event generation of paths not monitored by FIM
+    if whodata || paths.iter().any(|p| {
+        let cwd_path = cwd["cwd"].as_str();
+        config.path_in(p.get("name").map(|n| n.as_str()).unwrap_or_default(), cwd_path, audit_vec.clone()) ||
+        config.path_in(cwd_path, "", audit_vec.clone())
+    }) {
+        Some(Event::from(syscall, cwd, proctitle, execve, paths, config.clone()))
+    }else{
+        None
     }
-    (event, current_position)
 }
 
 // ----------------------------------------------------------------------------
 
 pub fn extract_fields(data: Vec<HashMap<String, String>>) -> (SHashMap,
-    SHashMap, SHashMap, Vec<SHashMap>) {
+    SHashMap, SHashMap, SHashMap, Vec<SHashMap>) {
     let mut paths: Vec<SHashMap> = Vec::new();
     let mut syscall = SHashMap::new();
     let mut cwd = SHashMap::from([ (String::from("cwd"), String::from("/UNKNOWN")) ]);
     let mut proctitle = SHashMap::new();
+    let mut execve = SHashMap::new();
 
     data.iter().for_each(|v| {
         match v["type"].as_str() {
@@ -111,25 +160,141 @@ pub fn extract_fields(data: Vec<HashMap<String, String>>) -> (SHashMap,
             "PATH" => paths.push(v.clone()),
             "CWD" => cwd = v.clone(),
             "PROCTITLE" => proctitle = v.clone(),
+            "EXECVE" => merge_execve(&mut execve, v),
             _ => error!("Unidentified Audit field")
         }
     });
-    (syscall, cwd, proctitle, paths)
+    (syscall, cwd, proctitle, execve, paths)
+}
+
+// ----------------------------------------------------------------------------
+
+// Long command lines are split in several EXECVE records, only the first one
+// has argc and an argument can continue in the next record
+fn merge_execve(execve: &mut SHashMap, record: &SHashMap) {
+    for (key, value) in record {
+        match execve.get_mut(key) {
+            Some(arg) if is_argument(key) => arg.push_str(value),
+            Some(_) => {},
+            None => { execve.insert(key.clone(), value.clone()); }
+        }
+    }
 }
 
 // ----------------------------------------------------------------------------
 
+// Parse an audit record into its fields. Newer auditd versions append the
+// interpreted values after a '\x1d' separator with uppercase keys, they are
+// kept with their own keys.
 pub fn parse_audit_log(log: String) -> HashMap<String, String> {
-    let fields: Vec<&str> = log.split(' ').collect();
-    fields.iter()
-        .map(|f| {
-            let obj: Vec<&str> = f.split('=').collect();
-            if obj.len() == 2 {
-                (String::from(obj[0]), String::from(obj[1]).replace(['\"', '\n'], ""))
-            }else{
-                (String::from(obj[0]), String::from("UNKNOWN"))
+    let log = log.trim_end_matches(['\n', '\r']);
+    let (raw, enriched) = log.split_once(ENRICHED_SEPARATOR).unwrap_or((log, ""));
+    let mut tokens = tokenize(raw);
+    // User space records carry their own fields inside msg='...'
+    if let Some(position) = tokens.iter().position(|(k, _, q)| k == "msg" && *q) {
+        let (_, inner, _) = tokens.remove(position);
+        tokens.extend(tokenize(&inner));
+    }
+    if tokens.iter().any(|(k, v, _)| k == "type" && v == "EXECVE") {
+        tokens = decode_execve(tokens);
+    }
+    let mut fields = HashMap::new();
+    for (key, value, quoted) in tokens.into_iter().chain(tokenize(enriched)) {
+        // Unquoted values of these fields are hex encoded by auditd
+        let value = if ! quoted && HEX_FIELDS.contains(&key.as_str()) && is_hex(&value) {
+            hash::hex_to_ascii(value)
+        }else{ value };
+        fields.entry(key).or_insert(value);
+    }
+    fields
+}
+
+// ----------------------------------------------------------------------------
+
+// Split a record in (key, value, quoted) tuples, values are split on the first
+// '=' and could be quoted with double or single quotes including spaces
+fn tokenize(text: &str) -> Vec<(String, String, bool)> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() { break; }
+
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|c| *c != '=' && ! c.is_whitespace()) {
+            key.push(c);
+        }
+        if chars.next_if_eq(&'=').is_none() {
+            // Tokens without value like the '{ read }' of AVC records
+            tokens.push((key, String::from("UNKNOWN"), false));
+            continue;
+        }
+
+        let mut value = String::new();
+        let quote = chars.next_if(|c| *c == '"' || *c == '\'');
+        match quote {
+            Some(q) => {
+                for c in chars.by_ref() {
+                    if c == q { break; }
+                    value.push(c);
+                }
+            },
+            None => while let Some(c) = chars.next_if(|c| ! c.is_whitespace()) {
+                value.push(c);
+            }
+        }
+        tokens.push((key, value, quote.is_some()));
+    }
+    tokens
+}
+
+// ----------------------------------------------------------------------------
+
+// Decode the arguments of EXECVE records into 'aN' fields. Unquoted arguments
+// are hex encoded and long ones are split in 'aN_len=X aN[0]=.. aN[1]=..' chunks
+fn decode_execve(tokens: Vec<(String, String, bool)>) -> Vec<(String, String, bool)> {
+    let mut fields = Vec::new();
+    let mut args: Vec<(String, Vec<u8>)> = Vec::new();
+    for (key, value, quoted) in tokens {
+        let name = key.split('[').next().unwrap_or_default();
+        if ! is_argument(name) {
+            if ! (key.starts_with('a') && key.ends_with("_len")) {
+                fields.push((key, value, quoted));
             }
-        }).collect::<HashMap<String, String>>()
+            continue;
+        }
+        let bytes = if ! quoted && is_hex(&value) {
+            hex::decode(&value).unwrap_or_default()
+        }else{ value.into_bytes() };
+        match args.iter_mut().find(|(n, _)| n == name) {
+            Some((_, arg)) => arg.extend(bytes),
+            None => args.push((String::from(name), bytes))
+        }
+    }
+    fields.extend(args.into_iter()
+        .map(|(name, arg)| (name, String::from_utf8_lossy(&arg).into_owned(), true)));
+    fields
+}
+
+// ----------------------------------------------------------------------------
+
+// Command line arguments of a parsed EXECVE record
+pub fn get_argv(execve: &SHashMap) -> Vec<String> {
+    let argc = execve.get("argc").and_then(|a| a.parse::<usize>().ok()).unwrap_or(0);
+    (0..argc).map(|i| execve.get(&format!("a{}", i)).cloned().unwrap_or_default()).collect()
+}
+
+// ----------------------------------------------------------------------------
+
+// EXECVE argument field names, a0, a1...
+fn is_argument(name: &str) -> bool {
+    name.len() > 1 && name.starts_with('a') && name[1..].chars().all(|c| c.is_ascii_digit())
+}
+
+// ----------------------------------------------------------------------------
+
+fn is_hex(value: &str) -> bool {
+    ! value.is_empty() && value.len().is_multiple_of(2) && value.chars().all(|c| c.is_ascii_hexdigit())
 }
 
 // ----------------------------------------------------------------------------
@@ -143,17 +308,20 @@ mod tests {
     fn test_read_log() {
         if utils::get_os() == "linux" {
             let config = Config::new("linux", None);
-            let (event, position) = read_log(String::from("test/unit/audit.log"),
-                config, 0, 0);
+            let mut assembler = Assembler::new();
+            let (events, position) = read_log(String::from("test/unit/audit.log"),
+                config, 0, &mut assembler);
+            assert_eq!(events.len(), 1);
+            let event = &events[0];
 
             assert_eq!(event.id.len(), 36);
             assert_eq!(event.path, ".");
             assert_eq!(event.operation, "CREATE");
             assert_eq!(event.file, "sedTsutP7");
             assert_eq!(event.timestamp, "1659026449689");
-            assert_eq!(event.proctitle, "736564002D6900737C68656C6C6F7C4849217C670066696C6531302E747874");
+            assert_eq!(event.proctitle, "sed -i s|hello|HI!|g file10.txt");
             assert_eq!(event.cap_fver, "0");
-            assert_eq!(event.inode, "1972630");
+            assert_eq!(event.inode, Some(1972630));
             assert_eq!(event.cap_fp, "0");
             assert_eq!(event.cap_fe, "0");
             assert_eq!(event.item, "1");
@@ -161,7 +329,7 @@ mod tests {
             assert_eq!(event.dev, "08:02");
             assert_eq!(event.mode, "0100000");
             assert_eq!(event.cap_frootid, "0");
-            assert_eq!(event.ouid, "0");
+            assert_eq!(event.ouid, Some(0));
             assert_eq!(event.paths[0]["item"], "0");
             assert_eq!(event.paths[0]["name"], "./");
             assert_eq!(event.paths[0]["inode"], "1966138");
@@ -192,54 +360,236 @@ mod tests {
             assert_eq!(event.paths[1]["cap_frootid"], "0");
             assert_eq!(event.cwd, "/tmp");
             assert_eq!(event.syscall, "257");
-            assert_eq!(event.ppid, "161880");
+            assert_eq!(event.syscall_name, "openat");
+            // Created with O_CREAT, nametype CREATE
+            assert_eq!(event.action, "create");
+            assert_eq!(event.ppid, Some(161880));
             assert_eq!(event.comm, "sed");
-            assert_eq!(event.fsuid, "0");
-            assert_eq!(event.pid, "161937");
+            assert_eq!(event.fsuid, Some(0));
+            assert_eq!(event.pid, Some(161937));
             assert_eq!(event.a0, "ffffff9c");
             assert_eq!(event.a1, "556150ee3c00");
             assert_eq!(event.a2, "c2");
             assert_eq!(event.a3, "180");
             assert_eq!(event.arch, "c000003e");
-            assert_eq!(event.auid, "1000");
-            assert_eq!(event.items, "2");
-            assert_eq!(event.gid, "0");
-            assert_eq!(event.euid, "0");
-            assert_eq!(event.sgid, "0");
-            assert_eq!(event.uid, "0");
+            assert_eq!(event.arch_name, "x86_64");
+            assert_eq!(event.auid, Some(1000));
+            assert_eq!(event.items, Some(2));
+            assert_eq!(event.gid, Some(0));
+            assert_eq!(event.euid, Some(0));
+            assert_eq!(event.sgid, Some(0));
+            assert_eq!(event.uid, Some(0));
             assert_eq!(event.tty, "pts0");
             assert_eq!(event.success, "yes");
-            assert_eq!(event.exit, "4");
-            assert_eq!(event.ses, "807");
+            assert_eq!(event.exit, Some(4));
+            assert_eq!(event.ses, Some(807));
             assert_eq!(event.key, "fim");
-            assert_eq!(event.suid, "0");
-            assert_eq!(event.egid, "0");
-            assert_eq!(event.fsgid, "0");
+            assert_eq!(event.suid, Some(0));
+            assert_eq!(event.egid, Some(0));
+            assert_eq!(event.fsgid, Some(0));
             assert_eq!(event.exe, "/usr/bin/sed");
-            assert_eq!(position, 845);
+            assert_eq!(position, 846);
+        }
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_read_log_interleaved() {
+        if utils::get_os() == "linux" {
+            let config = Config::new("linux", None);
+            let mut assembler = Assembler::new();
+            let file = String::from("test/unit/audit_interleaved.log");
+            let (events, position) = read_log(file.clone(), config.clone(), 0, &mut assembler);
+            assert_eq!(events.len(), 2);
+            assert_eq!(events[0].file, "sedXyz123");
+            assert_eq!(events[0].pid, Some(161940));
+            assert_eq!(events[0].paths.len(), 2);
+            assert_eq!(events[1].file, "sedTsutP7");
+            assert_eq!(events[1].pid, Some(161937));
+            assert_eq!(events[1].cwd, "/tmp");
+            assert_eq!(assembler.pending(), 0);
+
+            // The last partial line is not consumed
+            let end = utils::get_file_end(&file, 0);
+            assert!(position < end);
+            let (events, next) = read_log(file, config, position, &mut assembler);
+            assert!(events.is_empty());
+            assert_eq!(next, position);
         }
     }
 
     // ------------------------------------------------------------------------
 
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_read_audit_log_rotation() {
+        use std::fs;
+        let config = Config::new("linux", None);
+        let mut assembler = Assembler::new();
+        let dir = "test_read_audit_log";
+        let file = format!("{}/audit.log", dir);
+        let lines = fs::read_to_string("test/unit/audit.log").unwrap();
+        let (first, second) = lines.split_at(lines.find("type=PATH").unwrap());
+        fs::create_dir_all(dir).unwrap();
+        fs::write(&file, first).unwrap();
+
+        let mut state = ReadState { position: 0, inode: 0, dev: 0 };
+        assert!(read_audit_log(&file, &mut state, config.clone(), &mut assembler).is_empty());
+        assert_eq!(state.position, first.len() as u64);
+        assert!(state.is_file(&file));
+
+        // Rotation, the rest of the event was written to the rotated file
+        fs::write(&file, lines.clone()).unwrap();
+        fs::rename(&file, format!("{}.1", file)).unwrap();
+        fs::write(&file, "").unwrap();
+        fs::write(format!("{}.1", file), lines.replace("./sedTsutP7", "./sedRotated")).unwrap();
+        let events = read_audit_log(&file, &mut state, config.clone(), &mut assembler);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].file, "sedRotated");
+        assert_eq!(state.position, 0);
+        assert!(state.is_file(&file));
+
+        fs::write(&file, second).unwrap();
+        assert!(read_audit_log(&file, &mut state, config, &mut assembler).is_empty());
+        assert_eq!(state.position, second.len() as u64);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_read_line() {
+        let config = Config::new("linux", None);
+        let mut assembler = Assembler::new();
+        let contents = std::fs::read_to_string("test/unit/audit.log").unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        for line in &lines[..lines.len() - 1] {
+            assert!(read_line(String::from(*line), config.clone(), &mut assembler).is_empty());
+        }
+        let events = read_line(String::from(lines[lines.len() - 1]), config, &mut assembler);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].file, "sedTsutP7");
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_build_event() {
+        let config = Config::new("linux", None);
+        let data = vec![
+            HashMap::from([ (String::from("type"), String::from("CWD")),
+                (String::from("cwd"), String::from("/tmp")) ])
+        ];
+        assert!(build_event(data, config.clone()).is_none());
+
+        // Process executions are decoded without their paths
+        let data = vec![
+            HashMap::from([ (String::from("type"), String::from("SYSCALL")),
+                (String::from("pid"), String::from("300")),
+                (String::from("ppid"), String::from("200")),
+                (String::from("key"), String::from(ancestry::EXECVE_KEY)) ]),
+            HashMap::from([ (String::from("type"), String::from("PATH")),
+                (String::from("name"), String::from("/usr/bin/sh")) ])
+        ];
+        let event = build_event(data, config).unwrap();
+        assert_eq!(event.key, ancestry::EXECVE_KEY);
+        assert_eq!(event.pid, Some(300));
+        assert!(event.is_empty());
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_build_event_incomplete() {
+        // Groups completed by timeout or flushed at the end of the input can
+        // miss records, i.e. the trailing PROCTITLE
+        let config = Config::new("linux", None);
+        let contents = std::fs::read_to_string("test/unit/audit.log").unwrap();
+        let mut assembler = Assembler::new();
+        for line in contents.lines().take(4) {
+            assert!(assembler.push(parse_audit_log(String::from(line))).is_empty());
+        }
+        let mut groups = assembler.flush();
+        assert_eq!(groups.len(), 1);
+        let data = groups.pop().unwrap();
+        let event = build_event(data.clone(), config.clone()).unwrap();
+        assert_eq!(event.file, "sedTsutP7");
+        assert_eq!(event.proctitle, "");
+        assert_eq!(event.command, "");
+        assert_eq!(event.timestamp, "1659026449689");
+        assert_eq!(event.serial, "6434");
+
+        // Without PATH records nor syscall fields
+        let syscall = HashMap::from([ (String::from("type"), String::from("SYSCALL")),
+            (String::from("key"), String::from(whodata::WHODATA_KEY)) ]);
+        let event = build_event(vec![syscall], config).unwrap();
+        assert_eq!(event.path, "");
+        assert_eq!(event.comm, "UNKNOWN");
+        assert_eq!(event.timestamp, "");
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_read_log_whodata() {
+        // Whodata events are built for paths out of the audit section
+        let config = Config::new("linux", Some("test/unit/config/linux/whodata.yml"));
+        let mut assembler = Assembler::new();
+        let (events, _) = read_log(String::from("test/unit/audit.log"),
+            config.clone(), 0, &mut assembler);
+        assert!(events.is_empty());
+        let (events, _) = read_log(String::from("test/unit/audit_whodata.log"),
+            config, 0, &mut assembler);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].key, "fim_whodata");
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_read_log_setcap() {
+        let config = Config::new("linux", Some("test/unit/config/linux/replay.yml"));
+        let mut assembler = Assembler::new();
+        let (events, _) = read_log(String::from("test/unit/audit_setcap.log"),
+            config, 0, &mut assembler);
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.syscall_name, "setxattr");
+        assert_eq!(event.action, "capability_change");
+        assert_eq!(event.subj, "unconfined_u:unconfined_r:unconfined_t:s0-s0:c0.c1023");
+        assert_eq!(event.subj_type, "unconfined_t");
+        assert_eq!(event.subj_level, "s0-s0:c0.c1023");
+        assert_eq!(event.obj_user, "unconfined_u");
+        assert_eq!(event.obj_role, "object_r");
+        assert_eq!(event.obj_type, "user_tmp_t");
+        assert_eq!(event.obj_level, "s0");
+    }
+
+    // ------------------------------------------------------------------------
+
     #[test]
     fn test_extract_fields() {
-        let mut data = Vec::<HashMap<String, String>>::new();
-        data.push(HashMap::from([ (String::from("type"), String::from("SYSCALL")) ]));
-        data.push(HashMap::from([ (String::from("type"), String::from("CWD")) ]));
-        data.push(HashMap::from([ (String::from("type"), String::from("PROCTITLE")) ]));
-        data.push(HashMap::from([ (String::from("type"), String::from("PATH")),
-            (String::from("nametype"), String::from("CREATE")) ]));
-        data.push(HashMap::from([ (String::from("type"), String::from("PATH")),
-            (String::from("nametype"), String::from("PARENT")) ]));
-        let (a, b, c, vd) = extract_fields(data);
+        let data = vec![
+            HashMap::from([ (String::from("type"), String::from("SYSCALL")) ]),
+            HashMap::from([ (String::from("type"), String::from("CWD")) ]),
+            HashMap::from([ (String::from("type"), String::from("PROCTITLE")) ]),
+            HashMap::from([ (String::from("type"), String::from("EXECVE")) ]),
+            HashMap::from([ (String::from("type"), String::from("PATH")),
+                (String::from("nametype"), String::from("CREATE")) ]),
+            HashMap::from([ (String::from("type"), String::from("PATH")),
+                (String::from("nametype"), String::from("PARENT")) ])
+        ];
+        let (a, b, c, d, ve) = extract_fields(data);
         assert_eq!(a["type"], String::from("SYSCALL"));
         assert_eq!(b["type"], String::from("CWD"));
         assert_eq!(c["type"], String::from("PROCTITLE"));
-        assert_eq!(vd[0]["type"], String::from("PATH"));
-        assert_eq!(vd[0]["nametype"], String::from("CREATE"));
-        assert_eq!(vd[1]["type"], String::from("PATH"));
-        assert_eq!(vd[1]["nametype"], String::from("PARENT"));
+        assert_eq!(d["type"], String::from("EXECVE"));
+        assert_eq!(ve[0]["type"], String::from("PATH"));
+        assert_eq!(ve[0]["nametype"], String::from("CREATE"));
+        assert_eq!(ve[1]["type"], String::from("PATH"));
+        assert_eq!(ve[1]["nametype"], String::from("PARENT"));
     }
 
     // ------------------------------------------------------------------------
@@ -253,4 +603,126 @@ mod tests {
         assert_eq!(map["cwd"], "/tmp/test");
         assert_eq!(map.len(), 3);
     }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_parse_audit_log_quoted() {
+        let map = parse_audit_log(String::from("type=USER_CMD msg=audit(1659026449.689:6434): \
+            pid=10 msg='cwd=\"/root\" cmd=ls res=success' comm=\"my process\" key=(null)\n"));
+        assert_eq!(map["msg"], "audit(1659026449.689:6434):");
+        assert_eq!(map["cwd"], "/root");
+        assert_eq!(map["cmd"], "ls");
+        assert_eq!(map["res"], "success");
+        assert_eq!(map["comm"], "my process");
+        assert_eq!(map["key"], "(null)");
+        assert_eq!(map["pid"], "10");
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_parse_audit_log_hex() {
+        let map = parse_audit_log(String::from("type=PATH msg=audit(1659026449.689:6434): \
+            item=0 name=2F746D702F6D792066696C65 inode=12 cwd=\"2F746D70\" \
+            exe=2F7573722F62696E2F6D7920617070 comm=ABCD proctitle=6C73"));
+        assert_eq!(map["name"], "/tmp/my file");
+        assert_eq!(map["cwd"], "2F746D70");
+        assert_eq!(map["exe"], "/usr/bin/my app");
+        assert_eq!(map["comm"], "\u{FFFD}\u{FFFD}");
+        assert_eq!(map["inode"], "12");
+        assert_eq!(map["proctitle"], "ls");
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_parse_audit_log_execve() {
+        let map = parse_audit_log(String::from("type=EXECVE msg=audit(1659026449.689:6434):             argc=4 a0=\"sed\" a1=\"-i\" a2=732F612F6220632F67 a3_len=12 a3[0]=\"/tmp/\" \
+            a3[1]=66696C652E747874\n"));
+        assert_eq!(map["argc"], "4");
+        assert_eq!(map["a0"], "sed");
+        assert_eq!(map["a2"], "s/a/b c/g");
+        assert_eq!(map["a3"], "/tmp/file.txt");
+        assert!(!map.contains_key("a3_len"));
+        assert!(!map.contains_key("a3[0]"));
+        assert_eq!(get_argv(&map), vec!["sed", "-i", "s/a/b c/g", "/tmp/file.txt"]);
+
+        // Syscall arguments of other records are not decoded
+        let map = parse_audit_log(String::from("type=SYSCALL msg=audit(1659026449.689:6434): a0=ffffff9c"));
+        assert_eq!(map["a0"], "ffffff9c");
+        let map = parse_audit_log(String::from("type=PROCTITLE msg=audit(1659026449.689:6434):             proctitle=6C73002D6C"));
+        assert_eq!(map["proctitle"], "ls -l");
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_extract_fields_execve_split() {
+        // Second argument split between records, in hex chunks
+        let data = vec![
+            parse_audit_log(String::from("type=EXECVE msg=audit(1659026449.689:6434): argc=3 \
+                a0=\"cat\" a1_len=12 a1[0]=68656C6C6F")),
+            parse_audit_log(String::from("type=EXECVE msg=audit(1659026449.689:6434): \
+                a1[1]=20776F726C6421 a2=\"end\""))
+        ];
+        let (_, _, _, execve, _) = extract_fields(data);
+        assert_eq!(execve["argc"], "3");
+        assert_eq!(get_argv(&execve), vec!["cat", "hello world!", "end"]);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_get_argv() {
+        let execve = HashMap::from([ (String::from("argc"), String::from("2")),
+            (String::from("a0"), String::from("ls")) ]);
+        assert_eq!(get_argv(&execve), vec!["ls", ""]);
+        assert!(get_argv(&HashMap::new()).is_empty());
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_parse_audit_log_enriched() {
+        let map = parse_audit_log(String::from("type=SYSCALL msg=audit(1659026449.689:6434): \
+            arch=c000003e syscall=257 auid=1000 uid=0 key=\"fim\"\x1dARCH=x86_64 \
+            SYSCALL=openat AUID=\"vagrant\" UID=\"root\"\n"));
+        assert_eq!(map["arch"], "c000003e");
+        assert_eq!(map["ARCH"], "x86_64");
+        assert_eq!(map["syscall"], "257");
+        assert_eq!(map["SYSCALL"], "openat");
+        assert_eq!(map["auid"], "1000");
+        assert_eq!(map["AUID"], "vagrant");
+        assert_eq!(map["UID"], "root");
+        assert_eq!(map["key"], "fim");
+        assert_eq!(map.len(), 11);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(tokenize("a=1 b=\"x y\"  c={ read } d=e=f"), vec![
+            (String::from("a"), String::from("1"), false),
+            (String::from("b"), String::from("x y"), true),
+            (String::from("c"), String::from("{"), false),
+            (String::from("read"), String::from("UNKNOWN"), false),
+            (String::from("}"), String::from("UNKNOWN"), false),
+            (String::from("d"), String::from("e=f"), false)
+        ]);
+        assert!(tokenize("").is_empty());
+        assert!(tokenize("   ").is_empty());
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_is_hex() {
+        assert!(is_hex("2F746D70"));
+        assert!(is_hex("abcd"));
+        assert!(!is_hex("2F746D7"));
+        assert!(!is_hex("(null)"));
+        assert!(!is_hex(""));
+    }
 }
@@ -6,6 +6,8 @@
 
 // To manage event channels
 use std::sync::mpsc;
+// To store the global configuration
+use std::sync::OnceLock;
 
 // Utils functions
 mod utils;
@@ -20,13 +22,50 @@ mod event;
 // File reading continuously
 mod logreader;
 mod auditevent;
+// Syscall names tables
+mod syscalls;
+// File mode decoding
+mod filemode;
+// Security context labels and capability changes
+mod seccontext;
+// Persisted events sequence number
+mod sequence;
+// Common output of all event types
+mod fimevent;
+// Events schema and index template definitions
+mod schema;
+// Elastic Common Schema output format
+mod ecs;
+// Audit records grouping
+mod assembler;
+// Audit log read position
+mod readstate;
+// Kernel audit netlink client
+#[cfg(target_os = "linux")]
+mod netlink;
+// Audit rules management
+mod auditrules;
+// Mass change detection
+mod storm;
+// Events severity and tags rules
+mod ruleset;
+// Package manager transactions correlation
+mod package;
+// User and group names resolution
+mod identity;
+// Process tree of audit events
+mod ancestry;
+// Audit information of monitor events
+mod whodata;
+// Offline audit log processing
+mod replay;
 // Manage Windows service
 #[cfg(target_os = "windows")]
 mod service;
 // Manage monitor methods
 mod monitor;
 
-static mut GCONFIG: Option<config::Config> = None;
+static GCONFIG: OnceLock<config::Config> = OnceLock::new();
 
 // ----------------------------------------------------------------------------
 
@@ -38,31 +77,33 @@ fn init(){
 
     println!("Achiefs File Integrity Monitoring software starting!");
     println!("[INFO] Reading config...");
-    unsafe{
-        GCONFIG = Some(config::Config::new(&utils::get_os(), None));    
-
-        // Create folders to store logs based on config.yml
-        fs::create_dir_all(
-            Path::new( &GCONFIG.clone().unwrap().log_file
-            ).parent().unwrap().to_str().unwrap()
-        ).unwrap();
-
-        // Create logger output to write generated logs.
-        WriteLogger::init(
-            GCONFIG.clone().unwrap().get_level_filter(),
-            Config::default(),
-            fs::OpenOptions::new()
-                .write(true)
-                .create(true)
-                .append(true)
-                .open(GCONFIG.clone().unwrap().log_file)
-                .expect("Unable to open log file")
-        ).unwrap();
-
-        println!("[INFO] Configuration successfully read, forwarding output to log file");
-        println!("[INFO] Log file: '{}'", GCONFIG.clone().unwrap().log_file);
-        println!("[INFO] Log level: '{}'", GCONFIG.clone().unwrap().log_level);
-    };
+    let mut config = config::Config::new(&utils::get_os(), None);
+    // Started by auditd as a plugin, records come from stdin
+    if std::env::args().any(|a| a == "--audit-plugin") {
+        config.audit_source = String::from(config::AUDIT_STDIN_SOURCE);
+    }
+    let config = GCONFIG.get_or_init(|| config);
+
+    // Create folders to store logs based on config.yml
+    fs::create_dir_all(
+        Path::new( &config.log_file
+        ).parent().unwrap().to_str().unwrap()
+    ).unwrap();
+
+    // Create logger output to write generated logs.
+    WriteLogger::init(
+        config.get_level_filter(),
+        Config::default(),
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(config.log_file.clone())
+            .expect("Unable to open log file")
+    ).unwrap();
+
+    println!("[INFO] Configuration successfully read, forwarding output to log file");
+    println!("[INFO] Log file: '{}'", config.log_file);
+    println!("[INFO] Log level: '{}'", config.log_level);
     log_panics::init();
 }
 
@@ -72,8 +113,61 @@ fn init(){
 #[cfg(not(windows))]
 #[tokio::main]
 async fn main() {
+    // To manage terminal parameters
+    use std::env;
+    let args: Vec<_> = env::args().collect();
+    // Print generated documents before any other output
+    if args.len() > 1 && args[1] == "schema" {
+        if let Some(file) = args.iter().position(|a| a == "--validate").and_then(|p| args.get(p + 1)) {
+            let invalid = schema::validate_file(file);
+            println!("[INFO] Events file validated, invalid events: {}", invalid);
+            return;
+        }
+        let document = match (args.iter().any(|a| a == "--template"), args.iter().any(|a| a == "--ecs")) {
+            (true, true) => ecs::get_template(),
+            (true, false) => schema::get_template(),
+            _ => schema::get_json_schema()
+        };
+        println!("{}", serde_json::to_string_pretty(&document).unwrap());
+        return;
+    }
     init();
 
+    if args.len() > 1 && args[1] == "replay" {
+        let get_arg = |name: &str| args.iter().position(|a| a == name)
+            .and_then(|p| args.get(p + 1)).cloned();
+        let file = match get_arg("--audit-log") {
+            Some(file) => file,
+            None => {
+                println!("Usage: fim replay --audit-log <file> [--from <time>] [--to <time>]");
+                return;
+            }
+        };
+        let mut range = Vec::new();
+        for name in ["--from", "--to"] {
+            match get_arg(name).map(|t| replay::parse_time(&t)) {
+                Some(None) => {
+                    println!("[ERROR] Invalid {} time, use unix seconds or 'YYYY-MM-DD HH:MM:SS' (UTC)", name);
+                    return;
+                },
+                time => range.push(time.flatten())
+            }
+        }
+        let config = GCONFIG.get().unwrap().clone();
+        let produced = replay::replay(&file, range[0], range[1], config).await;
+        println!("[INFO] Audit log replayed, events: {}", produced);
+        return;
+    }
+    if args.len() > 1 && args[1] == "audit-rules" {
+        if args.iter().any(|a| a == "--cleanup") {
+            auditrules::cleanup(GCONFIG.get().unwrap());
+            println!("[INFO] Audit rules removed");
+        }else{
+            println!("Usage: fim audit-rules --cleanup");
+        }
+        return;
+    }
+
     let (tx, rx) = mpsc::channel();
     monitor::monitor(tx, rx).await;
 }
@@ -1,7 +1,17 @@
 // Copyright (C) 2021, Achiefs.
 
+// Global constants definitions
+// Path of the events sent to wake up the main loop with stdin or netlink records
+const INPUT_EVENT: &str = "AUDIT_INPUT";
+
 // To read and write directories and files
 use std::fs;
+// To read audit records from stdin or netlink
+use std::io::BufRead;
+use std::thread;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::path::PathBuf;
 // To get file system changes
 use notify::{RecommendedWatcher, RecursiveMode, Watcher, Config as NConfig};
 use std::sync::mpsc;
@@ -10,14 +20,10 @@ use log::{info, error, debug, warn};
 // To manage paths
 use std::path::Path;
 // To manage date and time
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{SystemTime, UNIX_EPOCH, Duration, Instant};
 use time::OffsetDateTime;
 // To use intersperse()
 use itertools::Itertools;
-// To run commands
-use std::process::Command;
-// Event handling
-use notify::event::{EventKind, AccessKind};
 
 
 // Utils functions
@@ -32,6 +38,31 @@ use crate::index;
 use crate::event;
 // File reading continuously
 use crate::logreader;
+// To group the records of each audit event
+use crate::assembler;
+// To keep the read position of the audit log
+use crate::readstate;
+// Kernel audit interface
+#[cfg(target_os = "linux")]
+use crate::netlink;
+// Audit rules management
+use crate::auditrules;
+// Mass change detection
+use crate::storm;
+// Events severity and tags rules
+use crate::ruleset;
+// Package manager transactions correlation
+use crate::package;
+// User and group names resolution
+use crate::identity;
+// Process tree of audit events
+use crate::ancestry;
+// Audit information of monitor events
+use crate::whodata;
+// Audit events data
+use crate::auditevent;
+// To output the events
+use crate::fimevent::FimEvent;
 
 // ----------------------------------------------------------------------------
 
@@ -56,7 +87,7 @@ async fn push_template(destination: &str, config: config::Config){
     match destination {
         config::NETWORK_MODE|config::BOTH_MODE => {
             // On start push template (Include check if events won't be ingested by http)
-            index::push_template(config.endpoint_address, config.endpoint_user, config.endpoint_pass, config.insecure).await;
+            index::push_template(config.endpoint_address, config.endpoint_user, config.endpoint_pass, config.insecure, &config.events_format).await;
         },
         _ => {
             debug!("Template not pushed in file mode");
@@ -66,17 +97,141 @@ async fn push_template(destination: &str, config: config::Config){
 
 // ----------------------------------------------------------------------------
 
+// Wake up the main loop while there are audit events waiting for records,
+// events without end record are completed after the assembly timeout
+fn expire_timer(tx: mpsc::Sender<Result<notify::Event, notify::Error>>, pending: Arc<AtomicUsize>) {
+    thread::spawn(move || {
+        loop {
+            thread::sleep(Duration::from_millis(assembler::ASSEMBLY_TIMEOUT as u64));
+            if pending.load(Ordering::Relaxed) > 0 {
+                let event = notify::Event::new(notify::EventKind::Any).add_path(PathBuf::from(INPUT_EVENT));
+                if tx.send(Ok(event)).is_err() { break; }
+            }
+        }
+    });
+}
+
+// ----------------------------------------------------------------------------
+
+// Read audit records in a thread, from stdin when FIM runs as an auditd plugin
+// or from netlink. Lines are queued and the main loop is woken up with an
+// input event, None is queued when the input is closed.
+fn read_input<F>(tx: mpsc::Sender<Result<notify::Event, notify::Error>>,
+    queue: Arc<Mutex<Vec<Option<String>>>>, mut next: F)
+    where F: FnMut() -> Option<Vec<String>> + Send + 'static {
+    thread::spawn(move || {
+        loop {
+            let data = next();
+            let closed = data.is_none();
+            let wake = {
+                let mut queue = queue.lock().unwrap();
+                let empty = queue.is_empty();
+                match data {
+                    Some(lines) => queue.extend(lines.into_iter().map(Some)),
+                    None => queue.push(None)
+                }
+                empty && ! queue.is_empty()
+            };
+            // Only wake up the loop when it has consumed the previous lines
+            if wake {
+                let event = notify::Event::new(notify::EventKind::Any).add_path(PathBuf::from(INPUT_EVENT));
+                if tx.send(Ok(event)).is_err() { break; }
+            }
+            if closed { break; }
+        }
+    });
+}
+
+// ----------------------------------------------------------------------------
+
+fn read_stdin_line() -> Option<Vec<String>> {
+    let mut line = String::new();
+    match std::io::stdin().lock().read_line(&mut line) {
+        Ok(0) => None,
+        Ok(_) => Some(vec![line]),
+        Err(e) => {
+            error!("Could not read audit records from stdin, error: {}", e);
+            None
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Read the available audit events of the configured source
+fn read_audit_events(config: &config::Config, queue: &Arc<Mutex<Vec<Option<String>>>>,
+    state: &mut readstate::ReadState, assembler: &mut assembler::Assembler,
+    input_closed: &mut bool, pending: &AtomicUsize, throttle: &Mutex<readstate::Throttle>) -> Vec<auditevent::Event> {
+    let mut events = Vec::new();
+    if config.audit_source == config::AUDIT_FILE_SOURCE {
+        events = logreader::read_audit_log(&config.audit_log, state, config.clone(), assembler);
+        throttle.lock().unwrap().save(state);
+        debug!("Events read from audit log, position: {}, pending events: {}",
+            state.position, assembler.pending());
+    }else{
+        let lines: Vec<Option<String>> = queue.lock().unwrap().drain(..).collect();
+        for line in lines {
+            match line {
+                Some(data) => events.extend(logreader::read_line(data, config.clone(), assembler)),
+                None => *input_closed = true
+            }
+        }
+        debug!("Events read from audit input, pending events: {}", assembler.pending());
+    }
+    events.extend(assembler.expire_idle(Instant::now()).into_iter()
+        .filter_map(|data| logreader::build_event(data, config.clone())));
+    pending.store(assembler.pending(), Ordering::Relaxed);
+    events
+}
+
+// ----------------------------------------------------------------------------
+
+// Keep the user and process of an audit event of a whodata monitor path
+fn cache_whodata(cache: &mut whodata::Cache, identities: &mut identity::Resolver,
+    audit_event: &auditevent::Event) {
+    let user = match identities.get_user(audit_event.uid) {
+        name if name.is_empty() => utils::option_to_string(audit_event.uid),
+        name => name
+    };
+    let time = audit_event.timestamp.parse::<u128>().unwrap_or(0);
+    let file = whodata::get_file_path(&audit_event.path, &audit_event.file, &audit_event.cwd);
+    cache.insert(&file, time, whodata::WhoData {
+        user,
+        process: audit_event.exe.clone(),
+        pid: audit_event.pid,
+        command: audit_event.command.clone()
+    });
+    debug!("Who-data cached, pending entries: {}", cache.pending());
+}
+
+// ----------------------------------------------------------------------------
+
+// Remember the process of an audit event to report it as ancestor later
+fn record_process(processes: &mut ancestry::Tracker, audit_event: &auditevent::Event, time: u64) {
+    if let (Some(pid), Some(ppid)) = (audit_event.pid, audit_event.ppid) {
+        processes.record(ancestry::Process {
+            pid,
+            ppid,
+            exe: audit_event.exe.clone(),
+            uid: audit_event.uid
+        }, time);
+    }
+}
+
+// ----------------------------------------------------------------------------
+
 // Function that monitorize files in loop
 pub async fn monitor(tx: mpsc::Sender<Result<notify::Event, notify::Error>>,
     rx: mpsc::Receiver<Result<notify::Event, notify::Error>>){
 
-    let config = unsafe { super::GCONFIG.clone().unwrap() };
+    let config = super::GCONFIG.get().unwrap().clone();
     let destination = config.get_events_destination();
     setup_events(destination.as_str(), config.clone());
 
     // Check if we have to push index template
     push_template(destination.as_str(), config.clone()).await;
 
+    let input_tx = tx.clone();
     let mut watcher = RecommendedWatcher::new(tx, NConfig::default()).unwrap();
     
     // Iterating over monitor paths and set watcher on each folder to watch.
@@ -98,17 +253,37 @@ pub async fn monitor(tx: mpsc::Sender<Result<notify::Event, notify::Error>>,
             };
         }
     }
-    let mut last_position = 0;
-    if ! config.audit.is_empty() && utils::get_os() == "linux" && utils::check_auditd() {
-        for element in config.audit.clone() {
+    let mut audit_state = readstate::ReadState { position: 0, inode: 0, dev: 0 };
+    let mut assembler = assembler::Assembler::new();
+    let pending_groups = Arc::new(AtomicUsize::new(0));
+    // Shared with the exit handler to write the last read position
+    let state_throttle = Arc::new(Mutex::new(readstate::Throttle::new(
+        &config.events_audit_state_file, readstate::SAVE_INTERVAL)));
+    let input_queue: Arc<Mutex<Vec<Option<String>>>> = Arc::new(Mutex::new(Vec::new()));
+    let mut input_closed = false;
+    let mut detector = storm::Detector::new(&config);
+    let mut known_types = hash::KnownTypes::new(hash::MAX_KNOWN_TYPES);
+    let rules = ruleset::Ruleset::new(&config.rules_file);
+    let mut suppressed_events: u64 = 0;
+    let mut packages = package::Tracker::new(config.clone());
+    let mut identities = identity::Resolver::new();
+    let mut processes = ancestry::Tracker::new();
+    let mut whodata_cache = whodata::Cache::new();
+    // Audit events read while looking for who-data, processed in the next read
+    let mut deferred_events: Vec<auditevent::Event> = Vec::new();
+    if detector.is_enabled() {
+        info!("Mass change detection enabled, window: {} seconds", config.storm_window);
+    }
+    let rules_installed = (! config.audit.is_empty() || config.has_whodata()) &&
+        utils::get_os() == "linux" && auditrules::check_audit(&config);
+    if rules_installed {
+        // Add missing rules and remove the ones left by a previous run
+        auditrules::reconcile(&config);
+        auditrules::set_panic_cleanup(config.clone());
+        for (index, element) in config.audit.clone().iter().enumerate() {
             let path = element["path"].as_str().unwrap();
-            match Command::new("/usr/sbin/auditctl")
-                .args(["-w", path, "-k", "fim", "-p", "wax"])
-                .output() {
-                Ok(d) => debug!("Auditctl command info: {:?}", d),
-                Err(e) => error!("Auditctl command error: {}", e)
-            };
-            info!("Monitoring audit path: {}", path);
+            info!("Monitoring audit path: {}, permissions: {}, key: {}", path,
+                config.get_audit_permissions(index), config.get_audit_key(index));
             match element["ignore"].as_vec() {
                 Some(ig) => {
                     let ignore_list_vec  = ig.iter().map(|e| { e.as_str().unwrap() });
@@ -118,25 +293,56 @@ pub async fn monitor(tx: mpsc::Sender<Result<notify::Event, notify::Error>>,
                 None => info!("Ignore for '{}' not set", path)
             };
         }
-        // Detect if file is moved or renamed (rotation)
-        watcher.watch(Path::new(logreader::AUDIT_PATH), RecursiveMode::NonRecursive).unwrap();
-        last_position = utils::get_file_end(logreader::AUDIT_LOG_PATH, 0);
-        // Remove auditd rules introduced by FIM
-        let cconfig = config.clone();
-        ctrlc::set_handler(move || {
-            for element in &cconfig.audit {
-                let path = element["path"].as_str().unwrap();
-                match Command::new("/usr/sbin/auditctl")
-                    .args(["-W", path, "-k", "fim", "-p", "wax"])
-                    .output()
-                    {
-                        Ok(d) => debug!("Auditctl command info: {:?}", d),
-                        Err(e) => error!("Auditctl command error: {}", e)
-                    };
-            }
-            std::process::exit(0);
-        }).expect("Error setting Ctrl-C handler");
+        expire_timer(input_tx.clone(), pending_groups.clone());
+        if config.audit_source == config::AUDIT_STDIN_SOURCE {
+            info!("Reading audit records from stdin");
+            read_input(input_tx, input_queue.clone(), read_stdin_line);
+        }else if config.audit_source == config::AUDIT_NETLINK_SOURCE {
+            info!("Reading audit records from netlink");
+            #[cfg(target_os = "linux")]
+            match netlink::Client::new().and_then(|mut client| {
+                client.set_pid(utils::get_pid())?;
+                Ok(client)
+            }) {
+                Ok(client) => read_input(input_tx, input_queue.clone(), move || {
+                    match client.receive() {
+                        Ok(lines) => Some(lines),
+                        Err(e) => {
+                            error!("Could not receive netlink audit records, error: {}", e);
+                            None
+                        }
+                    }
+                }),
+                Err(e) => error!("Could not register netlink audit reader, error: {}", e)
+            };
+        }else{
+            info!("Reading audit records from: {}", config.audit_log);
+            // Detect if file is moved or renamed (rotation)
+            let audit_folder = Path::new(&config.audit_log).parent().unwrap();
+            watcher.watch(audit_folder, RecursiveMode::NonRecursive).unwrap();
+            // Resume from the saved position, events logged while stopped are read
+            audit_state = match readstate::ReadState::load(&config.events_audit_state_file) {
+                Some(state) => {
+                    info!("Resuming audit log reading, position: {}", state.position);
+                    state
+                },
+                None => {
+                    let (inode, dev) = readstate::get_file_id(&config.audit_log);
+                    readstate::ReadState { position: utils::get_file_end(&config.audit_log, 0), inode, dev }
+                }
+            };
+        }
     }
+    // Save the audit read position and remove the audit rules introduced by FIM
+    let cconfig = config.clone();
+    let cthrottle = state_throttle.clone();
+    ctrlc::set_handler(move || {
+        cthrottle.lock().unwrap().flush();
+        if rules_installed {
+            auditrules::shutdown(&cconfig);
+        }
+        std::process::exit(0);
+    }).expect("Error setting Ctrl-C handler");
 
 
     // Main loop, receive any produced event and write it into the events log.
@@ -150,6 +356,7 @@ pub async fn monitor(tx: mpsc::Sender<Result<notify::Event, notify::Error>>,
                     let plain_path: &str = event.paths[0].to_str().unwrap();
                     if plain_path == "DISCONNECT" {
                         info!("Received exit signal, exiting...");
+                        info!("Suppressed audit events: {}", suppressed_events);
                         break;
                     }
 
@@ -158,49 +365,93 @@ pub async fn monitor(tx: mpsc::Sender<Result<notify::Event, notify::Error>>,
 
                     let current_date = OffsetDateTime::now_utc();
                     let index_name = format!("fim-{}-{}-{}", current_date.year(), current_date.month() as u8, current_date.day() );
-                    let current_timestamp = format!("{:?}", SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_millis());
+                    let current_millis = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_millis();
+                    let current_timestamp = format!("{:?}", current_millis);
+                    let current_secs = u64::try_from(current_millis / 1000).unwrap();
                     let current_hostname = utils::get_hostname();
-                    let kind = event.kind.clone();
+                    let kind = event.kind;
                     let path = event.paths[0].clone();
 
-                    // Reset reading position due to log rotation
-                    if plain_path == logreader::AUDIT_LOG_PATH && kind == EventKind::Access(AccessKind::Any) {
-                        last_position = 0;
-                    }
-
-                    // If the event comes from audit.log
-                    if plain_path == logreader::AUDIT_LOG_PATH {
-                        // Getting events from audit.log
-                        let mut events = Vec::new();
-                        let (event, position) = logreader::read_log(String::from(logreader::AUDIT_LOG_PATH), config.clone(), last_position, 0);
-                        if event.id != "0" { events.push(event); };
-                        let mut ctr = 0;
-                        last_position = position;
-                        while last_position < utils::get_file_end(logreader::AUDIT_LOG_PATH, 0) {
-                            debug!("Reading events, iteration: {}", ctr);
-                            ctr += 1;
-                            let (evt, pos) = logreader::read_log(String::from(logreader::AUDIT_LOG_PATH), config.clone(), last_position, ctr);
-                            if evt.id != "0" {
-                                events.push(evt);
-                                ctr = 0;
-                            };
-                            last_position = pos;
-                        }
-                        debug!("Events read from audit log, position: {}", last_position);
+                    // If the event comes from audit.log or the audit plugin input
+                    if plain_path == config.audit_log || plain_path == INPUT_EVENT {
+                        let mut events = std::mem::take(&mut deferred_events);
+                        events.extend(read_audit_events(&config, &input_queue,
+                            &mut audit_state, &mut assembler, &mut input_closed, &pending_groups, &state_throttle));
 
-                        for audit_event in events {
-                            if ! audit_event.is_empty() {
+                        for mut audit_event in events {
+                            if audit_event.key == ancestry::EXECVE_KEY {
+                                record_process(&mut processes, &audit_event, current_secs);
+                            }else if audit_event.key == whodata::WHODATA_KEY {
+                                cache_whodata(&mut whodata_cache, &mut identities, &audit_event);
+                            }else if ! audit_event.is_empty() {
                                 // Getting the position of event in config (match ignore and labels)
-                                let index = config.get_index(audit_event.clone().path.as_str(),
-                                    audit_event.clone().cwd.as_str(),
-                                    config.audit.clone().to_vec());
+                                let index = config.get_audit_index(&audit_event.key,
+                                    audit_event.path.as_str(), audit_event.cwd.as_str());
+
+                                let uid = utils::option_to_string(audit_event.uid);
+                                let auid = utils::option_to_string(audit_event.auid);
 
                                 if index != usize::MAX {
+                                    // If event comes from an ignored process or user suppress it
+                                    if config.match_ignore_process(index, &audit_event.exe,
+                                            &audit_event.comm, &uid, &auid) {
+                                        suppressed_events += 1;
+                                        debug!("Event suppressed by process or user, total suppressed: {}", suppressed_events);
+                                        if suppressed_events.is_multiple_of(1000) {
+                                            info!("Suppressed audit events: {}", suppressed_events);
+                                        }
                                     // If event contains ignored string ignore event
-                                    if ! config.match_ignore(index,
+                                    }else if ! config.match_ignore(index,
                                             audit_event.clone().file.as_str(),
                                             config.audit.clone()) {
-                                        audit_event.process(destination.clone().as_str(), index_name.clone(), config.clone()).await;
+                                        let file = format!("{}/{}", audit_event.path, audit_event.file);
+                                        audit_event.uid_name = identities.get_user(audit_event.uid);
+                                        audit_event.euid_name = identities.get_user(audit_event.euid);
+                                        audit_event.auid_name = identities.get_user(audit_event.auid);
+                                        audit_event.ouid_name = identities.get_user(audit_event.ouid);
+                                        audit_event.gid_name = identities.get_group(audit_event.gid);
+                                        audit_event.egid_name = identities.get_group(audit_event.egid);
+                                        audit_event.ogid_name = identities.get_group(audit_event.ogid);
+                                        record_process(&mut processes, &audit_event, current_secs);
+                                        audit_event.process_ancestry = processes.get_ancestry(
+                                            audit_event.ppid.unwrap_or(0), current_secs);
+                                        audit_event.file_type_changed = hash::file_type_changed(&mut known_types,
+                                            &file, &audit_event.file_type);
+                                        let rule_match = rules.evaluate(&ruleset::Subject {
+                                            path: &file,
+                                            operation: ruleset::get_audit_operation(&audit_event.operation, &audit_event.action),
+                                            detailed_operation: &audit_event.operation,
+                                            exe: &audit_event.exe,
+                                            uid: &uid,
+                                            auid: &auid,
+                                            file_type: &audit_event.file_type,
+                                            mode: &audit_event.mode
+                                        });
+                                        audit_event.severity = rule_match.severity;
+                                        audit_event.rule_id = rule_match.rule_id;
+                                        audit_event.rule_description = rule_match.rule_description;
+                                        // Keep the tags set by the event decoding
+                                        for tag in rule_match.tags {
+                                            if ! audit_event.tags.contains(&tag) { audit_event.tags.push(tag) }
+                                        }
+                                        if packages.is_enabled() {
+                                            if let Some(package) = packages.lookup(&file, &audit_event.comm, current_secs) {
+                                                audit_event.expected = true;
+                                                audit_event.package = package.name;
+                                                audit_event.package_version = package.version;
+                                            }
+                                        }
+                                        if audit_event.expected && config.package_suppress {
+                                            debug!("Event expected by package transaction, suppressed");
+                                        }else{
+                                            audit_event.process(destination.clone().as_str(), index_name.clone(), config.clone()).await;
+                                        }
+                                        if detector.is_enabled() && ! audit_event.expected && audit_event.operation != "PARENT" {
+                                            let root = config.audit[index]["path"].as_str().unwrap();
+                                            for mut alert in detector.record(current_millis, root, &audit_event.exe, &file) {
+                                                alert.process(destination.clone().as_str(), index_name.clone(), config.clone()).await;
+                                            }
+                                        }
                                     }else{
                                         debug!("Event ignored not stored in alerts");
                                     }
@@ -210,30 +461,95 @@ pub async fn monitor(tx: mpsc::Sender<Result<notify::Event, notify::Error>>,
                             }
                             debug!("Event processed: {:?}", audit_event.clone());
                         }
+                        if input_closed {
+                            info!("Audit records input closed, exiting...");
+                            info!("Suppressed audit events: {}", suppressed_events);
+                            auditrules::shutdown(&config);
+                            return;
+                        }
                     }else {
                         let index = config.get_index(event_path.to_str().unwrap(), "", config.monitor.clone().to_vec());
                         if index != usize::MAX {
                             let labels = config.get_labels(index, config.monitor.clone());
                             if ! config.match_ignore(index,
                                 event_filename.to_str().unwrap(), config.monitor.clone()){
-                                let event = event::Event {
+                                let (checksum, entropy, file_type) = hash::get_file_info( String::from(path.to_str().unwrap()), config.events_max_file_checksum );
+                                let operation = event::get_operation(kind);
+                                let detailed_operation = event::get_detailed_operation(kind);
+                                let rule_match = rules.evaluate(&ruleset::Subject {
+                                    path: plain_path,
+                                    operation: &operation,
+                                    detailed_operation: &detailed_operation,
+                                    exe: "", uid: "", auid: "",
+                                    file_type: &file_type,
+                                    mode: ""
+                                });
+                                let package = match packages.is_enabled() {
+                                    true => packages.lookup(plain_path, "", current_secs),
+                                    false => None
+                                };
+                                let who = match config.match_whodata(index) {
+                                    true => match whodata_cache.lookup(plain_path, current_millis) {
+                                        Some(data) => Some(data),
+                                        None => {
+                                            // Audit records could not be read yet
+                                            for audit_event in read_audit_events(&config, &input_queue,
+                                                &mut audit_state, &mut assembler, &mut input_closed, &pending_groups, &state_throttle) {
+                                                if audit_event.key == whodata::WHODATA_KEY {
+                                                    cache_whodata(&mut whodata_cache, &mut identities, &audit_event);
+                                                }else{
+                                                    deferred_events.push(audit_event);
+                                                }
+                                            }
+                                            whodata_cache.lookup(plain_path, current_millis)
+                                        }
+                                    },
+                                    false => None
+                                };
+                                let mut event = event::Event {
                                     id: utils::get_uuid(),
                                     timestamp: current_timestamp,
+                                    iso_timestamp: utils::get_iso_timestamp(current_millis),
                                     hostname: current_hostname,
                                     node: config.node.clone(),
                                     version: String::from(config::VERSION),
-                                    kind: kind.clone(),
+                                    kind,
                                     path: path.clone(),
                                     labels,
-                                    operation: event::get_operation(kind.clone()),
-                                    detailed_operation: event::get_detailed_operation(kind),
-                                    checksum: hash::get_checksum( String::from(path.to_str().unwrap()), config.events_max_file_checksum ),
+                                    operation,
+                                    detailed_operation,
+                                    checksum,
+                                    entropy,
+                                    file_type_changed: hash::file_type_changed(&mut known_types, plain_path, &file_type),
+                                    file_type,
+                                    severity: rule_match.severity,
+                                    rule_id: rule_match.rule_id,
+                                    rule_description: rule_match.rule_description,
+                                    tags: rule_match.tags,
+                                    expected: package.is_some(),
+                                    package: package.clone().map(|p| p.name).unwrap_or_default(),
+                                    package_version: package.map(|p| p.version).unwrap_or_default(),
+                                    user: who.clone().map(|w| w.user).unwrap_or_default(),
+                                    process: who.clone().map(|w| w.process).unwrap_or_default(),
+                                    pid: who.clone().and_then(|w| w.pid),
+                                    command: who.map(|w| w.command).unwrap_or_default(),
                                     fpid: utils::get_pid(),
-                                    system: config.system.clone()
+                                    system: config.system.clone(),
+                                    sequence: 0
                                 };
 
                                 debug!("Event processed: {:?}", event);
-                                event.process(destination.clone().as_str(), index_name.clone(), config.clone()).await;
+                                if event.expected && config.package_suppress {
+                                    debug!("Event expected by package transaction, suppressed");
+                                }else{
+                                    event.process(destination.clone().as_str(), index_name.clone(), config.clone()).await;
+                                }
+                                if detector.is_enabled() && ! event.expected && storm::is_change(event.kind) {
+                                    let root = config.monitor[index]["path"].as_str().unwrap();
+                                    for mut alert in detector.record(current_millis, root, "", plain_path) {
+                                        alert.process(destination.clone().as_str(), index_name.clone(), config.clone()).await;
+                                    }
+                                }
                             }else{
                                 debug!("Event ignored not stored in alerts");
                             }
@@ -269,6 +585,24 @@ mod tests {
 
     // ------------------------------------------------------------------------
 
+    #[test]
+    fn test_cache_whodata() {
+        // Relative names are cached with the absolute path of notify events
+        let config = config::Config::new("linux", Some("test/unit/config/linux/whodata.yml"));
+        let mut assembler = assembler::Assembler::new();
+        let (events, _) = logreader::read_log(String::from("test/unit/audit_whodata.log"),
+            config, 0, &mut assembler);
+        assert_eq!(events[0].path, ".");
+        let mut cache = whodata::Cache::new();
+        cache_whodata(&mut cache, &mut identity::Resolver::new(), &events[0]);
+        let who = cache.lookup("/tmp/sedTsutP7", 1659026449689).unwrap();
+        assert_eq!(who.process, "/usr/bin/sed");
+        assert_eq!(who.pid, Some(161937));
+        assert_eq!(cache.lookup("./sedTsutP7", 1659026449689), None);
+    }
+
+    // ------------------------------------------------------------------------
+
     #[test]
     fn test_setup_events() {
         let config = config::Config::new(&utils::get_os(), None);
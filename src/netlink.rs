@@ -0,0 +1,609 @@
+// Copyright (C) 2023, Achiefs.
+
+// Global constants definitions
+const NETLINK_AUDIT: i32 = 9;
+const NLMSG_HEADER_SIZE: usize = 16;
+const NLMSG_ERROR: u16 = 2;
+const NLMSG_DONE: u16 = 3;
+const NLM_F_REQUEST: u16 = 0x01;
+const NLM_F_ACK: u16 = 0x04;
+const AUDIT_GET: u16 = 1000;
+const AUDIT_SET: u16 = 1001;
+const AUDIT_ADD_RULE: u16 = 1011;
+const AUDIT_DEL_RULE: u16 = 1012;
+const AUDIT_LIST_RULES: u16 = 1013;
+const AUDIT_STATUS_ENABLED: u32 = 0x01;
+const AUDIT_STATUS_PID: u32 = 0x04;
+const AUDIT_FILTER_EXIT: u32 = 0x04;
+const AUDIT_ALWAYS: u32 = 2;
+const AUDIT_BITMASK_SIZE: usize = 64;
+const AUDIT_ARCH: u32 = 11;
+const AUDIT_WATCH: u32 = 105;
+const AUDIT_PERM: u32 = 106;
+const AUDIT_DIR: u32 = 107;
+const AUDIT_EXE: u32 = 112;
+const AUDIT_FILTERKEY: u32 = 210;
+const AUDIT_EQUAL: u32 = 0x40000000;
+const AUDIT_PERM_EXEC: u32 = 1;
+const AUDIT_PERM_WRITE: u32 = 2;
+const AUDIT_PERM_READ: u32 = 4;
+const AUDIT_PERM_ATTR: u32 = 8;
+const MAX_AUDIT_MESSAGE_LENGTH: usize = 8970;
+
+// To manage the netlink socket
+use std::io::{Error, Result};
+use std::mem;
+// To log the program process
+use log::{debug, warn};
+
+// ----------------------------------------------------------------------------
+
+// Socket connected to the kernel audit subsystem
+pub struct Client {
+    fd: i32,
+    sequence: u32
+}
+
+impl Client {
+    pub fn new() -> Result<Self> {
+        let fd = unsafe { libc::socket(libc::AF_NETLINK, libc::SOCK_RAW | libc::SOCK_CLOEXEC, NETLINK_AUDIT) };
+        if fd < 0 {
+            return Err(Error::last_os_error())
+        }
+        Ok(Client { fd, sequence: 0 })
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Register this socket as the audit daemon, the kernel sends the audit
+    // records to it from now on
+    pub fn set_pid(&mut self, pid: u32) -> Result<()> {
+        self.request(AUDIT_SET, &build_status(AUDIT_STATUS_PID | AUDIT_STATUS_ENABLED, pid))
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Unregister the audit daemon so the kernel stops sending records to a
+    // socket that is going to be closed
+    pub fn clear_pid(&mut self) -> Result<()> {
+        self.request(AUDIT_SET, &build_status(AUDIT_STATUS_PID, 0))
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Request the audit status, it fails without the audit control capability
+    pub fn get_status(&mut self) -> Result<()> {
+        self.request(AUDIT_GET, &[])
+    }
+
+    // ------------------------------------------------------------------------
+
+    pub fn add_watch(&mut self, path: &str, permissions: &str, key: &str) -> Result<()> {
+        self.request(AUDIT_ADD_RULE, &build_watch_rule(path, permissions, key))
+    }
+
+    // ------------------------------------------------------------------------
+
+    pub fn delete_watch(&mut self, path: &str, permissions: &str, key: &str) -> Result<()> {
+        self.request(AUDIT_DEL_RULE, &build_watch_rule(path, permissions, key))
+    }
+
+    // ------------------------------------------------------------------------
+
+    pub fn add_syscall(&mut self, arch: u32, syscall: u32, key: &str) -> Result<()> {
+        self.request(AUDIT_ADD_RULE, &build_syscall_rule(arch, syscall, key))
+    }
+
+    // ------------------------------------------------------------------------
+
+    pub fn delete_syscall(&mut self, arch: u32, syscall: u32, key: &str) -> Result<()> {
+        self.request(AUDIT_DEL_RULE, &build_syscall_rule(arch, syscall, key))
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Equivalent to 'auditctl -l', returns the watch rules and the syscall
+    // rules without path
+    pub fn list_rules(&mut self) -> Result<Vec<ListedRule>> {
+        self.send(AUDIT_LIST_RULES, NLM_F_REQUEST, &[])?;
+        let mut rules = Vec::new();
+        let mut buffer = vec![0u8; MAX_AUDIT_MESSAGE_LENGTH + NLMSG_HEADER_SIZE];
+        loop {
+            let size = self.recv(&mut buffer)?;
+            for (reply_type, reply) in parse_messages(&buffer[..size]) {
+                match reply_type {
+                    AUDIT_LIST_RULES => rules.extend(parse_rule(&reply)),
+                    NLMSG_DONE => return Ok(rules),
+                    NLMSG_ERROR => get_error(&reply).map_or(Ok(()), Err)?,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Wait for the next audit records, returned in audit.log format
+    pub fn receive(&self) -> Result<Vec<String>> {
+        let mut buffer = vec![0u8; MAX_AUDIT_MESSAGE_LENGTH + NLMSG_HEADER_SIZE];
+        let size = self.recv(&mut buffer)?;
+        Ok(parse_messages(&buffer[..size]).into_iter()
+            .filter(|(msg_type, _)| *msg_type != NLMSG_ERROR && *msg_type != NLMSG_DONE)
+            .map(|(msg_type, payload)| format_record(msg_type, &payload))
+            .collect())
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Send a message and wait for the kernel acknowledgement
+    fn request(&mut self, msg_type: u16, payload: &[u8]) -> Result<()> {
+        self.send(msg_type, NLM_F_REQUEST | NLM_F_ACK, payload)?;
+        let mut buffer = vec![0u8; MAX_AUDIT_MESSAGE_LENGTH];
+        loop {
+            let size = self.recv(&mut buffer)?;
+            for (reply_type, reply) in parse_messages(&buffer[..size]) {
+                if reply_type == NLMSG_ERROR && reply.len() >= 4 {
+                    debug!("Netlink audit reply to message {}, code: {}", msg_type, get_code(&reply));
+                    return match get_error(&reply) {
+                        None => Ok(()),
+                        Some(e) => Err(e)
+                    }
+                }
+            }
+        }
+    }
+
+    // ------------------------------------------------------------------------
+
+    fn send(&mut self, msg_type: u16, flags: u16, payload: &[u8]) -> Result<()> {
+        self.sequence += 1;
+        let message = build_message(msg_type, flags, self.sequence, payload);
+        let mut address: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        address.nl_family = libc::AF_NETLINK as u16;
+        let sent = unsafe {
+            libc::sendto(self.fd, message.as_ptr() as *const libc::c_void, message.len(), 0,
+                &address as *const libc::sockaddr_nl as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_nl>() as u32)
+        };
+        if sent < 0 {
+            return Err(Error::last_os_error())
+        }
+        Ok(())
+    }
+
+    // ------------------------------------------------------------------------
+
+    fn recv(&self, buffer: &mut [u8]) -> Result<usize> {
+        let size = unsafe {
+            libc::recv(self.fd, buffer.as_mut_ptr() as *mut libc::c_void, buffer.len(), 0)
+        };
+        if size < 0 {
+            return Err(Error::last_os_error())
+        }
+        Ok(size as usize)
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Rule loaded in the kernel, path is empty for syscall rules and syscalls
+// is empty for watches
+#[derive(Debug, PartialEq, Eq)]
+pub struct ListedRule {
+    pub path: String,
+    pub permissions: String,
+    pub key: String,
+    pub syscalls: Vec<u32>
+}
+
+// ----------------------------------------------------------------------------
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Check if this host can use the kernel audit netlink interface, the socket
+// can be created in containers without permissions to use it
+pub fn is_available() -> bool {
+    match Client::new().and_then(|mut client| client.get_status()) {
+        Ok(_) => true,
+        Err(e) => {
+            warn!("Audit netlink socket not available, error: {}", e);
+            false
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+fn build_message(msg_type: u16, flags: u16, sequence: u32, payload: &[u8]) -> Vec<u8> {
+    let length = NLMSG_HEADER_SIZE + payload.len();
+    let mut message = Vec::with_capacity(align(length));
+    message.extend((length as u32).to_ne_bytes());
+    message.extend(msg_type.to_ne_bytes());
+    message.extend(flags.to_ne_bytes());
+    message.extend(sequence.to_ne_bytes());
+    message.extend(0u32.to_ne_bytes());
+    message.extend(payload);
+    message.resize(align(length), 0);
+    message
+}
+
+// ----------------------------------------------------------------------------
+
+// Audit status payload with the mask of the fields to set
+fn build_status(mask: u32, pid: u32) -> Vec<u8> {
+    let mut status: Vec<u8> = Vec::new();
+    let enabled = u32::from(mask & AUDIT_STATUS_ENABLED != 0);
+    // mask, enabled, failure, pid, rate_limit, backlog_limit, lost,
+    // backlog, feature_bitmap and backlog_wait_time fields
+    for value in [mask, enabled, 0, pid, 0, 0, 0, 0, 0, 0] {
+        status.extend(value.to_ne_bytes());
+    }
+    status
+}
+
+// ----------------------------------------------------------------------------
+
+// Split a netlink buffer in (type, payload) messages
+fn parse_messages(buffer: &[u8]) -> Vec<(u16, Vec<u8>)> {
+    let mut messages = Vec::new();
+    let mut offset = 0;
+    while offset + NLMSG_HEADER_SIZE <= buffer.len() {
+        let header = &buffer[offset..offset + NLMSG_HEADER_SIZE];
+        let length = u32::from_ne_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let msg_type = u16::from_ne_bytes([header[4], header[5]]);
+        // Audit records do not always set the length including the header
+        let end = (offset + length.max(NLMSG_HEADER_SIZE)).min(buffer.len());
+        messages.push((msg_type, buffer[offset + NLMSG_HEADER_SIZE..end].to_vec()));
+        offset += align(length.max(NLMSG_HEADER_SIZE));
+    }
+    messages
+}
+
+// ----------------------------------------------------------------------------
+
+// Error code of a NLMSG_ERROR payload, zero is an acknowledgement
+fn get_code(reply: &[u8]) -> i32 {
+    match reply.get(..4) {
+        Some(code) => i32::from_ne_bytes([code[0], code[1], code[2], code[3]]),
+        None => 0
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+fn get_error(reply: &[u8]) -> Option<Error> {
+    match get_code(reply) {
+        0 => None,
+        code => Some(Error::from_raw_os_error(-code))
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Equivalent to 'auditctl -w path -p permissions -k key'
+fn build_watch_rule(path: &str, permissions: &str, key: &str) -> Vec<u8> {
+    // Watches apply to all syscalls
+    build_rule(&[u32::MAX; AUDIT_BITMASK_SIZE], &[
+        (AUDIT_WATCH, path.len() as u32),
+        (AUDIT_PERM, get_permissions(permissions)),
+        (AUDIT_FILTERKEY, key.len() as u32)
+    ], &[path, key])
+}
+
+// ----------------------------------------------------------------------------
+
+// Equivalent to 'auditctl -a always,exit -F arch=... -S syscall -k key'
+fn build_syscall_rule(arch: u32, syscall: u32, key: &str) -> Vec<u8> {
+    let mut mask = [0u32; AUDIT_BITMASK_SIZE];
+    if let Some(word) = mask.get_mut(syscall as usize / 32) {
+        *word = 1 << (syscall % 32);
+    }
+    build_rule(&mask, &[
+        (AUDIT_ARCH, arch),
+        (AUDIT_FILTERKEY, key.len() as u32)
+    ], &[key])
+}
+
+// ----------------------------------------------------------------------------
+
+// Exit rule with the syscalls mask, (field, value) pairs and the string
+// values in the order of their fields
+fn build_rule(mask: &[u32; AUDIT_BITMASK_SIZE], rule_fields: &[(u32, u32)], strings: &[&str]) -> Vec<u8> {
+    let mut fields = [0u32; AUDIT_BITMASK_SIZE];
+    let mut values = [0u32; AUDIT_BITMASK_SIZE];
+    let mut fieldflags = [0u32; AUDIT_BITMASK_SIZE];
+    for (index, (field, value)) in rule_fields.iter().enumerate() {
+        fields[index] = *field;
+        values[index] = *value;
+        fieldflags[index] = AUDIT_EQUAL;
+    }
+
+    let mut rule: Vec<u8> = Vec::new();
+    rule.extend(AUDIT_FILTER_EXIT.to_ne_bytes());
+    rule.extend(AUDIT_ALWAYS.to_ne_bytes());
+    rule.extend((rule_fields.len() as u32).to_ne_bytes());
+    for value in mask.iter().chain(fields.iter()).chain(values.iter()).chain(fieldflags.iter()) {
+        rule.extend(value.to_ne_bytes());
+    }
+    rule.extend((strings.iter().map(|s| s.len()).sum::<usize>() as u32).to_ne_bytes());
+    for text in strings {
+        rule.extend(text.as_bytes());
+    }
+    rule
+}
+
+// ----------------------------------------------------------------------------
+
+// Get a listed rule, None if it has neither path nor syscalls
+fn parse_rule(rule: &[u8]) -> Option<ListedRule> {
+    let read = |offset: usize| rule.get(offset..offset + 4)
+        .map(|v| u32::from_ne_bytes([v[0], v[1], v[2], v[3]]));
+    let fields = 12 + AUDIT_BITMASK_SIZE * 4;
+    let values = fields + AUDIT_BITMASK_SIZE * 4;
+    let buffer = values + AUDIT_BITMASK_SIZE * 4 * 2 + 4;
+    let count = (read(8)? as usize).min(AUDIT_BITMASK_SIZE);
+
+    let mut path = None;
+    let mut permissions = String::new();
+    let mut key = String::new();
+    let mut offset = buffer;
+    for index in 0..count {
+        let field = read(fields + index * 4)?;
+        let value = read(values + index * 4)?;
+        // String values are stored one after another in the rule buffer
+        let text = match field {
+            AUDIT_WATCH | AUDIT_DIR | AUDIT_FILTERKEY | AUDIT_EXE | 13..=17 | 19..=23 => {
+                let text = String::from_utf8_lossy(rule.get(offset..offset + value as usize)?).to_string();
+                offset += value as usize;
+                text
+            },
+            _ => String::new()
+        };
+        match field {
+            AUDIT_WATCH | AUDIT_DIR => path = Some(text),
+            AUDIT_FILTERKEY => key = text,
+            AUDIT_PERM => permissions = get_permissions_text(value),
+            _ => {}
+        }
+    }
+    let syscalls = match path {
+        Some(_) => Vec::new(),
+        None => (0..AUDIT_BITMASK_SIZE * 32).filter(|syscall| read(12 + syscall / 32 * 4)
+            .map(|word| word & (1 << (syscall % 32)) != 0).unwrap_or(false))
+            .map(|syscall| syscall as u32).collect()
+    };
+    // Rules of all syscalls without path are not managed by FIM
+    if path.is_none() && (syscalls.is_empty() || syscalls.len() == AUDIT_BITMASK_SIZE * 32) {
+        return None
+    }
+    Some(ListedRule { path: path.unwrap_or_default(), permissions, key, syscalls })
+}
+
+// ----------------------------------------------------------------------------
+
+// Translate permissions in auditctl format 'rwxa' to the rule bitmask
+fn get_permissions(permissions: &str) -> u32 {
+    permissions.chars().fold(0, |mask, c| mask | match c {
+        'r' => AUDIT_PERM_READ,
+        'w' => AUDIT_PERM_WRITE,
+        'x' => AUDIT_PERM_EXEC,
+        'a' => AUDIT_PERM_ATTR,
+        _ => 0
+    })
+}
+
+// ----------------------------------------------------------------------------
+
+// Translate a rule permissions bitmask to auditctl format 'rwxa'
+fn get_permissions_text(mask: u32) -> String {
+    [('r', AUDIT_PERM_READ), ('w', AUDIT_PERM_WRITE), ('x', AUDIT_PERM_EXEC), ('a', AUDIT_PERM_ATTR)]
+        .iter().filter(|(_, bit)| mask & bit != 0).map(|(c, _)| *c).collect()
+}
+
+// ----------------------------------------------------------------------------
+
+// Build an audit.log line from a netlink record
+fn format_record(msg_type: u16, payload: &[u8]) -> String {
+    let text = String::from_utf8_lossy(payload);
+    format!("type={} msg={}\n", get_record_name(msg_type), text.trim_end_matches(['\0', '\n']))
+}
+
+// ----------------------------------------------------------------------------
+
+pub fn get_record_name(msg_type: u16) -> String {
+    match msg_type {
+        1300 => String::from("SYSCALL"),
+        1302 => String::from("PATH"),
+        1305 => String::from("CONFIG_CHANGE"),
+        1307 => String::from("CWD"),
+        1309 => String::from("EXECVE"),
+        1320 => String::from("EOE"),
+        1327 => String::from("PROCTITLE"),
+        _ => format!("UNKNOWN[{}]", msg_type)
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+fn align(length: usize) -> usize {
+    (length + 3) & !3
+}
+
+// ----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_u32(data: &[u8], offset: usize) -> u32 {
+        u32::from_ne_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_build_message() {
+        let message = build_message(AUDIT_SET, NLM_F_REQUEST | NLM_F_ACK, 7, &[1, 2, 3]);
+        assert_eq!(message.len(), 20);
+        assert_eq!(read_u32(&message, 0), 19);
+        assert_eq!(u16::from_ne_bytes([message[4], message[5]]), AUDIT_SET);
+        assert_eq!(u16::from_ne_bytes([message[6], message[7]]), 5);
+        assert_eq!(read_u32(&message, 8), 7);
+        assert_eq!(&message[16..], &[1, 2, 3, 0]);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_build_status() {
+        let status = build_status(AUDIT_STATUS_PID | AUDIT_STATUS_ENABLED, 1234);
+        assert_eq!(status.len(), 40);
+        assert_eq!(read_u32(&status, 0), 5);
+        assert_eq!(read_u32(&status, 4), 1);
+        assert_eq!(read_u32(&status, 12), 1234);
+        let status = build_status(AUDIT_STATUS_PID, 0);
+        assert_eq!(read_u32(&status, 0), AUDIT_STATUS_PID);
+        assert_eq!(read_u32(&status, 4), 0);
+        assert_eq!(read_u32(&status, 12), 0);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_parse_messages() {
+        let mut buffer = build_message(1300, 0, 1, b"audit(1.002:3): arch=c000003e\0");
+        buffer.extend(build_message(1320, 0, 2, b"audit(1.002:3): "));
+        let messages = parse_messages(&buffer);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].0, 1300);
+        assert_eq!(format_record(messages[0].0, &messages[0].1),
+            "type=SYSCALL msg=audit(1.002:3): arch=c000003e\n");
+        assert_eq!(messages[1].0, 1320);
+        assert!(parse_messages(&[0, 1, 2]).is_empty());
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_build_watch_rule() {
+        let rule = build_watch_rule("/tmp", "wa", "fim");
+        assert_eq!(rule.len(), 4 * 3 + AUDIT_BITMASK_SIZE * 4 * 4 + 4 + 7);
+        assert_eq!(read_u32(&rule, 0), AUDIT_FILTER_EXIT);
+        assert_eq!(read_u32(&rule, 4), AUDIT_ALWAYS);
+        assert_eq!(read_u32(&rule, 8), 3);
+        assert_eq!(read_u32(&rule, 12), u32::MAX);
+        let fields = 12 + AUDIT_BITMASK_SIZE * 4;
+        let values = fields + AUDIT_BITMASK_SIZE * 4;
+        let flags = values + AUDIT_BITMASK_SIZE * 4;
+        assert_eq!(read_u32(&rule, fields), AUDIT_WATCH);
+        assert_eq!(read_u32(&rule, fields + 4), AUDIT_PERM);
+        assert_eq!(read_u32(&rule, fields + 8), AUDIT_FILTERKEY);
+        assert_eq!(read_u32(&rule, values), 4);
+        assert_eq!(read_u32(&rule, values + 4), AUDIT_PERM_WRITE | AUDIT_PERM_ATTR);
+        assert_eq!(read_u32(&rule, values + 8), 3);
+        assert_eq!(read_u32(&rule, flags), AUDIT_EQUAL);
+        assert_eq!(read_u32(&rule, flags + 12), 0);
+        let buffer = flags + AUDIT_BITMASK_SIZE * 4;
+        assert_eq!(read_u32(&rule, buffer), 7);
+        assert_eq!(&rule[buffer + 4..], b"/tmpfim");
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_get_permissions() {
+        assert_eq!(get_permissions("rwxa"), 15);
+        assert_eq!(get_permissions("wax"), AUDIT_PERM_WRITE | AUDIT_PERM_ATTR | AUDIT_PERM_EXEC);
+        assert_eq!(get_permissions(""), 0);
+        assert_eq!(get_permissions("z"), 0);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_build_syscall_rule() {
+        let rule = build_syscall_rule(0xc000003e, 59, "fim_execve");
+        assert_eq!(rule.len(), 4 * 3 + AUDIT_BITMASK_SIZE * 4 * 4 + 4 + 10);
+        assert_eq!(read_u32(&rule, 8), 2);
+        assert_eq!(read_u32(&rule, 12), 0);
+        assert_eq!(read_u32(&rule, 12 + 4), 1 << 27);
+        let fields = 12 + AUDIT_BITMASK_SIZE * 4;
+        let values = fields + AUDIT_BITMASK_SIZE * 4;
+        assert_eq!(read_u32(&rule, fields), AUDIT_ARCH);
+        assert_eq!(read_u32(&rule, fields + 4), AUDIT_FILTERKEY);
+        assert_eq!(read_u32(&rule, values), 0xc000003e);
+        assert_eq!(read_u32(&rule, values + 4), 10);
+        assert!(rule.ends_with(b"fim_execve"));
+    }
+
+    // ------------------------------------------------------------------------
+
+    fn create_listed(path: &str, permissions: &str, key: &str, syscalls: Vec<u32>) -> ListedRule {
+        ListedRule {
+            path: String::from(path),
+            permissions: String::from(permissions),
+            key: String::from(key),
+            syscalls
+        }
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_parse_rule() {
+        assert_eq!(parse_rule(&build_watch_rule("/tmp", "wa", "fim")),
+            Some(create_listed("/tmp", "wa", "fim", Vec::new())));
+
+        // Directory watches are listed with the dir field
+        let mut rule = build_watch_rule("/etc", "rwxa", "fim_audit");
+        let fields = 12 + AUDIT_BITMASK_SIZE * 4;
+        rule[fields..fields + 4].copy_from_slice(&AUDIT_DIR.to_ne_bytes());
+        assert_eq!(parse_rule(&rule),
+            Some(create_listed("/etc", "rwxa", "fim_audit", Vec::new())));
+
+        // Syscall rules without path
+        assert_eq!(parse_rule(&build_syscall_rule(0xc000003e, 59, "fim_execve")),
+            Some(create_listed("", "", "fim_execve", vec![59])));
+        let mut rule = build_watch_rule("/tmp", "wa", "fim");
+        rule[fields..fields + 4].copy_from_slice(&0u32.to_ne_bytes());
+        assert_eq!(parse_rule(&rule), None);
+        assert_eq!(parse_rule(&rule[..20]), None);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_get_permissions_text() {
+        assert_eq!(get_permissions_text(15), "rwxa");
+        assert_eq!(get_permissions_text(get_permissions("aw")), "wa");
+        assert_eq!(get_permissions_text(0), "");
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_get_error() {
+        assert!(get_error(&0i32.to_ne_bytes()).is_none());
+        assert_eq!(get_error(&(-1i32).to_ne_bytes()).unwrap().raw_os_error(), Some(1));
+        assert!(get_error(&[]).is_none());
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_get_record_name() {
+        assert_eq!(get_record_name(1300), "SYSCALL");
+        assert_eq!(get_record_name(1302), "PATH");
+        assert_eq!(get_record_name(1307), "CWD");
+        assert_eq!(get_record_name(1309), "EXECVE");
+        assert_eq!(get_record_name(1320), "EOE");
+        assert_eq!(get_record_name(1327), "PROCTITLE");
+        assert_eq!(get_record_name(1400), "UNKNOWN[1400]");
+    }
+}
@@ -0,0 +1,327 @@
+// Copyright (C) 2023, Achiefs.
+
+// Global constants definitions
+pub const DPKG_LOG: &str = "/var/log/dpkg.log";
+pub const DNF_LOG: &str = "/var/log/dnf.rpm.log";
+pub const YUM_LOG: &str = "/var/log/yum.log";
+pub const DPKG_INFO: &str = "/var/lib/dpkg/info";
+// Process names (audit comm) of package managers
+pub const PACKAGE_MANAGERS: [&str; 10] = ["dpkg", "apt", "apt-get", "aptitude",
+    "unattended-upgr", "rpm", "dnf", "yum", "zypper", "packagekitd"];
+const DPKG_ACTIONS: [&str; 4] = ["install", "upgrade", "remove", "purge"];
+const RPM_ACTIONS: [&str; 12] = ["Install:", "Installed:", "Upgrade:", "Upgraded:",
+    "Updated:", "Erase:", "Erased:", "Reinstall:", "Reinstalled:", "Downgrade:",
+    "Downgraded:", "Obsoleted:"];
+
+// To read package manager logs
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::SystemTime;
+// To run commands
+use std::process::Command;
+// To keep the read position of each log
+use std::collections::{HashMap, HashSet};
+// To log the program process
+use log::{debug, info};
+
+// To get configuration values
+use crate::config;
+// To manage common functions
+use crate::utils;
+
+// ----------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Package {
+    pub name: String,
+    pub version: String
+}
+
+// ----------------------------------------------------------------------------
+
+// Package changed in a transaction and the second it was read, the dpkg
+// file list is loaded on the first lookup
+struct Change {
+    package: Package,
+    time: u64,
+    rpm: bool,
+    files: Option<HashSet<String>>
+}
+
+// ----------------------------------------------------------------------------
+
+pub struct Tracker {
+    enabled: bool,
+    window: u64,
+    logs: Vec<String>,
+    dpkg_info: String,
+    positions: HashMap<String, u64>,
+    // Size and modification time of each log when it was last read
+    stamps: HashMap<String, (u64, Option<SystemTime>)>,
+    changes: Vec<Change>
+}
+
+impl Tracker {
+    pub fn new(config: config::Config) -> Self {
+        let logs: Vec<String> = [DPKG_LOG, DNF_LOG, YUM_LOG].iter()
+            .map(|l| String::from(*l)).collect();
+        // Only transactions done after the start are correlated
+        let positions = logs.iter()
+            .filter(|l| Path::new(l).exists())
+            .map(|l| (l.clone(), utils::get_file_end(l, 0)))
+            .collect();
+        if config.package_enabled {
+            info!("Package transaction correlation enabled, window: {} seconds", config.package_window);
+        }
+        Tracker {
+            enabled: config.package_enabled,
+            window: config.package_window,
+            logs,
+            dpkg_info: String::from(DPKG_INFO),
+            positions,
+            stamps: HashMap::new(),
+            changes: Vec::new()
+        }
+    }
+
+    // ------------------------------------------------------------------------
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Check if the change of 'file' at 'time' seconds is expected, it is done
+    // by a package manager process 'comm' (empty if unknown) or the file
+    // belongs to a package changed in a recent transaction.
+    // Returns the package owning the file, empty name when it is not found.
+    pub fn lookup(&mut self, file: &str, comm: &str, time: u64) -> Option<Package> {
+        self.update(time);
+        let owner = match self.changes.is_empty() {
+            true => None,
+            false => self.get_owner(file)
+        };
+        match owner {
+            Some(package) => {
+                debug!("File '{}' changed by package '{}'", file, package.name);
+                Some(package)
+            },
+            None => if PACKAGE_MANAGERS.contains(&comm) {
+                Some(Package { name: String::new(), version: String::new() })
+            }else{ None }
+        }
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Read the new lines of changed package manager logs and forget old
+    // transactions
+    fn update(&mut self, time: u64) {
+        for log in self.logs.clone() {
+            let stamp = match fs::metadata(&log) {
+                Ok(metadata) => (metadata.len(), metadata.modified().ok()),
+                Err(_) => continue
+            };
+            if self.stamps.get(&log) == Some(&stamp) { continue }
+            self.stamps.insert(log.clone(), stamp);
+            let rpm = ! log.ends_with("dpkg.log");
+            for package in self.read_log(&log) {
+                debug!("Package transaction detected: {} {}", package.name, package.version);
+                self.changes.retain(|c| c.package.name != package.name);
+                self.changes.push(Change { package, time, rpm, files: None });
+            }
+        }
+        let window = self.window;
+        self.changes.retain(|c| time.saturating_sub(c.time) <= window);
+    }
+
+    // ------------------------------------------------------------------------
+
+    fn read_log(&mut self, log: &str) -> Vec<Package> {
+        let mut file = match File::open(log) {
+            Ok(f) => f,
+            Err(_) => return Vec::new()
+        };
+        let end = file.metadata().map(|m| m.len()).unwrap_or(0);
+        let mut position = *self.positions.get(log).unwrap_or(&0);
+        // Log truncated or rotated
+        if position > end { position = 0 }
+        let mut contents = String::new();
+        if file.seek(SeekFrom::Start(position)).is_err() ||
+            file.read_to_string(&mut contents).is_err() {
+            return Vec::new()
+        }
+        // Consume only full lines, the rest is read in the next update
+        let consumed = contents.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        self.positions.insert(String::from(log), position + consumed as u64);
+        contents[..consumed].lines()
+            .filter_map(|line| if log.ends_with("dpkg.log") {
+                parse_dpkg_line(line)
+            }else{
+                parse_rpm_line(line)
+            }).collect()
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Get the recently changed package that owns 'file', the rpm database
+    // is queried once per file and only if rpm transactions were read
+    fn get_owner(&mut self, file: &str) -> Option<Package> {
+        let dpkg_info = &self.dpkg_info;
+        for change in self.changes.iter_mut().filter(|c| ! c.rpm) {
+            let files = change.files.get_or_insert_with(|| read_dpkg_files(dpkg_info, &change.package.name));
+            if files.contains(file) { return Some(change.package.clone()) }
+        }
+        if ! self.changes.iter().any(|c| c.rpm) { return None }
+        match Command::new("rpm").args(["-qf", "--queryformat", "%{NAME}", file]).output() {
+            Ok(output) if output.status.success() => {
+                let name = String::from_utf8_lossy(&output.stdout);
+                self.changes.iter().find(|c| c.rpm && c.package.name == name)
+                    .map(|c| c.package.clone())
+            },
+            _ => None
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Files installed by a dpkg package, multiarch packages store their files
+// as 'name:arch.list'
+fn read_dpkg_files(dpkg_info: &str, name: &str) -> HashSet<String> {
+    let mut lists = vec![format!("{}/{}.list", dpkg_info, name)];
+    if let Ok(paths) = glob::glob(&format!("{}/{}:*.list", dpkg_info, name)) {
+        lists.extend(paths.flatten().map(|p| p.to_string_lossy().to_string()));
+    }
+    lists.iter()
+        .filter_map(|list| fs::read_to_string(list).ok())
+        .flat_map(|contents| contents.lines().map(String::from).collect::<Vec<String>>())
+        .collect()
+}
+
+// ----------------------------------------------------------------------------
+
+// Parse dpkg.log lines like "2023-05-10 10:00:00 upgrade bash:amd64 5.1-6 5.2-1"
+pub fn parse_dpkg_line(line: &str) -> Option<Package> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < 6 || ! DPKG_ACTIONS.contains(&fields[2]) { return None }
+    let name = fields[3].split(':').next().unwrap_or(fields[3]);
+    let version = if fields[5] == "<none>" { fields[4] } else { fields[5] };
+    Some(Package { name: String::from(name), version: String::from(version) })
+}
+
+// ----------------------------------------------------------------------------
+
+// Parse dnf.rpm.log and yum.log lines like "... Upgrade: bash-5.2.15-1.fc38.x86_64"
+pub fn parse_rpm_line(line: &str) -> Option<Package> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let position = fields.iter().position(|f| RPM_ACTIONS.contains(f))?;
+    let nevra = fields.get(position + 1)?;
+    // Remove epoch and architecture
+    let nevra = nevra.split_once(':').map(|(_, n)| n).unwrap_or(nevra);
+    let nvr = match nevra.rsplit_once('.') {
+        Some((nvr, arch)) if ! arch.chars().next().unwrap_or('0').is_ascii_digit() => nvr,
+        _ => nevra
+    };
+    let (nv, release) = nvr.rsplit_once('-')?;
+    let (name, version) = nv.rsplit_once('-')?;
+    Some(Package { name: String::from(name), version: format!("{}-{}", version, release) })
+}
+
+// ----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+
+    fn create_test_tracker(log: &str, window: u64) -> Tracker {
+        Tracker {
+            enabled: true,
+            window,
+            logs: vec![String::from(log)],
+            dpkg_info: String::from("test_package_info"),
+            positions: HashMap::new(),
+            stamps: HashMap::new(),
+            changes: Vec::new()
+        }
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_new() {
+        let config = config::Config::new(&utils::get_os(), None);
+        let tracker = Tracker::new(config);
+        assert!(!tracker.is_enabled());
+        assert_eq!(tracker.logs.len(), 3);
+        assert_eq!(tracker.dpkg_info, DPKG_INFO);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_parse_dpkg_line() {
+        assert_eq!(parse_dpkg_line("2023-05-10 10:00:00 upgrade bash:amd64 5.1-6 5.2-1"),
+            Some(Package { name: String::from("bash"), version: String::from("5.2-1") }));
+        assert_eq!(parse_dpkg_line("2023-05-10 10:00:00 remove vim:amd64 2:9.0 <none>"),
+            Some(Package { name: String::from("vim"), version: String::from("2:9.0") }));
+        assert_eq!(parse_dpkg_line("2023-05-10 10:00:00 status installed bash:amd64 5.2-1"), None);
+        assert_eq!(parse_dpkg_line("2023-05-10 10:00:00 startup archives unpack"), None);
+        assert_eq!(parse_dpkg_line(""), None);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_parse_rpm_line() {
+        assert_eq!(parse_rpm_line("2023-05-10T10:00:00+0000 SUBDEBUG Upgrade: bash-5.2.15-1.fc38.x86_64"),
+            Some(Package { name: String::from("bash"), version: String::from("5.2.15-1.fc38") }));
+        assert_eq!(parse_rpm_line("May 10 10:00:00 Updated: 1:openssl-libs-1.0.2k-26.el7.x86_64"),
+            Some(Package { name: String::from("openssl-libs"), version: String::from("1.0.2k-26.el7") }));
+        assert_eq!(parse_rpm_line("May 10 10:00:00 Erased: tzdata-2023c-1.el7.noarch"),
+            Some(Package { name: String::from("tzdata"), version: String::from("2023c-1.el7") }));
+        assert_eq!(parse_rpm_line("2023-05-10T10:00:00+0000 INFO --- logging initialized ---"), None);
+        assert_eq!(parse_rpm_line("May 10 10:00:00 Updated: broken"), None);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_lookup() {
+        let log = "test_package_dpkg.log";
+        fs::create_dir_all("test_package_info").unwrap();
+        fs::write("test_package_info/bash.list", "/.\n/usr\n/usr/bin\n/usr/bin/bash\n").unwrap();
+        fs::write("test_package_info/libtest:amd64.list", "/usr/lib/libtest.so.1\n").unwrap();
+        fs::write(log, "2023-05-10 10:00:00 upgrade bash:amd64 5.1-6 5.2-1\n").unwrap();
+        let mut tracker = create_test_tracker(log, 60);
+
+        let package = tracker.lookup("/usr/bin/bash", "", 100);
+        assert_eq!(package, Some(Package { name: String::from("bash"), version: String::from("5.2-1") }));
+        assert_eq!(tracker.lookup("/usr/bin/other", "", 100), None);
+        assert_eq!(tracker.lookup("/usr/bin/other", "dpkg", 100),
+            Some(Package { name: String::new(), version: String::new() }));
+
+        // Multiarch list file and partial lines
+        let mut file = fs::OpenOptions::new().append(true).open(log).unwrap();
+        write!(file, "2023-05-10 10:00:05 install libtest:amd64 <none> 1.0\n2023-05-10").unwrap();
+        assert_eq!(tracker.lookup("/usr/lib/libtest.so.1", "", 120).unwrap().name, "libtest");
+        assert_eq!(tracker.changes.len(), 2);
+        assert!(tracker.changes.iter().all(|c| ! c.rpm && c.files.is_some()));
+
+        // Unchanged logs are not read again
+        tracker.positions.insert(String::from(log), 0);
+        tracker.update(120);
+        assert_eq!(tracker.positions[log], 0);
+
+        // Transactions out of the window are forgotten
+        assert_eq!(tracker.lookup("/usr/bin/bash", "", 200), None);
+        assert!(tracker.changes.is_empty());
+        fs::remove_file(log).unwrap();
+        fs::remove_dir_all("test_package_info").unwrap();
+    }
+}
@@ -0,0 +1,208 @@
+// Copyright (C) 2023, Achiefs.
+
+// Global constants definitions
+// Minimum seconds between two saves of a changing state
+pub const SAVE_INTERVAL: u64 = 5;
+
+// To handle files
+use std::fs;
+use std::path::Path;
+// To limit the saves frequency
+use std::time::{Duration, Instant};
+// To log the program procedure
+use log::{debug, error, warn};
+// To handle JSON objects
+use serde_json::{json, to_string, Value};
+
+// ----------------------------------------------------------------------------
+
+// Read position of a log file, the inode and device identify the file
+// so a rotation can be detected after a restart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadState {
+    pub position: u64,
+    pub inode: u64,
+    pub dev: u64
+}
+
+impl ReadState {
+    // Load the saved state, None if it does not exist or it is not valid
+    pub fn load(file: &str) -> Option<Self> {
+        let contents = fs::read_to_string(file).ok()?;
+        let data: Value = match serde_json::from_str(&contents) {
+            Ok(value) => value,
+            Err(e) => {
+                warn!("Could not parse read state file '{}', error: {}", file, e);
+                return None
+            }
+        };
+        Some(ReadState {
+            position: data["position"].as_u64()?,
+            inode: data["inode"].as_u64()?,
+            dev: data["dev"].as_u64()?
+        })
+    }
+
+    // ------------------------------------------------------------------------
+
+    pub fn save(&self, file: &str) {
+        let data = json!({
+            "position": self.position,
+            "inode": self.inode,
+            "dev": self.dev
+        });
+        if let Some(parent) = Path::new(file).parent() {
+            if let Err(e) = fs::create_dir_all(parent) {
+                error!("Could not create read state folder, error: {}", e);
+            }
+        }
+        // Write and rename so a crash never leaves a partial state
+        let tmp = format!("{}.tmp", file);
+        match fs::write(&tmp, to_string(&data).unwrap()).and_then(|_| fs::rename(&tmp, file)) {
+            Ok(_) => debug!("Read state saved, position: {}", self.position),
+            Err(e) => error!("Could not save read state file '{}', error: {}", file, e)
+        }
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Check if the state belongs to the given file
+    pub fn is_file(&self, file: &str) -> bool {
+        let (inode, dev) = get_file_id(file);
+        inode != 0 && inode == self.inode && dev == self.dev
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Save a read state only when it changed since the last save and not more
+// often than the interval, the last state is written by flush at exit
+pub struct Throttle {
+    file: String,
+    interval: Duration,
+    saved: Option<(ReadState, Instant)>,
+    last: Option<ReadState>
+}
+
+impl Throttle {
+    pub fn new(file: &str, interval: u64) -> Self {
+        Throttle { file: String::from(file), interval: Duration::from_secs(interval),
+            saved: None, last: None }
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Returns if the state was written
+    pub fn save(&mut self, state: &ReadState) -> bool {
+        self.last = Some(*state);
+        match self.saved {
+            Some((saved, _)) if saved == *state => false,
+            Some((_, time)) if time.elapsed() < self.interval => false,
+            _ => {
+                state.save(&self.file);
+                self.saved = Some((*state, Instant::now()));
+                true
+            }
+        }
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Write the last state if it was not saved yet
+    pub fn flush(&mut self) {
+        if let Some(state) = self.last {
+            if self.saved.map(|(saved, _)| saved != state).unwrap_or(true) {
+                state.save(&self.file);
+                self.saved = Some((state, Instant::now()));
+            }
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Get the inode and device of a file, zero if they are not available
+#[cfg(unix)]
+pub fn get_file_id(file: &str) -> (u64, u64) {
+    use std::os::unix::fs::MetadataExt;
+    match fs::metadata(file) {
+        Ok(metadata) => (metadata.ino(), metadata.dev()),
+        Err(_) => (0, 0)
+    }
+}
+
+#[cfg(not(unix))]
+pub fn get_file_id(_file: &str) -> (u64, u64) {
+    (0, 0)
+}
+
+// ----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_load() {
+        let file = "test_readstate.state";
+        let state = ReadState { position: 845, inode: 12, dev: 2049 };
+        state.save(file);
+        assert_eq!(ReadState::load(file), Some(state));
+        fs::remove_file(file).unwrap();
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_throttle() {
+        let file = "test_readstate_throttle.state";
+        let mut throttle = Throttle::new(file, 0);
+        let mut state = ReadState { position: 10, inode: 12, dev: 2049 };
+        assert!(throttle.save(&state));
+        // Unchanged position
+        assert!(!throttle.save(&state));
+        state.position = 20;
+        assert!(throttle.save(&state));
+        assert_eq!(ReadState::load(file), Some(state));
+
+        // Changes inside the interval are saved later or at flush
+        let mut throttle = Throttle::new(file, SAVE_INTERVAL);
+        assert!(throttle.save(&state));
+        state.position = 30;
+        assert!(!throttle.save(&state));
+        assert_eq!(ReadState::load(file).unwrap().position, 20);
+        throttle.flush();
+        assert_eq!(ReadState::load(file).unwrap().position, 30);
+        fs::remove_file(file).unwrap();
+        // Nothing pending
+        throttle.flush();
+        assert!(!Path::new(file).exists());
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_load_invalid() {
+        let file = "test_readstate_invalid.state";
+        assert_eq!(ReadState::load(file), None);
+        fs::write(file, "{\"position\": 10}").unwrap();
+        assert_eq!(ReadState::load(file), None);
+        fs::write(file, "not json").unwrap();
+        assert_eq!(ReadState::load(file), None);
+        fs::remove_file(file).unwrap();
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_file() {
+        let (inode, dev) = get_file_id("test/unit/audit.log");
+        assert_ne!(inode, 0);
+        let state = ReadState { position: 0, inode, dev };
+        assert!(state.is_file("test/unit/audit.log"));
+        assert!(!state.is_file("test/unit/audit_interleaved.log"));
+        assert!(!state.is_file("test/unit/not_found.log"));
+        assert_eq!(get_file_id("test/unit/not_found.log"), (0, 0));
+    }
+}
@@ -0,0 +1,212 @@
+// Copyright (C) 2023, Achiefs.
+
+// To read audit log files, plain or gzip compressed
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use flate2::read::GzDecoder;
+// To manage date and time
+use time::{Date, Month, OffsetDateTime, PrimitiveDateTime, Time};
+// To log the program process
+use log::{debug, info, error};
+
+// To get configuration values
+use crate::config;
+// To parse audit records
+use crate::logreader;
+// To group the records of each audit event
+use crate::assembler::Assembler;
+// Audit events data
+use crate::auditevent;
+// Events severity and tags rules
+use crate::ruleset;
+// User and group names resolution
+use crate::identity;
+// To manage common functions
+use crate::utils;
+// To output the events
+use crate::fimevent::FimEvent;
+
+// ----------------------------------------------------------------------------
+
+// Open an audit log file, gzip files are detected by their magic number
+pub fn open_log(file: &str) -> std::io::Result<Box<dyn BufRead>> {
+    let mut magic = [0u8; 2];
+    let compressed = File::open(file)?.read(&mut magic)? == 2 && magic == [0x1f, 0x8b];
+    let reader = File::open(file)?;
+    if compressed {
+        Ok(Box::new(BufReader::new(GzDecoder::new(reader))))
+    }else{
+        Ok(Box::new(BufReader::new(reader)))
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Parse a time argument as unix seconds or UTC 'YYYY-MM-DD[( |T)HH:MM:SS]'
+pub fn parse_time(value: &str) -> Option<i64> {
+    if let Ok(seconds) = value.parse::<i64>() {
+        return Some(seconds)
+    }
+    let (date, clock) = match value.split_once(['T', ' ']) {
+        Some((date, clock)) => (date, clock.trim_end_matches('Z')),
+        None => (value, "00:00:00")
+    };
+    let date: Vec<&str> = date.split('-').collect();
+    let clock: Vec<&str> = clock.split(':').collect();
+    if date.len() != 3 || clock.len() != 3 { return None }
+    let date = Date::from_calendar_date(date[0].parse().ok()?,
+        Month::try_from(date[1].parse::<u8>().ok()?).ok()?, date[2].parse().ok()?).ok()?;
+    let clock = Time::from_hms(clock[0].parse().ok()?, clock[1].parse().ok()?,
+        clock[2].parse().ok()?).ok()?;
+    Some(PrimitiveDateTime::new(date, clock).assume_utc().unix_timestamp())
+}
+
+// ----------------------------------------------------------------------------
+
+// Check if the event time is inside the given range of seconds
+fn in_range(event: &auditevent::Event, from: Option<i64>, to: Option<i64>) -> bool {
+    let seconds = event.timestamp.parse::<i64>().unwrap_or(0) / 1000;
+    from.map(|f| seconds >= f).unwrap_or(true) && to.map(|t| seconds <= t).unwrap_or(true)
+}
+
+// ----------------------------------------------------------------------------
+
+// Run an audit log file through the parser, path matching and output of the
+// audit events, returns the number of produced events
+pub async fn replay(file: &str, from: Option<i64>, to: Option<i64>, config: config::Config) -> usize {
+    let reader = match open_log(file) {
+        Ok(reader) => reader,
+        Err(e) => {
+            error!("Could not open audit log '{}', error: {}", file, e);
+            return 0
+        }
+    };
+    info!("Replaying audit log: {}", file);
+    let destination = config.get_events_destination();
+    let rules = ruleset::Ruleset::new(&config.rules_file);
+    let mut identities = identity::Resolver::new();
+    let mut assembler = Assembler::new();
+    let mut events = Vec::new();
+    for line in reader.lines() {
+        match line {
+            Ok(line) => events.extend(logreader::read_line(line, config.clone(), &mut assembler)),
+            Err(e) => {
+                error!("Could not read audit log '{}', error: {}", file, e);
+                break;
+            }
+        }
+    }
+    // Events without end record at the end of the file
+    events.extend(assembler.flush().into_iter()
+        .filter_map(|data| logreader::build_event(data, config.clone())));
+
+    let mut produced = 0;
+    for mut audit_event in events {
+        if audit_event.is_empty() || ! in_range(&audit_event, from, to) { continue }
+        let index = config.get_audit_index(&audit_event.key,
+            audit_event.path.as_str(), audit_event.cwd.as_str());
+        let uid = utils::option_to_string(audit_event.uid);
+        let auid = utils::option_to_string(audit_event.auid);
+        if index == usize::MAX ||
+            config.match_ignore_process(index, &audit_event.exe, &audit_event.comm, &uid, &auid) ||
+            config.match_ignore(index, audit_event.file.as_str(), config.audit.clone()) {
+            debug!("Replayed event ignored: {:?}", audit_event);
+            continue;
+        }
+        let file = format!("{}/{}", audit_event.path, audit_event.file);
+        audit_event.uid_name = identities.get_user(audit_event.uid);
+        audit_event.euid_name = identities.get_user(audit_event.euid);
+        audit_event.auid_name = identities.get_user(audit_event.auid);
+        audit_event.ouid_name = identities.get_user(audit_event.ouid);
+        audit_event.gid_name = identities.get_group(audit_event.gid);
+        audit_event.egid_name = identities.get_group(audit_event.egid);
+        audit_event.ogid_name = identities.get_group(audit_event.ogid);
+        let rule_match = rules.evaluate(&ruleset::Subject {
+            path: &file,
+            operation: ruleset::get_audit_operation(&audit_event.operation, &audit_event.action),
+            detailed_operation: &audit_event.operation,
+            exe: &audit_event.exe,
+            uid: &uid,
+            auid: &auid,
+            file_type: &audit_event.file_type,
+            mode: &audit_event.mode
+        });
+        audit_event.severity = rule_match.severity;
+        audit_event.rule_id = rule_match.rule_id;
+        audit_event.rule_description = rule_match.rule_description;
+        // Keep the tags set by the event decoding
+        for tag in rule_match.tags {
+            if ! audit_event.tags.contains(&tag) { audit_event.tags.push(tag) }
+        }
+
+        // Events are stored in the index of the day they happened
+        let seconds = audit_event.timestamp.parse::<i64>().unwrap_or(0) / 1000;
+        let date = OffsetDateTime::from_unix_timestamp(seconds).unwrap_or(OffsetDateTime::UNIX_EPOCH);
+        let index_name = format!("fim-{}-{}-{}", date.year(), date.month() as u8, date.day());
+        audit_event.process(destination.as_str(), index_name, config.clone()).await;
+        produced += 1;
+    }
+    info!("Audit log replay finished, events: {}", produced);
+    produced
+}
+
+// ----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use tokio_test::block_on;
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time("1659026449"), Some(1659026449));
+        assert_eq!(parse_time("2022-07-28T16:40:49"), Some(1659026449));
+        assert_eq!(parse_time("2022-07-28 16:40:49"), Some(1659026449));
+        assert_eq!(parse_time("2022-07-28T16:40:49Z"), Some(1659026449));
+        assert_eq!(parse_time("2022-07-28"), Some(1658966400));
+        assert_eq!(parse_time("2022-13-28"), None);
+        assert_eq!(parse_time("yesterday"), None);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_open_log() {
+        let file = "test_replay_audit.log.gz";
+        let mut encoder = GzEncoder::new(fs::File::create(file).unwrap(), Compression::default());
+        encoder.write_all(&fs::read("test/unit/audit.log").unwrap()).unwrap();
+        encoder.finish().unwrap();
+        let lines: Vec<String> = open_log(file).unwrap().lines().map(|l| l.unwrap()).collect();
+        let plain: Vec<String> = open_log("test/unit/audit.log").unwrap().lines().map(|l| l.unwrap()).collect();
+        assert_eq!(lines, plain);
+        assert!(open_log("test/unit/not_found.log").is_err());
+        fs::remove_file(file).unwrap();
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_replay() {
+        let config = config::Config::new("linux", Some("test/unit/config/linux/replay.yml"));
+        let _ = fs::remove_file(&config.events_file);
+        assert_eq!(block_on(replay("test/unit/audit.log", None, None, config.clone())), 1);
+        let events = fs::read_to_string(&config.events_file).unwrap();
+        assert_eq!(events.lines().count(), 1);
+        assert!(events.contains("\"file\":\"sedTsutP7\""));
+        assert!(events.contains("\"@timestamp\":\"2022-07-28T16:40:49.689Z\""));
+        assert!(events.contains("\"serial\":\"6434\""));
+
+        // Time range
+        assert_eq!(block_on(replay("test/unit/audit.log", Some(1659026449), Some(1659026450), config.clone())), 1);
+        assert_eq!(block_on(replay("test/unit/audit.log", Some(1659026450), None, config.clone())), 0);
+        assert_eq!(block_on(replay("test/unit/audit.log", None, Some(1659026448), config.clone())), 0);
+        assert_eq!(block_on(replay("test/unit/not_found.log", None, None, config.clone())), 0);
+        fs::remove_file(&config.events_file).unwrap();
+        fs::remove_file(&config.events_sequence_file).unwrap();
+    }
+}
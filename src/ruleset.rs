@@ -0,0 +1,353 @@
+// Copyright (C) 2023, Achiefs.
+
+// Global constants definitions
+pub const SEVERITIES: [&str; 4] = ["low", "medium", "high", "critical"];
+
+// To parse files in yaml format
+use yaml_rust::yaml::Yaml;
+// To match paths with glob patterns
+use glob::Pattern;
+// To log the program process
+use log::{info, debug, error};
+// To check the rules file
+use std::path::Path;
+
+// To read the rules file
+use crate::config;
+
+// ----------------------------------------------------------------------------
+
+// Event attributes evaluated by the rules, empty when unknown
+pub struct Subject<'a> {
+    pub path: &'a str,
+    pub operation: &'a str,
+    pub detailed_operation: &'a str,
+    pub exe: &'a str,
+    pub uid: &'a str,
+    pub auid: &'a str,
+    pub file_type: &'a str,
+    pub mode: &'a str
+}
+
+// ----------------------------------------------------------------------------
+
+// Result of the rules evaluation over one event
+#[derive(Debug, PartialEq, Eq)]
+pub struct Match {
+    pub severity: String,
+    pub rule_id: String,
+    pub rule_description: String,
+    pub tags: Vec<String>
+}
+
+// ----------------------------------------------------------------------------
+
+pub struct Rule {
+    pub id: String,
+    pub description: String,
+    pub severity: String,
+    pub paths: Vec<Pattern>,
+    pub operations: Vec<String>,
+    pub exe: Vec<String>,
+    pub uid: Vec<String>,
+    pub auid: Vec<String>,
+    pub file_type: Vec<String>,
+    pub mode: Vec<String>,
+    pub tags: Vec<String>
+}
+
+impl Rule {
+    pub fn from(yaml: &Yaml) -> Result<Self, String> {
+        let id = match &yaml["id"] {
+            Yaml::Integer(value) => value.to_string(),
+            Yaml::String(value) => value.clone(),
+            _ => return Err(String::from("Rule without id found in rules file."))
+        };
+        let severity = String::from(yaml["severity"].as_str().unwrap_or("low"));
+        if ! SEVERITIES.contains(&severity.as_str()) {
+            return Err(format!("Rule '{}' has an invalid severity '{}'.", id, severity));
+        }
+        let paths = get_list(&yaml["path"]).iter().map(|p| Pattern::new(p)
+            .map_err(|e| format!("Rule '{}' has an invalid path pattern '{}': {}", id, p, e)))
+            .collect::<Result<Vec<Pattern>, String>>()?;
+
+        Ok(Rule {
+            description: String::from(yaml["description"].as_str().unwrap_or("")),
+            severity,
+            paths,
+            operations: get_list(&yaml["operation"]),
+            exe: get_list(&yaml["exe"]),
+            uid: get_list(&yaml["uid"]),
+            auid: get_list(&yaml["auid"]),
+            file_type: get_list(&yaml["file_type"]),
+            mode: get_list(&yaml["mode"]),
+            tags: get_list(&yaml["tags"]),
+            id
+        })
+    }
+
+    // ------------------------------------------------------------------------
+
+    // All the conditions set in the rule have to match, any value of each one
+    pub fn matches(&self, subject: &Subject) -> bool {
+        (self.paths.is_empty() || self.paths.iter().any(|p| p.matches(subject.path))) &&
+        (self.operations.is_empty() || self.operations.iter().any(|o| {
+            o == subject.operation || o == subject.detailed_operation })) &&
+        match_value(&self.exe, subject.exe) &&
+        match_value(&self.uid, subject.uid) &&
+        match_value(&self.auid, subject.auid) &&
+        match_value(&self.file_type, subject.file_type) &&
+        match_value(&self.mode, subject.mode)
+    }
+
+    // ------------------------------------------------------------------------
+
+    fn get_level(&self) -> usize {
+        SEVERITIES.iter().position(|s| *s == self.severity).unwrap_or(0)
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+pub struct Ruleset {
+    pub rules: Vec<Rule>
+}
+
+impl Ruleset {
+    pub fn new(file: &str) -> Self {
+        if file.is_empty() {
+            debug!("Rules file not set, events will not be evaluated");
+            return Ruleset { rules: Vec::new() }
+        }
+        if ! Path::new(file).is_file() {
+            error!("Rules file '{}' not found, events will not be evaluated", file);
+            return Ruleset { rules: Vec::new() }
+        }
+        let yaml = config::read_config(String::from(file));
+        // Invalid rules are skipped, the rest of the file is still loaded
+        let rules: Vec<Rule> = match yaml.first().and_then(|y| y["rules"].as_vec()) {
+            Some(rules) => rules.iter().filter_map(|r| match Rule::from(r) {
+                Ok(rule) => Some(rule),
+                Err(e) => {
+                    error!("{} Rule skipped.", e);
+                    None
+                }
+            }).collect(),
+            None => Vec::new()
+        };
+        info!("Loaded {} rules from: {}", rules.len(), file);
+        Ruleset { rules }
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Evaluate all rules, the most severe matched rule sets the severity
+    // while tags are gathered from all matched rules
+    pub fn evaluate(&self, subject: &Subject) -> Match {
+        let matched: Vec<&Rule> = self.rules.iter().filter(|r| r.matches(subject)).collect();
+        let mut tags: Vec<String> = Vec::new();
+        matched.iter().for_each(|r| r.tags.iter().for_each(|t| {
+            if ! tags.contains(t) { tags.push(t.clone()) }
+        }));
+        // First rule in file order wins between rules of the same severity
+        match matched.iter().rev().max_by_key(|r| r.get_level()) {
+            Some(rule) => {
+                debug!("Event matched rule '{}'", rule.id);
+                Match {
+                    severity: rule.severity.clone(),
+                    rule_id: rule.id.clone(),
+                    rule_description: rule.description.clone(),
+                    tags
+                }
+            },
+            None => Match {
+                severity: String::new(),
+                rule_id: String::new(),
+                rule_description: String::new(),
+                tags
+            }
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Operation of an audit event in the vocabulary of monitor events (CREATE,
+// WRITE and REMOVE) so the same rules match both. The PATH nametype is kept
+// as detailed operation.
+pub fn get_audit_operation(nametype: &str, action: &str) -> &'static str {
+    match (nametype, action) {
+        ("CREATE", _) => "CREATE",
+        ("DELETE", _) => "REMOVE",
+        (_, a) if a.starts_with("create") => "CREATE",
+        (_, a) if a.starts_with("unlink") => "REMOVE",
+        _ => "WRITE"
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Read a rule condition that could be a single value or a list of values
+fn get_list(yaml: &Yaml) -> Vec<String> {
+    match yaml {
+        Yaml::Array(values) => values.iter().filter_map(get_value).collect(),
+        _ => get_value(yaml).into_iter().collect()
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+fn get_value(yaml: &Yaml) -> Option<String> {
+    match yaml {
+        Yaml::String(value) => Some(value.clone()),
+        Yaml::Integer(value) => Some(value.to_string()),
+        Yaml::Real(value) => Some(value.clone()),
+        Yaml::Boolean(value) => Some(value.to_string()),
+        _ => None
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+fn match_value(values: &[String], value: &str) -> bool {
+    values.is_empty() || values.iter().any(|v| v == value)
+}
+
+// ----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_subject<'a>(path: &'a str, operation: &'a str, exe: &'a str, uid: &'a str) -> Subject<'a> {
+        Subject {
+            path, operation,
+            detailed_operation: "",
+            exe, uid,
+            auid: "1000",
+            file_type: "elf",
+            mode: "0100755"
+        }
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_new() {
+        let ruleset = Ruleset::new("test/unit/rules.yml");
+        assert_eq!(ruleset.rules.len(), 4);
+        assert_eq!(ruleset.rules[0].id, "100001");
+        assert_eq!(ruleset.rules[0].severity, "high");
+        assert_eq!(ruleset.rules[0].operations, vec!["WRITE", "CREATE", "REMOVE"]);
+        assert_eq!(ruleset.rules[0].tags, vec!["binaries", "integrity"]);
+        assert_eq!(ruleset.rules[1].uid, vec!["0"]);
+        assert_eq!(ruleset.rules[2].severity, "low");
+        assert!(Ruleset::new("").rules.is_empty());
+        assert_eq!(Ruleset::new("config/linux/rules.yml").rules.len(), 5);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_new_invalid_severity() {
+        let yaml = yaml_rust::YamlLoader::load_from_str("id: 1\nseverity: extreme").unwrap();
+        assert!(Rule::from(&yaml[0]).err().unwrap().contains("invalid severity"));
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_new_invalid() {
+        let yaml = yaml_rust::YamlLoader::load_from_str("description: no id").unwrap();
+        assert!(Rule::from(&yaml[0]).is_err());
+        let yaml = yaml_rust::YamlLoader::load_from_str("id: 2\npath: \"/tmp/[\"").unwrap();
+        assert!(Rule::from(&yaml[0]).err().unwrap().contains("invalid path pattern"));
+        assert!(Ruleset::new("test/unit/not_exists.yml").rules.is_empty());
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_matches() {
+        let ruleset = Ruleset::new("test/unit/rules.yml");
+        let rule = &ruleset.rules[0];
+        assert!(rule.matches(&create_test_subject("/usr/bin/ls", "WRITE", "", "")));
+        assert!(rule.matches(&create_test_subject("/bin/sub/ls", "CREATE", "", "")));
+        assert!(!rule.matches(&create_test_subject("/usr/bin/ls", "ACCESS", "", "")));
+        assert!(!rule.matches(&create_test_subject("/etc/passwd", "WRITE", "", "")));
+
+        let rule = &ruleset.rules[1];
+        assert!(rule.matches(&create_test_subject("/etc/shadow", "NORMAL", "/usr/bin/vim", "0")));
+        assert!(!rule.matches(&create_test_subject("/etc/shadow", "NORMAL", "/usr/bin/vim", "1000")));
+        assert!(!rule.matches(&create_test_subject("/etc/shadow", "NORMAL", "", "")));
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_evaluate() {
+        let ruleset = Ruleset::new("test/unit/rules.yml");
+        let result = ruleset.evaluate(&create_test_subject("/usr/bin/ls", "WRITE", "", ""));
+        assert_eq!(result, Match {
+            severity: String::from("high"),
+            rule_id: String::from("100001"),
+            rule_description: String::from("System binary changed"),
+            tags: vec![String::from("binaries"), String::from("integrity"), String::from("executable")]
+        });
+
+        let result = ruleset.evaluate(&create_test_subject("/etc/shadow", "NORMAL", "/usr/bin/vim", "0"));
+        assert_eq!(result.severity, "critical");
+        assert_eq!(result.rule_id, "shadow");
+        assert_eq!(result.tags, vec!["credentials", "executable"]);
+
+        let result = ruleset.evaluate(&Subject { path: "/var/log/syslog", operation: "WRITE",
+            detailed_operation: "MODIFY_DATA_CONTENT", exe: "", uid: "", auid: "",
+            file_type: "text", mode: "" });
+        assert_eq!(result, Match {
+            severity: String::new(),
+            rule_id: String::new(),
+            rule_description: String::new(),
+            tags: Vec::new()
+        });
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_matches_audit() {
+        // Audit events match the operations of monitor events
+        let ruleset = Ruleset::new("test/unit/rules.yml");
+        let rule = &ruleset.rules[0];
+        let subject = |nametype: &'static str, action: &'static str| Subject {
+            path: "/usr/bin/ls", operation: get_audit_operation(nametype, action),
+            detailed_operation: nametype, exe: "", uid: "", auid: "", file_type: "", mode: "" };
+        assert!(rule.matches(&subject("NORMAL", "open")));
+        assert!(rule.matches(&subject("CREATE", "create")));
+        assert!(rule.matches(&subject("DELETE", "unlink")));
+        assert!(ruleset.rules[1].matches(&create_test_subject("/etc/shadow", "WRITE", "/usr/bin/vim", "0")));
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_get_audit_operation() {
+        assert_eq!(get_audit_operation("CREATE", "open"), "CREATE");
+        assert_eq!(get_audit_operation("NORMAL", "create"), "CREATE");
+        assert_eq!(get_audit_operation("DELETE", "unlink"), "REMOVE");
+        assert_eq!(get_audit_operation("NORMAL", "unlink_denied"), "REMOVE");
+        assert_eq!(get_audit_operation("NORMAL", "chmod"), "WRITE");
+        assert_eq!(get_audit_operation("UNKNOWN", ""), "WRITE");
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_get_list() {
+        let yaml = yaml_rust::YamlLoader::load_from_str("a: [x, 1]\nb: y\nc: 2\nd: {}").unwrap();
+        assert_eq!(get_list(&yaml[0]["a"]), vec!["x", "1"]);
+        assert_eq!(get_list(&yaml[0]["b"]), vec!["y"]);
+        assert_eq!(get_list(&yaml[0]["c"]), vec!["2"]);
+        assert!(get_list(&yaml[0]["d"]).is_empty());
+        assert!(get_list(&yaml[0]["e"]).is_empty());
+    }
+}
@@ -0,0 +1,462 @@
+// Copyright (C) 2023, Achiefs.
+
+// Global constants definitions
+// Version of the events document, change it with any field addition or change
+pub const SCHEMA_VERSION: &str = "1.1";
+
+// To build JSON documents
+use serde_json::{json, Map, Value};
+// To read events files
+use std::fs;
+
+// To check events written in Elastic Common Schema format
+use crate::ecs;
+
+// ----------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    Keyword,
+    KeywordList,
+    Text,
+    Long,
+    OptionalLong,
+    OptionalFloat,
+    Boolean,
+    Date,
+    Nested,
+    // Nested objects with the given fields
+    TypedNested(&'static [(&'static str, FieldType)])
+}
+
+// Fields of each process of the 'process_ancestry' list
+const ANCESTRY_FIELDS: &[(&str, FieldType)] = &[
+    ("pid", FieldType::Long),
+    ("ppid", FieldType::Long),
+    ("exe", FieldType::Keyword),
+    ("uid", FieldType::OptionalLong)
+];
+
+// ----------------------------------------------------------------------------
+
+// Fields of the monitor events, audit events and storm alerts, all of them
+// are stored in the same 'fim-*' indices
+pub const FIELDS: &[(&str, FieldType)] = &[
+    // Common fields
+    ("id", FieldType::Keyword),
+    ("timestamp", FieldType::Date),
+    ("@timestamp", FieldType::Date),
+    ("schema_version", FieldType::Keyword),
+    ("sequence", FieldType::Long),
+    ("hostname", FieldType::Keyword),
+    ("node", FieldType::Keyword),
+    ("version", FieldType::Keyword),
+    ("fpid", FieldType::Long),
+    ("system", FieldType::Keyword),
+    ("operation", FieldType::Keyword),
+    ("severity", FieldType::Keyword),
+    ("file", FieldType::Keyword),
+    ("path", FieldType::Keyword),
+    ("labels", FieldType::KeywordList),
+    ("checksum", FieldType::Keyword),
+    ("entropy", FieldType::OptionalFloat),
+    // Type detected from the content magic bytes, i.e. "elf" or "jpeg"
+    ("file_type", FieldType::Keyword),
+    ("file_type_changed", FieldType::Boolean),
+    ("rule_id", FieldType::Keyword),
+    ("rule_description", FieldType::Text),
+    ("tags", FieldType::KeywordList),
+    ("expected", FieldType::Boolean),
+    ("package", FieldType::Keyword),
+    ("package_version", FieldType::Keyword),
+    ("command", FieldType::Keyword),
+    ("process", FieldType::Keyword),
+    ("pid", FieldType::OptionalLong),
+    // Monitor events
+    ("detailed_operation", FieldType::Keyword),
+    ("user", FieldType::Keyword),
+    // Audit events
+    ("source", FieldType::Keyword),
+    ("proctitle", FieldType::Keyword),
+    ("argv", FieldType::KeywordList),
+    ("cwd", FieldType::Keyword),
+    ("paths", FieldType::Nested),
+    ("item", FieldType::Keyword),
+    ("inode", FieldType::OptionalLong),
+    ("dev", FieldType::Keyword),
+    ("rdev", FieldType::Keyword),
+    ("mode", FieldType::Keyword),
+    // Type decoded from the audit mode, i.e. "regular" or "symlink". Named
+    // inode_type because file_type already holds the content type.
+    ("inode_type", FieldType::Keyword),
+    ("permissions", FieldType::Keyword),
+    ("setuid", FieldType::Boolean),
+    ("setgid", FieldType::Boolean),
+    ("sticky", FieldType::Boolean),
+    ("ouid", FieldType::OptionalLong),
+    ("ogid", FieldType::OptionalLong),
+    ("cap_fver", FieldType::Keyword),
+    ("cap_fp", FieldType::Keyword),
+    ("cap_fe", FieldType::Keyword),
+    ("cap_fi", FieldType::Keyword),
+    ("cap_frootid", FieldType::Keyword),
+    ("obj", FieldType::Keyword),
+    ("obj_user", FieldType::Keyword),
+    ("obj_role", FieldType::Keyword),
+    ("obj_type", FieldType::Keyword),
+    ("obj_level", FieldType::Keyword),
+    ("syscall", FieldType::Keyword),
+    ("syscall_name", FieldType::Keyword),
+    ("arch", FieldType::Keyword),
+    ("arch_name", FieldType::Keyword),
+    ("action", FieldType::Keyword),
+    ("success", FieldType::Keyword),
+    ("exit", FieldType::OptionalLong),
+    ("a0", FieldType::Keyword),
+    ("a1", FieldType::Keyword),
+    ("a2", FieldType::Keyword),
+    ("a3", FieldType::Keyword),
+    ("items", FieldType::OptionalLong),
+    ("ppid", FieldType::OptionalLong),
+    ("process_ancestry", FieldType::TypedNested(ANCESTRY_FIELDS)),
+    ("comm", FieldType::Keyword),
+    ("exe", FieldType::Keyword),
+    ("key", FieldType::Keyword),
+    ("tty", FieldType::Keyword),
+    ("ses", FieldType::OptionalLong),
+    ("uid", FieldType::OptionalLong),
+    ("euid", FieldType::OptionalLong),
+    ("suid", FieldType::OptionalLong),
+    ("fsuid", FieldType::OptionalLong),
+    ("auid", FieldType::OptionalLong),
+    ("gid", FieldType::OptionalLong),
+    ("egid", FieldType::OptionalLong),
+    ("sgid", FieldType::OptionalLong),
+    ("fsgid", FieldType::OptionalLong),
+    ("uid_name", FieldType::Keyword),
+    ("euid_name", FieldType::Keyword),
+    ("auid_name", FieldType::Keyword),
+    ("ouid_name", FieldType::Keyword),
+    ("gid_name", FieldType::Keyword),
+    ("egid_name", FieldType::Keyword),
+    ("ogid_name", FieldType::Keyword),
+    ("subj", FieldType::Keyword),
+    ("subj_user", FieldType::Keyword),
+    ("subj_role", FieldType::Keyword),
+    ("subj_type", FieldType::Keyword),
+    ("subj_level", FieldType::Keyword),
+    ("serial", FieldType::Keyword),
+    // Storm alerts
+    ("scope", FieldType::Keyword),
+    ("count", FieldType::Long),
+    ("window", FieldType::Long),
+    ("files", FieldType::KeywordList)
+];
+
+// Fields that every event contains
+const REQUIRED: [&str; 10] = ["id", "timestamp", "@timestamp", "schema_version", "sequence",
+    "hostname", "node", "version", "fpid", "operation"];
+
+// ----------------------------------------------------------------------------
+
+fn get_field_type(name: &str) -> Option<FieldType> {
+    FIELDS.iter().find(|(field, _)| *field == name).map(|(_, field_type)| *field_type)
+}
+
+// ----------------------------------------------------------------------------
+
+// Elasticsearch/OpenSearch mapping type of a field
+fn get_mapping(field_type: FieldType) -> Value {
+    let mapping = match field_type {
+        FieldType::Keyword | FieldType::KeywordList => "keyword",
+        FieldType::Text => "text",
+        FieldType::Long | FieldType::OptionalLong => "long",
+        FieldType::OptionalFloat => "float",
+        FieldType::Boolean => "boolean",
+        FieldType::Date => "date",
+        FieldType::Nested => "nested",
+        FieldType::TypedNested(fields) => return json!({
+            "type": "nested",
+            "properties": fields.iter()
+                .map(|(name, field_type)| (String::from(*name), get_mapping(*field_type)))
+                .collect::<Map<String, Value>>()
+        })
+    };
+    json!({ "type": mapping })
+}
+
+// ----------------------------------------------------------------------------
+
+// JSON Schema type of a field
+fn get_json_type(field_type: FieldType) -> Value {
+    match field_type {
+        FieldType::Keyword | FieldType::Text | FieldType::Date => json!({ "type": "string" }),
+        FieldType::KeywordList => json!({ "type": "array", "items": { "type": "string" } }),
+        FieldType::Long => json!({ "type": "integer" }),
+        FieldType::OptionalLong => json!({ "type": ["integer", "null"] }),
+        FieldType::OptionalFloat => json!({ "type": ["number", "null"] }),
+        FieldType::Boolean => json!({ "type": "boolean" }),
+        FieldType::Nested => json!({ "type": "array", "items": { "type": "object" } }),
+        FieldType::TypedNested(fields) => json!({ "type": "array", "items": {
+            "type": "object",
+            "properties": fields.iter()
+                .map(|(name, field_type)| (String::from(*name), get_json_type(*field_type)))
+                .collect::<Map<String, Value>>(),
+            "additionalProperties": false
+        } })
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Check if a serialized value matches the type of its field
+pub fn match_type(field_type: FieldType, value: &Value) -> bool {
+    match field_type {
+        FieldType::Keyword | FieldType::Text | FieldType::Date => value.is_string(),
+        FieldType::KeywordList => value.as_array()
+            .map(|a| a.iter().all(|v| v.is_string())).unwrap_or(false),
+        FieldType::Long => value.is_i64() || value.is_u64(),
+        FieldType::OptionalLong => value.is_null() || value.is_i64() || value.is_u64(),
+        FieldType::OptionalFloat => value.is_null() || value.is_number(),
+        FieldType::Boolean => value.is_boolean(),
+        FieldType::Nested => value.as_array()
+            .map(|a| a.iter().all(|v| v.is_object())).unwrap_or(false),
+        FieldType::TypedNested(fields) => value.as_array()
+            .map(|a| a.iter().all(|v| v.as_object().map(|object| object.iter().all(|(name, value)| {
+                fields.iter().any(|(field, field_type)| field == name && match_type(*field_type, value))
+            })).unwrap_or(false))).unwrap_or(false)
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Index template of the events, produced from the fields definition
+pub fn get_template() -> Value {
+    let fields: Vec<(String, FieldType)> = FIELDS.iter()
+        .map(|(name, field_type)| (String::from(*name), *field_type))
+        .collect();
+    build_template(&fields, json!({ "schema_version": SCHEMA_VERSION }))
+}
+
+// ----------------------------------------------------------------------------
+
+// Index template of the given fields, dotted names are mapped as objects
+pub fn build_template(fields: &[(String, FieldType)], meta: Value) -> Value {
+    let mut properties = Map::new();
+    for (name, field_type) in fields {
+        let mut parents: Vec<&str> = name.split('.').collect();
+        let leaf = parents.pop().unwrap();
+        let object = parents.iter().fold(&mut properties, |object, parent| {
+            object.entry(*parent).or_insert_with(|| json!({ "properties": {} }))
+                ["properties"].as_object_mut().unwrap()
+        });
+        object.insert(String::from(leaf), get_mapping(*field_type));
+    }
+    let default_field: Vec<&str> = fields.iter()
+        .filter(|(_, field_type)| matches!(field_type,
+            FieldType::Keyword | FieldType::KeywordList | FieldType::Text))
+        .map(|(name, _)| name.as_str())
+        .collect();
+    json!({
+        "order": 0,
+        "index_patterns": [ "fim-*" ],
+        "mappings": {
+            "_meta": meta,
+            "properties": properties
+        },
+        "settings": {
+            "index": {
+                "number_of_shards": "3",
+                "number_of_replicas": "0",
+                "auto_expand_replicas": "0-all",
+                "query": { "default_field": default_field }
+            }
+        }
+    })
+}
+
+// ----------------------------------------------------------------------------
+
+// JSON Schema document of the events, produced from the fields definition
+pub fn get_json_schema() -> Value {
+    let properties: Map<String, Value> = FIELDS.iter()
+        .map(|(name, field_type)| (String::from(*name), get_json_type(*field_type)))
+        .collect();
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$id": format!("https://github.com/Achiefs/fim/schema/{}/event.json", SCHEMA_VERSION),
+        "title": "FIM event",
+        "type": "object",
+        "properties": properties,
+        "required": REQUIRED,
+        "additionalProperties": false
+    })
+}
+
+// ----------------------------------------------------------------------------
+
+// Fields of a serialized event that are not defined or whose value does not
+// match their type, empty if the event follows the schema
+pub fn validate(event: &Value) -> Vec<String> {
+    let object = match event.as_object() {
+        Some(object) => object,
+        None => return vec![String::from("Event is not an object")]
+    };
+    let mut errors: Vec<String> = object.iter().filter_map(|(name, value)| {
+        match get_field_type(name) {
+            None => Some(format!("Field '{}' is not defined", name)),
+            Some(field_type) if ! match_type(field_type, value) =>
+                Some(format!("Field '{}' value {} is not {:?}", name, value, field_type)),
+            _ => None
+        }
+    }).collect();
+    errors.extend(REQUIRED.iter()
+        .filter(|name| ! object.contains_key(**name))
+        .map(|name| format!("Required field '{}' is missing", name)));
+    errors
+}
+
+// ----------------------------------------------------------------------------
+
+// Check every event of an events file, printing the errors found, returns
+// the number of invalid events
+pub fn validate_file(file: &str) -> usize {
+    let content = match fs::read_to_string(file) {
+        Ok(content) => content,
+        Err(e) => {
+            println!("[ERROR] Could not read events file '{}', error: {}", file, e);
+            return 0;
+        }
+    };
+    content.lines().enumerate().filter(|(_, line)| ! line.trim().is_empty())
+        .filter(|(number, line)| {
+            let errors = match serde_json::from_str::<Value>(line) {
+                Ok(event) if event.get("ecs").is_some() => ecs::validate(&event),
+                Ok(event) => validate(&event),
+                Err(e) => vec![format!("Event is not valid JSON, error: {}", e)]
+            };
+            for error in &errors {
+                println!("[ERROR] Line {}: {}", number + 1, error);
+            }
+            ! errors.is_empty()
+        }).count()
+}
+
+// ----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEMPLATE_FILE: &str = "config/index_template.json";
+    const SCHEMA_FILE: &str = "config/event_schema.json";
+
+    #[test]
+    fn test_fields_unique() {
+        for (name, _) in FIELDS {
+            assert_eq!(FIELDS.iter().filter(|(field, _)| field == name).count(), 1, "{}", name);
+        }
+        assert!(REQUIRED.iter().all(|name| get_field_type(name).is_some()));
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_get_template() {
+        let template = get_template();
+        assert_eq!(template["mappings"]["_meta"]["schema_version"], SCHEMA_VERSION);
+        assert_eq!(template["mappings"]["properties"]["pid"]["type"], "long");
+        assert_eq!(template["mappings"]["properties"]["paths"]["type"], "nested");
+        let ancestry = &template["mappings"]["properties"]["process_ancestry"];
+        assert_eq!(ancestry["type"], "nested");
+        assert_eq!(ancestry["properties"]["ppid"]["type"], "long");
+        assert_eq!(ancestry["properties"]["exe"]["type"], "keyword");
+        assert_eq!(template["mappings"]["properties"]["ses"]["type"], "long");
+        assert_eq!(template["mappings"]["properties"].as_object().unwrap().len(), FIELDS.len());
+        let default_field = template["settings"]["index"]["query"]["default_field"].as_array().unwrap();
+        assert!(default_field.contains(&json!("file")));
+        assert!(!default_field.contains(&json!("pid")));
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_build_template() {
+        let fields = vec![(String::from("file.path"), FieldType::Keyword),
+            (String::from("file.inode"), FieldType::Keyword),
+            (String::from("process.parent.pid"), FieldType::OptionalLong)];
+        let template = build_template(&fields, json!({}));
+        let properties = &template["mappings"]["properties"];
+        assert_eq!(properties["file"]["properties"]["path"]["type"], "keyword");
+        assert_eq!(properties["file"]["properties"]["inode"]["type"], "keyword");
+        assert_eq!(properties["process"]["properties"]["parent"]["properties"]["pid"]["type"], "long");
+        assert_eq!(template["settings"]["index"]["query"]["default_field"], json!(["file.path", "file.inode"]));
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_get_json_schema() {
+        let schema = get_json_schema();
+        assert_eq!(schema["properties"]["uid"]["type"], json!(["integer", "null"]));
+        assert_eq!(schema["properties"]["tags"]["items"]["type"], "string");
+        assert_eq!(schema["properties"]["process_ancestry"]["items"]["properties"]["uid"]["type"],
+            json!(["integer", "null"]));
+        assert_eq!(schema["properties"].as_object().unwrap().len(), FIELDS.len());
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Shipped files must be the generated ones, run 'fim schema' and
+    // 'fim schema --template' to write them again after changing the fields
+    #[test]
+    fn test_files_generated() {
+        let template: Value = serde_json::from_str(&fs::read_to_string(TEMPLATE_FILE).unwrap()).unwrap();
+        assert_eq!(template, get_template());
+        let schema: Value = serde_json::from_str(&fs::read_to_string(SCHEMA_FILE).unwrap()).unwrap();
+        assert_eq!(schema, get_json_schema());
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_validate() {
+        let event = json!({ "id": "1", "timestamp": "1", "@timestamp": "1970-01-01T00:00:00.001Z",
+            "schema_version": SCHEMA_VERSION, "sequence": 1, "hostname": "host", "node": "FIM",
+            "version": "0.4.6", "fpid": 100, "operation": "CREATE", "pid": null, "tags": ["a"] });
+        assert!(validate(&event).is_empty());
+
+        let mut wrong = event.clone();
+        wrong["uid"] = json!("0");
+        wrong["unknown"] = json!(1);
+        wrong.as_object_mut().unwrap().remove("sequence");
+        let errors = validate(&wrong);
+        assert_eq!(errors.len(), 3);
+
+        let mut ancestry = event.clone();
+        ancestry["process_ancestry"] = json!([{ "pid": 1, "ppid": 0, "exe": "/sbin/init", "uid": null }]);
+        assert!(validate(&ancestry).is_empty());
+        ancestry["process_ancestry"] = json!([{ "pid": "1" }]);
+        assert_eq!(validate(&ancestry).len(), 1);
+        ancestry["process_ancestry"] = json!([{ "unknown": 1 }]);
+        assert_eq!(validate(&ancestry).len(), 1);
+        assert!(errors.contains(&String::from("Field 'unknown' is not defined")));
+        assert!(errors.contains(&String::from("Required field 'sequence' is missing")));
+        assert_eq!(validate(&json!([])), vec![String::from("Event is not an object")]);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_validate_file() {
+        let file = "test_validate_file.json";
+        let event = json!({ "id": "1", "timestamp": "1", "@timestamp": "1970-01-01T00:00:00.001Z",
+            "schema_version": SCHEMA_VERSION, "sequence": 1, "hostname": "host", "node": "FIM",
+            "version": "0.4.6", "fpid": 100, "operation": "CREATE" });
+        fs::write(file, format!("{}\n{{\"id\": 1}}\nnot json\n{}\n", event,
+            ecs::from_event(&event))).unwrap();
+        assert_eq!(validate_file(file), 2);
+        fs::remove_file(file).unwrap();
+        assert_eq!(validate_file(file), 0);
+    }
+}
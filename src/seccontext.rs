@@ -0,0 +1,236 @@
+// Copyright (C) 2023, Achiefs.
+
+// Global constants definitions
+// Action of setxattr events that change file capabilities
+pub const CAPABILITY_ACTION: &str = "capability_change";
+// Extended attribute holding the file capabilities
+pub const CAPABILITY_XATTR: &str = "security.capability";
+const CAPABILITY_TOOLS: [&str; 1] = ["setcap"];
+// Size of the largest capabilities attribute (vfs_cap_data revision 3)
+const CAPABILITY_XATTR_SIZE: usize = 24;
+
+// To read the PATH record fields
+use std::collections::HashMap;
+
+// ----------------------------------------------------------------------------
+
+// Security context of an audit subject or object
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Context {
+    pub user: String,
+    pub role: String,
+    pub context_type: String,
+    pub level: String
+}
+
+// ----------------------------------------------------------------------------
+
+// Parse a SELinux 'user:role:type:level' label, the level could contain ':'
+// as in 's0-s0:c0.c1023'. AppArmor labels are profile names, their profile
+// is stored as type without the mode, like 'unconfined' or '/usr/bin/man (enforce)'
+pub fn parse_context(label: &str) -> Context {
+    let parts: Vec<&str> = label.splitn(4, ':').collect();
+    match parts.len() {
+        4 => Context {
+            user: String::from(parts[0]),
+            role: String::from(parts[1]),
+            context_type: String::from(parts[2]),
+            level: String::from(parts[3])
+        },
+        3 => Context {
+            user: String::from(parts[0]),
+            role: String::from(parts[1]),
+            context_type: String::from(parts[2]),
+            level: String::new()
+        },
+        _ => {
+            let profile = match label.rsplit_once(" (") {
+                Some((profile, mode)) if mode.ends_with(')') => profile,
+                _ => label
+            };
+            Context { context_type: String::from(profile.trim()), ..Default::default() }
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Audit does not log the name of the changed attribute. Successful setxattr
+// calls compare the capabilities before the call, in the PATH record, with
+// the current ones of the file. This is best-effort, a later change could be
+// read first. When the file cannot be read (replay, removed file) or the call
+// failed, they are capability changes when done by setcap or the command line
+// names the capability attribute.
+pub fn is_capability_change(action: &str, comm: &str, command: &str,
+    path: &HashMap<String, String>, file: &str) -> bool {
+    if ! action.starts_with("setxattr") {
+        return false
+    }
+    if action == "setxattr" {
+        if let (Some(previous), Some(current)) = (get_path_capabilities(path), get_file_capabilities(file)) {
+            return previous != current
+        }
+    }
+    CAPABILITY_TOOLS.contains(&comm) || command.contains(CAPABILITY_XATTR)
+}
+
+// ----------------------------------------------------------------------------
+
+// Permitted set, effective flag and revision of the cap_fp, cap_fe and
+// cap_fver fields of a PATH record, all of them are hex values
+fn get_path_capabilities(path: &HashMap<String, String>) -> Option<(u64, u32, u32)> {
+    let get = |field: &str| path.get(field).and_then(|v| u64::from_str_radix(v, 16).ok());
+    Some((get("cap_fp")?, get("cap_fe")? as u32, get("cap_fver")? as u32))
+}
+
+// ----------------------------------------------------------------------------
+
+// Capabilities of a file in the PATH record format, files without the
+// attribute have them cleared
+#[cfg(target_os = "linux")]
+fn get_file_capabilities(file: &str) -> Option<(u64, u32, u32)> {
+    use std::ffi::CString;
+    let path = CString::new(file).ok()?;
+    let name = CString::new(CAPABILITY_XATTR).ok()?;
+    let mut data = [0u8; CAPABILITY_XATTR_SIZE];
+    let size = unsafe {
+        libc::getxattr(path.as_ptr(), name.as_ptr(), data.as_mut_ptr() as *mut libc::c_void, data.len())
+    };
+    if size < 0 {
+        return match std::io::Error::last_os_error().raw_os_error() {
+            Some(libc::ENODATA) | Some(libc::EOPNOTSUPP) => Some((0, 0, 0)),
+            _ => None
+        }
+    }
+    parse_capabilities(&data[..size as usize])
+}
+
+#[cfg(not(target_os = "linux"))]
+fn get_file_capabilities(_file: &str) -> Option<(u64, u32, u32)> {
+    None
+}
+
+// ----------------------------------------------------------------------------
+
+// Decode a vfs_cap_data attribute, the revision and effective flag are in
+// the first word followed by the permitted and inheritable sets of each word
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_capabilities(data: &[u8]) -> Option<(u64, u32, u32)> {
+    let read = |offset: usize| data.get(offset..offset + 4)
+        .map(|v| u32::from_le_bytes([v[0], v[1], v[2], v[3]]));
+    let magic = read(0)?;
+    let revision = magic >> 24;
+    let low = read(4)? as u64;
+    let permitted = match revision {
+        1 => low,
+        _ => low | (read(12)? as u64) << 32
+    };
+    Some((permitted, magic & 1, revision))
+}
+
+// ----------------------------------------------------------------------------
+
+// Replace the setxattr action keeping its denied or failed suffix
+pub fn get_capability_action(action: &str) -> String {
+    action.replacen("setxattr", CAPABILITY_ACTION, 1)
+}
+
+// ----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_context() {
+        let context = parse_context("unconfined_u:unconfined_r:unconfined_t:s0-s0:c0.c1023");
+        assert_eq!(context.user, "unconfined_u");
+        assert_eq!(context.role, "unconfined_r");
+        assert_eq!(context.context_type, "unconfined_t");
+        assert_eq!(context.level, "s0-s0:c0.c1023");
+
+        let context = parse_context("system_u:object_r:shadow_t:s0");
+        assert_eq!(context.context_type, "shadow_t");
+        assert_eq!(context.level, "s0");
+        assert_eq!(parse_context("system_u:object_r:etc_t").level, "");
+
+        let context = parse_context("/usr/bin/man (enforce)");
+        assert_eq!(context.context_type, "/usr/bin/man");
+        assert_eq!(context.user, "");
+        assert_eq!(parse_context("unconfined").context_type, "unconfined");
+        assert_eq!(parse_context(""), Context::default());
+    }
+
+    // ------------------------------------------------------------------------
+
+    fn create_path(fp: &str, fe: &str, fver: &str) -> HashMap<String, String> {
+        HashMap::from([ (String::from("cap_fp"), String::from(fp)),
+            (String::from("cap_fe"), String::from(fe)),
+            (String::from("cap_fver"), String::from(fver)) ])
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_is_capability_change() {
+        let path = HashMap::new();
+        assert!(is_capability_change("setxattr", "setcap", "setcap cap_net_raw+ep /usr/bin/ping",
+            &path, "/tmp/fim_not_exists"));
+        assert!(is_capability_change("setxattr_denied", "setfattr",
+            "setfattr -n security.capability -v 0x01 /usr/bin/ping", &path, ""));
+        assert!(!is_capability_change("setxattr", "setfattr", "setfattr -n user.note -v a /tmp/a",
+            &path, ""));
+        assert!(!is_capability_change("chmod", "setcap", "setcap", &path, ""));
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_is_capability_change_file() {
+        // Files without capabilities, a removal of the attribute
+        let file = "test_is_capability_change";
+        std::fs::write(file, "").unwrap();
+        assert_eq!(get_file_capabilities(file), Some((0, 0, 0)));
+        assert!(!is_capability_change("setxattr", "setcap", "setcap -r test",
+            &create_path("0", "0", "0"), file));
+        assert!(is_capability_change("setxattr", "setfattr", "setfattr -x security.capability",
+            &create_path("0000000000002000", "1", "2"), file));
+        std::fs::remove_file(file).unwrap();
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_get_path_capabilities() {
+        assert_eq!(get_path_capabilities(&create_path("0000000000002000", "1", "2")),
+            Some((0x2000, 1, 2)));
+        assert_eq!(get_path_capabilities(&create_path("0", "0", "0")), Some((0, 0, 0)));
+        assert_eq!(get_path_capabilities(&HashMap::new()), None);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_parse_capabilities() {
+        // cap_net_raw+ep, revision 2
+        let mut data = Vec::new();
+        for word in [0x02000001u32, 0x2000, 0, 0, 0] {
+            data.extend(word.to_le_bytes());
+        }
+        assert_eq!(parse_capabilities(&data), Some((0x2000, 1, 2)));
+        assert_eq!(parse_capabilities(&data[..12]), None);
+        let data: Vec<u8> = [0x01000000u32, 0x400, 0].iter().flat_map(|w| w.to_le_bytes()).collect();
+        assert_eq!(parse_capabilities(&data), Some((0x400, 0, 1)));
+        assert_eq!(parse_capabilities(&[]), None);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_get_capability_action() {
+        assert_eq!(get_capability_action("setxattr"), "capability_change");
+        assert_eq!(get_capability_action("setxattr_denied"), "capability_change_denied");
+        assert_eq!(get_capability_action("setxattr_failed"), "capability_change_failed");
+    }
+}
@@ -0,0 +1,185 @@
+// Copyright (C) 2023, Achiefs.
+
+// Global constants definitions
+// Sequence numbers reserved with each write of the sequence file
+const SEQUENCE_BLOCK: u64 = 1000;
+// Seconds between errors of the sequence file
+const SEQUENCE_ERROR_INTERVAL: u64 = 60;
+
+// To read and write the sequence file
+use std::fs;
+// To share the sequence between all event types
+use std::sync::Mutex;
+// To rate limit the errors
+use std::time::{Duration, Instant};
+// To log the program process
+use log::{debug, error};
+
+// Last sequence number produced, loaded from file on first use
+static SEQUENCE: Mutex<Option<Sequence>> = Mutex::new(None);
+
+// ----------------------------------------------------------------------------
+
+// Monotonic event counter, 'file' stores the end of the reserved block of
+// numbers so numbering continues after it across restarts without writing
+// the file on each event. An empty file keeps it in memory.
+struct Sequence {
+    file: String,
+    value: u64,
+    reserved: u64,
+    block: u64,
+    last_error: Option<Instant>
+}
+
+impl Sequence {
+    fn load(file: &str) -> Self {
+        let value = match file.is_empty() {
+            true => 0,
+            false => fs::read_to_string(file).ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .unwrap_or(0)
+        };
+        debug!("Events sequence loaded, last reserved value: {}", value);
+        Sequence {
+            file: String::from(file),
+            value,
+            reserved: value,
+            block: SEQUENCE_BLOCK,
+            last_error: None
+        }
+    }
+
+    // ------------------------------------------------------------------------
+
+    fn next(&mut self) -> u64 {
+        self.value += 1;
+        if ! self.file.is_empty() && self.value > self.reserved {
+            self.reserved = self.value + self.block - 1;
+            self.store();
+        }
+        self.value
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Write the reserved block end, the numbers are still given in memory
+    // when it fails and the error is logged once per interval
+    fn store(&mut self) {
+        // Write and rename to not leave a truncated file on crashes
+        let temporal = format!("{}.tmp", self.file);
+        if let Err(e) = fs::write(&temporal, self.reserved.to_string())
+            .and_then(|_| fs::rename(&temporal, &self.file)) {
+            let interval = Duration::from_secs(SEQUENCE_ERROR_INTERVAL);
+            if self.last_error.map(|t| t.elapsed() >= interval).unwrap_or(true) {
+                error!("Could not store events sequence in '{}', error: {}", self.file, e);
+                self.last_error = Some(Instant::now());
+            }else{
+                debug!("Could not store events sequence in '{}', error: {}", self.file, e);
+            }
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Get the next sequence number of the host events
+pub fn next(file: &str) -> u64 {
+    let mut sequence = SEQUENCE.lock().unwrap();
+    if sequence.as_ref().map(|s| s.file != file).unwrap_or(true) {
+        *sequence = Some(Sequence::load(file));
+    }
+    sequence.as_mut().unwrap().next()
+}
+
+// ----------------------------------------------------------------------------
+
+// Get the serial of an audit record from 'audit(1659026449.689:6434):'
+pub fn get_audit_serial(msg: &str) -> String {
+    msg.split_once(':')
+        .map(|(_, serial)| serial.trim_end_matches([')', ':']))
+        .unwrap_or_default()
+        .to_string()
+}
+
+// ----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequence_persisted() {
+        let file = "test_sequence_persisted";
+        let _ = fs::remove_file(file);
+        let mut sequence = Sequence::load(file);
+        assert_eq!(sequence.next(), 1);
+        assert_eq!(sequence.next(), 2);
+        assert_eq!(fs::read_to_string(file).unwrap(), SEQUENCE_BLOCK.to_string());
+
+        // Restart continues after the reserved block
+        let mut sequence = Sequence::load(file);
+        assert_eq!(sequence.next(), SEQUENCE_BLOCK + 1);
+        assert_eq!(fs::read_to_string(file).unwrap(), (SEQUENCE_BLOCK * 2).to_string());
+        fs::remove_file(file).unwrap();
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_sequence_block() {
+        let file = "test_sequence_block";
+        let _ = fs::remove_file(file);
+        let mut sequence = Sequence::load(file);
+        sequence.block = 3;
+        assert_eq!(sequence.next(), 1);
+        fs::remove_file(file).unwrap();
+        // The file is only written when the block runs out
+        assert_eq!(sequence.next(), 2);
+        assert_eq!(sequence.next(), 3);
+        assert!(fs::metadata(file).is_err());
+        assert_eq!(sequence.next(), 4);
+        assert_eq!(fs::read_to_string(file).unwrap(), "6");
+        fs::remove_file(file).unwrap();
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_sequence_error() {
+        let mut sequence = Sequence::load("test_sequence_missing/sequence");
+        assert_eq!(sequence.next(), 1);
+        let first_error = sequence.last_error.unwrap();
+        sequence.block = 1;
+        assert_eq!(sequence.next(), 2);
+        assert_eq!(sequence.last_error, Some(first_error));
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_sequence_memory() {
+        let mut sequence = Sequence::load("");
+        assert_eq!(sequence.next(), 1);
+        assert_eq!(sequence.next(), 2);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_next() {
+        let file = "test_sequence_next";
+        let _ = fs::remove_file(file);
+        let first = next(file);
+        assert!(next(file) > first);
+        fs::remove_file(file).unwrap();
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_get_audit_serial() {
+        assert_eq!(get_audit_serial("audit(1659026449.689:6434):"), "6434");
+        assert_eq!(get_audit_serial("audit(1659026449.689:6434)"), "6434");
+        assert_eq!(get_audit_serial("audit"), "");
+    }
+}
@@ -0,0 +1,319 @@
+// Copyright (C) 2023, Achiefs.
+
+// Global constants definitions
+pub const STORM_OPERATION: &str = "STORM";
+pub const STORM_SEVERITY: &str = "high";
+const MAX_SAMPLES: usize = 5;
+
+// To implement Debug and fmt method
+use std::fmt;
+// To manage the sliding windows of changes
+use std::collections::{HashMap, VecDeque};
+// Event handling
+use notify::event::{EventKind, ModifyKind};
+// To log the program procedure
+use log::*;
+// To serialize the alerts
+use serde::Serialize;
+
+// To get configuration constants
+use crate::config;
+// To manage common functions
+use crate::utils;
+// To output the events
+use crate::fimevent::FimEvent;
+// To calculate the entropy of sampled files
+use crate::hash;
+
+// ----------------------------------------------------------------------------
+
+// Changes registered over a single root or process inside the time window
+struct Counter {
+    times: VecDeque<u128>,
+    samples: VecDeque<String>
+}
+
+// ----------------------------------------------------------------------------
+
+pub struct Detector {
+    threshold: usize,
+    process_threshold: usize,
+    window: u128,
+    entropy: bool,
+    read_limit: usize,
+    node: String,
+    system: String,
+    counters: HashMap<String, Counter>
+}
+
+impl Detector {
+    pub fn new(config: &config::Config) -> Self {
+        Detector {
+            threshold: config.storm_threshold,
+            process_threshold: config.storm_process_threshold,
+            window: u128::from(config.storm_window) * 1000,
+            entropy: config.storm_entropy,
+            read_limit: config.events_max_file_checksum,
+            node: config.node.clone(),
+            system: config.system.clone(),
+            counters: HashMap::new()
+        }
+    }
+
+    // ------------------------------------------------------------------------
+
+    pub fn is_enabled(&self) -> bool {
+        self.threshold > 0 || self.process_threshold > 0
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Register a change of 'file' under the monitored 'root' done by 'process'
+    // (empty if unknown) at 'timestamp' milliseconds. Returns the alerts
+    // produced by any threshold exceeded with this change.
+    pub fn record(&mut self, timestamp: u128, root: &str, process: &str, file: &str) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        if self.threshold > 0 && ! root.is_empty() {
+            if let Some(alert) = self.update("root", root, self.threshold, timestamp, file) {
+                alerts.push(alert);
+            }
+        }
+        if self.process_threshold > 0 && ! process.is_empty() {
+            if let Some(alert) = self.update("process", process, self.process_threshold, timestamp, file) {
+                alerts.push(alert);
+            }
+        }
+        alerts
+    }
+
+    // ------------------------------------------------------------------------
+
+    fn update(&mut self, scope: &str, subject: &str, threshold: usize, timestamp: u128, file: &str) -> Option<Alert> {
+        let window = self.window;
+        let counter = self.counters.entry(format!("{}:{}", scope, subject))
+            .or_insert(Counter { times: VecDeque::new(), samples: VecDeque::new() });
+
+        // Drop changes out of the time window
+        while counter.times.front().is_some_and(|t| timestamp.saturating_sub(*t) > window) {
+            counter.times.pop_front();
+        }
+        counter.times.push_back(timestamp);
+        if ! counter.samples.iter().any(|s| s == file) {
+            counter.samples.push_back(String::from(file));
+            if counter.samples.len() > MAX_SAMPLES { counter.samples.pop_front(); }
+        }
+
+        if counter.times.len() >= threshold {
+            let count = counter.times.len();
+            let files: Vec<String> = counter.samples.iter().cloned().collect();
+            // Start a new window so the alert is not repeated on every change
+            counter.times.clear();
+            counter.samples.clear();
+            warn!("Mass change detected on {} '{}', {} changes in {} seconds", scope, subject, count, window / 1000);
+
+            let entropy = if self.entropy {
+                let values: Vec<f64> = files.iter()
+                    .map(|f| hash::get_entropy(f.clone(), self.read_limit))
+                    .collect();
+                Some(values.iter().sum::<f64>() / values.len() as f64)
+            }else{ None };
+
+            Some(Alert {
+                id: utils::get_uuid(),
+                timestamp: format!("{}", timestamp),
+                iso_timestamp: utils::get_iso_timestamp(timestamp),
+                hostname: utils::get_hostname(),
+                node: self.node.clone(),
+                version: String::from(config::VERSION),
+                operation: String::from(STORM_OPERATION),
+                severity: String::from(STORM_SEVERITY),
+                scope: String::from(scope),
+                path: if scope == "root" { String::from(subject) }else{ String::new() },
+                process: if scope == "process" { String::from(subject) }else{ String::new() },
+                count,
+                window: (window / 1000) as u64,
+                files,
+                entropy,
+                fpid: utils::get_pid(),
+                system: self.system.clone(),
+                sequence: 0
+            })
+        }else{ None }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Returns if a monitor event kind has to be accounted as a change
+pub fn is_change(kind: EventKind) -> bool {
+    matches!(kind, EventKind::Create(_) | EventKind::Remove(_) |
+        EventKind::Modify(ModifyKind::Any) | EventKind::Modify(ModifyKind::Data(_)) |
+        EventKind::Modify(ModifyKind::Name(_)))
+}
+
+// ----------------------------------------------------------------------------
+
+#[derive(Serialize)]
+pub struct Alert {
+    pub id: String,
+    pub timestamp: String,
+    #[serde(rename = "@timestamp")]
+    pub iso_timestamp: String,
+    pub hostname: String,
+    pub node: String,
+    pub version: String,
+    pub operation: String,
+    pub severity: String,
+    pub scope: String,
+    pub path: String,
+    pub process: String,
+    pub count: usize,
+    pub window: u64,
+    pub files: Vec<String>,
+    pub entropy: Option<f64>,
+    pub fpid: u32,
+    pub system: String,
+    pub sequence: u64
+}
+
+impl FimEvent for Alert {
+    fn get_id(&self) -> &str { &self.id }
+
+    // ------------------------------------------------------------------------
+
+    fn set_sequence(&mut self, sequence: u64) { self.sequence = sequence }
+}
+
+// ----------------------------------------------------------------------------
+
+impl fmt::Debug for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        f.debug_tuple("")
+          .field(&self.id)
+          .field(&self.scope)
+          .field(&self.path)
+          .field(&self.process)
+          .field(&self.count)
+          .finish()
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, DataChange, RenameMode, AccessKind};
+    use std::fs;
+    use crate::schema;
+    use crate::ecs;
+
+    fn create_test_config(threshold: usize, process_threshold: usize, entropy: bool) -> config::Config {
+        let mut config = config::Config::new(&utils::get_os(), None);
+        config.storm_threshold = threshold;
+        config.storm_process_threshold = process_threshold;
+        config.storm_window = 10;
+        config.storm_entropy = entropy;
+        config
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_is_enabled() {
+        assert!(!Detector::new(&create_test_config(0, 0, false)).is_enabled());
+        assert!(Detector::new(&create_test_config(10, 0, false)).is_enabled());
+        assert!(Detector::new(&create_test_config(0, 10, false)).is_enabled());
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_record_root() {
+        let config = create_test_config(3, 0, false);
+        let mut detector = Detector::new(&config);
+        assert!(detector.record(1000, "/tmp", "", "/tmp/a").is_empty());
+        assert!(detector.record(2000, "/tmp", "", "/tmp/b").is_empty());
+        let alerts = detector.record(3000, "/tmp", "", "/tmp/c");
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].scope, "root");
+        assert_eq!(alerts[0].path, "/tmp");
+        assert_eq!(alerts[0].process, "");
+        assert_eq!(alerts[0].count, 3);
+        assert_eq!(alerts[0].window, 10);
+        assert_eq!(alerts[0].files, vec!["/tmp/a", "/tmp/b", "/tmp/c"]);
+        assert_eq!(alerts[0].severity, STORM_SEVERITY);
+        assert_eq!(alerts[0].entropy, None);
+        // Window restarted after the alert
+        assert!(detector.record(4000, "/tmp", "", "/tmp/d").is_empty());
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_record_window() {
+        let config = create_test_config(3, 0, false);
+        let mut detector = Detector::new(&config);
+        assert!(detector.record(1000, "/tmp", "", "/tmp/a").is_empty());
+        assert!(detector.record(2000, "/tmp", "", "/tmp/b").is_empty());
+        assert!(detector.record(20000, "/tmp", "", "/tmp/c").is_empty());
+        assert!(detector.record(21000, "/var", "", "/var/c").is_empty());
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_record_process() {
+        let config = create_test_config(0, 2, false);
+        let mut detector = Detector::new(&config);
+        assert!(detector.record(1000, "/tmp", "/usr/bin/sed", "/tmp/a").is_empty());
+        let alerts = detector.record(1500, "/var", "/usr/bin/sed", "/var/b");
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].scope, "process");
+        assert_eq!(alerts[0].process, "/usr/bin/sed");
+        assert_eq!(alerts[0].path, "");
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_record_entropy() {
+        let filename = "test_storm_entropy";
+        fs::write(filename, "aaaabbbb").unwrap();
+        let config = create_test_config(1, 0, true);
+        let mut detector = Detector::new(&config);
+        let alerts = detector.record(1000, "/tmp", "", filename);
+        assert_eq!(alerts[0].entropy, Some(1.0));
+        fs::remove_file(filename).unwrap();
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_is_change() {
+        assert!(is_change(EventKind::Create(CreateKind::File)));
+        assert!(is_change(EventKind::Modify(ModifyKind::Data(DataChange::Content))));
+        assert!(is_change(EventKind::Modify(ModifyKind::Name(RenameMode::Both))));
+        assert!(!is_change(EventKind::Access(AccessKind::Any)));
+        assert!(!is_change(EventKind::Any));
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_format_json() {
+        let config = create_test_config(1, 0, false);
+        let mut detector = Detector::new(&config);
+        let alert = detector.record(1000, "/tmp", "", "/tmp/a").pop().unwrap();
+        let json = alert.get_json();
+        assert_eq!(json["operation"], STORM_OPERATION);
+        assert_eq!(json["count"], 1);
+        assert_eq!(json["timestamp"], "1000");
+        assert_eq!(json["entropy"], serde_json::Value::Null);
+        assert!(alert.format_json(config::FIM_FORMAT).contains("\"scope\":\"root\""));
+        assert_eq!(schema::validate(&json), Vec::<String>::new());
+        let ecs = alert.get_output(config::ECS_FORMAT);
+        assert_eq!(ecs["event"]["kind"], "alert");
+        assert_eq!(ecs::validate(&ecs), Vec::<String>::new());
+    }
+}
@@ -20,6 +20,8 @@ use std::process::Command;
 use log::{warn, error, debug};
 // To manage maps
 use std::collections::HashMap;
+// To format event timestamps
+use time::OffsetDateTime;
 
 // ----------------------------------------------------------------------------
 
@@ -218,6 +220,23 @@ pub fn get_field(data: HashMap<String, String>, field_name: &str) -> String {
 
 // ----------------------------------------------------------------------------
 
+// Text of an optional number, empty if there is no value
+pub fn option_to_string<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+// ----------------------------------------------------------------------------
+
+// RFC 3339 UTC representation with milliseconds of unix epoch milliseconds
+pub fn get_iso_timestamp(millis: u128) -> String {
+    let nanos = i128::try_from(millis).unwrap_or(0) * 1_000_000;
+    let date = OffsetDateTime::from_unix_timestamp_nanos(nanos).unwrap_or(OffsetDateTime::UNIX_EPOCH);
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z", date.year(), date.month() as u8,
+        date.day(), date.hour(), date.minute(), date.second(), date.millisecond())
+}
+
+// ----------------------------------------------------------------------------
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -373,4 +392,20 @@ mod tests {
         }
     }
 
-}
\ No newline at end of file
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_option_to_string() {
+        assert_eq!(option_to_string(Some(1000)), "1000");
+        assert_eq!(option_to_string::<u32>(None), "");
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_get_iso_timestamp() {
+        assert_eq!(get_iso_timestamp(1659026449689), "2022-07-28T16:40:49.689Z");
+        assert_eq!(get_iso_timestamp(0), "1970-01-01T00:00:00.000Z");
+    }
+
+}
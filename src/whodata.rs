@@ -0,0 +1,154 @@
+// Copyright (C) 2023, Achiefs.
+
+// Global constants definitions
+// Audit key of the rules added for monitor paths with whodata enabled
+pub const WHODATA_KEY: &str = "fim_whodata";
+pub const WHODATA_PERMISSIONS: &str = "wa";
+// Milliseconds between an audit record and a notify event to join them
+pub const WHODATA_WINDOW: u128 = 2000;
+
+// To log the program process
+use log::debug;
+// To resolve relative audit paths
+use std::path::{Path, PathBuf};
+
+// ----------------------------------------------------------------------------
+
+// User and process that changed a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WhoData {
+    pub user: String,
+    pub process: String,
+    pub pid: Option<u32>,
+    pub command: String
+}
+
+// ----------------------------------------------------------------------------
+
+struct Entry {
+    file: String,
+    time: u128,
+    data: WhoData
+}
+
+// ----------------------------------------------------------------------------
+
+// Audit information of recent changes, joined to notify events by file and time
+pub struct Cache {
+    window: u128,
+    entries: Vec<Entry>
+}
+
+impl Cache {
+    pub fn new() -> Self {
+        Cache { window: WHODATA_WINDOW, entries: Vec::new() }
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Store the who-data of a file change at 'time' milliseconds
+    pub fn insert(&mut self, file: &str, time: u128, data: WhoData) {
+        self.expire(time);
+        self.entries.push(Entry { file: String::from(file), time, data });
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Get the who-data of the closest change of file around 'time' milliseconds
+    pub fn lookup(&mut self, file: &str, time: u128) -> Option<WhoData> {
+        self.expire(time);
+        let window = self.window;
+        let found = self.entries.iter()
+            .filter(|e| e.file == file && e.time.abs_diff(time) <= window)
+            .min_by_key(|e| e.time.abs_diff(time))
+            .map(|e| e.data.clone());
+        if found.is_none() {
+            debug!("Who-data of '{}' not found", file);
+        }
+        found
+    }
+
+    // ------------------------------------------------------------------------
+
+    pub fn pending(&self) -> usize {
+        self.entries.len()
+    }
+
+    // ------------------------------------------------------------------------
+
+    // Forget the changes that cannot be joined anymore
+    fn expire(&mut self, time: u128) {
+        let window = self.window;
+        self.entries.retain(|e| time.saturating_sub(e.time) <= window * 2);
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Absolute path of the file of an audit event, relative names are resolved
+// against the working directory of the process to match the notify paths
+pub fn get_file_path(path: &str, file: &str, cwd: &str) -> String {
+    let full: PathBuf = Path::new(cwd).join(path).join(file).components().collect();
+    String::from(full.to_string_lossy())
+}
+
+// ----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_whodata(pid: u32) -> WhoData {
+        WhoData {
+            user: String::from("alice"),
+            process: String::from("/usr/bin/vim"),
+            pid: Some(pid),
+            command: String::from("vim /etc/hosts")
+        }
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_lookup() {
+        let mut cache = Cache::new();
+        cache.insert("/etc/hosts", 10000, create_whodata(1));
+        cache.insert("/etc/hosts", 10800, create_whodata(2));
+        cache.insert("/etc/passwd", 10900, create_whodata(3));
+        assert_eq!(cache.lookup("/etc/hosts", 10100).unwrap().pid, Some(1));
+        assert_eq!(cache.lookup("/etc/hosts", 10700).unwrap().pid, Some(2));
+        assert_eq!(cache.lookup("/etc/hosts", 9500).unwrap().pid, Some(1));
+        assert_eq!(cache.lookup("/etc/group", 10000), None);
+        assert_eq!(cache.lookup("/etc/passwd", 13500), None);
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_get_file_path() {
+        assert_eq!(get_file_path("/etc", "hosts", "/tmp"), "/etc/hosts");
+        assert_eq!(get_file_path(".", "sedTsutP7", "/tmp"), "/tmp/sedTsutP7");
+        assert_eq!(get_file_path("./ssh", "config", "/etc"), "/etc/ssh/config");
+        assert_eq!(get_file_path("/etc", "", "/tmp"), "/etc");
+    }
+
+    // ------------------------------------------------------------------------
+
+    #[test]
+    fn test_expire() {
+        let mut cache = Cache::new();
+        cache.insert("/etc/hosts", 10000, create_whodata(1));
+        cache.insert("/etc/hosts", 13000, create_whodata(2));
+        assert_eq!(cache.pending(), 2);
+        cache.insert("/etc/hosts", 14500, create_whodata(3));
+        assert_eq!(cache.pending(), 2);
+    }
+}